@@ -8,16 +8,23 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/blkio-controller.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/blkio-controller.txt)
-use std::io::Write;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use log::*;
+use nix::unistd::{access, AccessFlags};
 
 use crate::fs::error::ErrorKind::*;
 use crate::fs::error::*;
 
-use crate::fs::{read_string_from, read_u64_from};
+use crate::fs::devices::resolve_block_device;
+use crate::fs::{parse_pressure, read_string_from, read_u64_from};
 use crate::fs::{
-    BlkIoResources, ControllIdentifier, ControllerInternal, Controllers, CustomizedAttribute,
-    Resources, Subsystem,
+    BlkIoDeviceResource, BlkIoDeviceThrottleResource, BlkIoResources, ControllIdentifier,
+    ControllerInternal, Controllers, CustomizedAttribute, MaxValue, Pressure, Resources, Subsystem,
 };
 
 /// A controller that allows controlling the `blkio` subsystem of a Cgroup.
@@ -65,7 +72,7 @@ pub struct IoService {
     pub total: u64,
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Debug, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Per-device activity from the control group.
 /// Only for cgroup v2
@@ -82,12 +89,240 @@ pub struct IoStat {
     pub rios: u64,
     /// How many iops were written to the device.
     pub wios: u64,
-    /// How many discard bytes were read from the device.
+    /// How many bytes were discarded (TRIM) on the device, 0 if the kernel doesn't report it.
+    pub dbytes: u64,
+    /// How many discard (TRIM) iops were issued to the device, 0 if the kernel doesn't report it.
+    pub dios: u64,
+}
+
+/// The activity of the control group summed over all the devices, from `io.stat` (cgroup v2
+/// only).
+#[derive(Eq, PartialEq, Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IoStatTotal {
+    /// How many bytes were read.
+    pub rbytes: u64,
+    /// How many bytes were written.
+    pub wbytes: u64,
+    /// How many read iops were issued.
+    pub rios: u64,
+    /// How many write iops were issued.
+    pub wios: u64,
+    /// How many bytes were discarded (TRIM).
     pub dbytes: u64,
-    /// How many discard iops were written to the device.
+    /// How many discard (TRIM) iops were issued.
     pub dios: u64,
 }
 
+fn io_stat_total(stats: &[IoStat]) -> IoStatTotal {
+    stats
+        .iter()
+        .fold(IoStatTotal::default(), |total, s| IoStatTotal {
+            rbytes: total.rbytes + s.rbytes,
+            wbytes: total.wbytes + s.wbytes,
+            rios: total.rios + s.rios,
+            wios: total.wios + s.wios,
+            dbytes: total.dbytes + s.dbytes,
+            dios: total.dios + s.dios,
+        })
+}
+
+/// An I/O control model, i.e. the knob used to share the block devices between control groups,
+/// see `BlkIoController::io_models()`.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IoModel {
+    /// Proportional weights, `io.weight` on v2 and `blkio.weight` (CFQ) on v1.
+    Weight,
+    /// Throttling limits, `io.max` on v2 and `blkio.throttle.*` on v1.
+    Max,
+    /// The `io.cost` model, enabled for a device in the root `io.cost.qos` and driven by the
+    /// `io.weight` of the control groups (cgroup v2 only).
+    Cost,
+    /// The weights of the BFQ I/O scheduler, `io.bfq.weight` on v2 and `blkio.bfq.weight` on v1.
+    Bfq,
+    /// None of the above is usable.
+    Unknown,
+}
+
+/// The limits of a device in `io.max` (cgroup v2 only), `None` meaning unlimited (`max`), as
+/// does `u64::MAX` when writing them.
+#[derive(Eq, PartialEq, Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IoMax {
+    /// Maximum bytes per second read from the device.
+    pub rbps: Option<u64>,
+    /// Maximum bytes per second written to the device.
+    pub wbps: Option<u64>,
+    /// Maximum read I/O operations per second on the device.
+    pub riops: Option<u64>,
+    /// Maximum write I/O operations per second on the device.
+    pub wiops: Option<u64>,
+}
+
+fn io_max_line(major: u64, minor: u64, max: &IoMax) -> String {
+    // u64::MAX is unlimited as well
+    let value = |v: Option<u64>| {
+        v.filter(|v| *v != u64::MAX)
+            .map_or_else(|| "max".to_string(), |v| v.to_string())
+    };
+    format!(
+        "{}:{} rbps={} wbps={} riops={} wiops={}",
+        major,
+        minor,
+        value(max.rbps),
+        value(max.wbps),
+        value(max.riops),
+        value(max.wiops)
+    )
+}
+
+fn io_latency_line(major: u64, minor: u64, target: MaxValue) -> String {
+    format!("{}:{} target={}", major, minor, target)
+}
+
+fn check_leaf_weight(weight: u64) -> Result<()> {
+    if !(10..=1000).contains(&weight) {
+        return Err(Error::new(InvalidArgument(format!(
+            "invalid leaf weight {}, must be in 10-1000",
+            weight
+        ))));
+    }
+    Ok(())
+}
+
+fn io_utilization(max: &IoMax, before: Option<IoStat>, after: Option<IoStat>, secs: f64) -> f64 {
+    let (before, after) = (before.unwrap_or_default(), after.unwrap_or_default());
+    let delta = |b: u64, a: u64| if a >= b { a - b } else { a };
+
+    let (mut bytes, mut cap) = (0, 0);
+    if let Some(rbps) = max.rbps {
+        bytes += delta(before.rbytes, after.rbytes);
+        cap += rbps;
+    }
+    if let Some(wbps) = max.wbps {
+        bytes += delta(before.wbytes, after.wbytes);
+        cap += wbps;
+    }
+    if cap == 0 || secs <= 0.0 {
+        return 0.0;
+    }
+    bytes as f64 / secs / cap as f64 * 100.0
+}
+
+// Parses the `major:minor value` lines of the per-device files, skipping the other ones such as
+// the `default` line of `io.bfq.weight`.
+fn parse_device_values(s: &str) -> Vec<(u64, u64, u64)> {
+    s.lines()
+        .filter_map(|line| {
+            let (device, value) = line.trim().split_once(' ')?;
+            let (major, minor) = device.split_once(':')?;
+            Some((
+                major.parse().ok()?,
+                minor.parse().ok()?,
+                value.trim().parse().ok()?,
+            ))
+        })
+        .collect()
+}
+
+fn parse_io_max(s: &str) -> Vec<(u64, u64, IoMax)> {
+    s.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (major, minor) = fields.next()?.split_once(':')?;
+            let mut max = IoMax::default();
+            for (key, value) in fields.filter_map(|f| f.split_once('=')) {
+                let value = value.parse().ok();
+                match key {
+                    "rbps" => max.rbps = value,
+                    "wbps" => max.wbps = value,
+                    "riops" => max.riops = value,
+                    "wiops" => max.wiops = value,
+                    _ => {}
+                }
+            }
+            Some((major.parse().ok()?, minor.parse().ok()?, max))
+        })
+        .collect()
+}
+
+fn device_weight(
+    devices: &mut Vec<BlkIoDeviceResource>,
+    major: u64,
+    minor: u64,
+) -> &mut BlkIoDeviceResource {
+    match devices
+        .iter()
+        .position(|d| d.major == major && d.minor == minor)
+    {
+        Some(i) => &mut devices[i],
+        None => {
+            devices.push(BlkIoDeviceResource {
+                major,
+                minor,
+                weight: None,
+                leaf_weight: None,
+            });
+            devices.last_mut().unwrap()
+        }
+    }
+}
+
+/// The statistics of the `io.latency` controller for a device, found in `io.stat` (cgroup v2
+/// only).
+///
+/// The keys vary across kernel versions, the well-known ones are exposed as fields, and all of
+/// them are kept in `raw`.
+#[derive(Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IoLatencyStat {
+    /// The major number of the device.
+    pub major: i16,
+    /// The minor number of the device.
+    pub minor: i16,
+    /// The current queue depth allowed to the control group.
+    pub depth: Option<u64>,
+    /// The running average IO latency, in microseconds.
+    pub avg_lat: Option<u64>,
+    /// The sampling window, in milliseconds, reported as `win` or `cur_win`.
+    pub cur_win: Option<u64>,
+    /// All the numeric `io.stat` keys of the device other than the generic IO counters.
+    pub raw: HashMap<String, u64>,
+}
+
+fn parse_io_latency_stat(s: &str) -> Vec<IoLatencyStat> {
+    const GENERIC_KEYS: [&str; 6] = ["rbytes", "wbytes", "rios", "wios", "dbytes", "dios"];
+    const LATENCY_KEYS: [&str; 4] = ["depth", "avg_lat", "win", "cur_win"];
+
+    s.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mut device = fields.next()?.split(':');
+            let major = device.next()?.parse::<i16>().ok()?;
+            let minor = device.next()?.parse::<i16>().ok()?;
+
+            let raw = fields
+                .filter_map(|f| f.split_once('='))
+                .filter(|(k, _)| !GENERIC_KEYS.contains(k))
+                .filter_map(|(k, v)| v.parse::<u64>().ok().map(|v| (k.to_string(), v)))
+                .collect::<HashMap<String, u64>>();
+            if !LATENCY_KEYS.iter().any(|k| raw.contains_key(*k)) {
+                return None;
+            }
+
+            Some(IoLatencyStat {
+                major,
+                minor,
+                depth: raw.get("depth").copied(),
+                avg_lat: raw.get("avg_lat").copied(),
+                cur_win: raw.get("cur_win").or_else(|| raw.get("win")).copied(),
+                raw,
+            })
+        })
+        .collect()
+}
+
 fn parse_io_service(s: String) -> Result<Vec<IoService>> {
     let mut io_services = Vec::<IoService>::new();
     let mut io_service = IoService::default();
@@ -147,36 +382,50 @@ fn parse_io_service(s: String) -> Result<Vec<IoService>> {
     Ok(io_services)
 }
 
-fn get_value(s: &str) -> String {
-    let arr = s.split(':').collect::<Vec<&str>>();
-    if arr.len() != 2 {
-        return "0".to_string();
-    }
-    arr[1].to_string()
+fn parse_io_stat(s: String) -> Vec<IoStat> {
+    s.lines()
+        .filter_map(parse_io_stat_line)
+        .collect::<Vec<IoStat>>()
 }
 
-fn parse_io_stat(s: String) -> Vec<IoStat> {
+fn parse_io_stat_line(line: &str) -> Option<IoStat> {
     // line:
     // 8:0 rbytes=180224 wbytes=0 rios=3 wios=0 dbytes=0 dios=0
-    s.lines()
-        .filter(|x| x.split_whitespace().count() == 7)
-        .map(|x| {
-            let arr = x.split_whitespace().collect::<Vec<&str>>();
-            let device = arr[0].split(':').collect::<Vec<&str>>();
-            let (major, minor) = (device[0], device[1]);
+    //
+    // The discard keys are only reported since Linux 5.0, and other controllers, like
+    // io.latency, may append their own keys.
+    let mut fields = line.split_whitespace();
+    let (major, minor) = fields.next()?.split_once(':')?;
+    let mut stat = IoStat {
+        major: major.parse::<i16>().ok()?,
+        minor: minor.parse::<i16>().ok()?,
+        ..Default::default()
+    };
 
-            IoStat {
-                major: major.parse::<i16>().unwrap(),
-                minor: minor.parse::<i16>().unwrap(),
-                rbytes: get_value(arr[1]).parse::<u64>().unwrap(),
-                wbytes: get_value(arr[2]).parse::<u64>().unwrap(),
-                rios: get_value(arr[3]).parse::<u64>().unwrap(),
-                wios: get_value(arr[4]).parse::<u64>().unwrap(),
-                dbytes: get_value(arr[5]).parse::<u64>().unwrap(),
-                dios: get_value(arr[6]).parse::<u64>().unwrap(),
-            }
-        })
-        .collect::<Vec<IoStat>>()
+    for (key, value) in fields.filter_map(|f| f.split_once('=')) {
+        let value = match value.parse::<u64>() {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        match key {
+            "rbytes" => stat.rbytes = value,
+            "wbytes" => stat.wbytes = value,
+            "rios" => stat.rios = value,
+            "wios" => stat.wios = value,
+            "dbytes" => stat.dbytes = value,
+            "dios" => stat.dios = value,
+            _ => {}
+        }
+    }
+
+    Some(stat)
+}
+
+fn parse_io_stat_for(s: &str, major: u64, minor: u64) -> Option<IoStat> {
+    let device = format!("{}:{} ", major, minor);
+    s.lines()
+        .find(|line| line.starts_with(&device))
+        .and_then(parse_io_stat_line)
 }
 
 fn parse_io_service_total(s: String) -> Result<u64> {
@@ -346,10 +595,59 @@ pub struct BlkIo {
     pub io_stat: Vec<IoStat>,
 }
 
+// The control files used by the controller on cgroup v1, see `Controller::managed_files()`.
+const MANAGED_FILES_V1: &[&str] = &[
+    "blkio.weight",
+    "blkio.weight_device",
+    "blkio.leaf_weight",
+    "blkio.leaf_weight_device",
+    "blkio.throttle.read_bps_device",
+    "blkio.throttle.read_iops_device",
+    "blkio.throttle.write_bps_device",
+    "blkio.throttle.write_iops_device",
+    "blkio.reset_stats",
+    "blkio.io_merged",
+    "blkio.io_merged_recursive",
+    "blkio.io_queued",
+    "blkio.io_queued_recursive",
+    "blkio.io_service_bytes",
+    "blkio.io_service_bytes_recursive",
+    "blkio.io_serviced",
+    "blkio.io_serviced_recursive",
+    "blkio.io_service_time",
+    "blkio.io_service_time_recursive",
+    "blkio.io_wait_time",
+    "blkio.io_wait_time_recursive",
+    "blkio.sectors",
+    "blkio.sectors_recursive",
+    "blkio.time",
+    "blkio.time_recursive",
+    "blkio.throttle.io_service_bytes",
+    "blkio.throttle.io_service_bytes_recursive",
+    "blkio.throttle.io_serviced",
+    "blkio.throttle.io_serviced_recursive",
+];
+
+// The control files used by the controller on cgroup v2.
+const MANAGED_FILES_V2: &[&str] = &[
+    "io.bfq.weight",
+    "io.max",
+    "io.latency",
+    "io.stat",
+    "io.pressure",
+];
+
 impl ControllerInternal for BlkIoController {
     fn control_type(&self) -> Controllers {
         Controllers::BlkIo
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        if self.is_v2() {
+            MANAGED_FILES_V2
+        } else {
+            MANAGED_FILES_V1
+        }
+    }
     fn get_path(&self) -> &PathBuf {
         &self.path
     }
@@ -406,6 +704,102 @@ impl ControllerInternal for BlkIoController {
 
         Ok(())
     }
+
+    fn reset(&self) -> Result<()> {
+        if self.v2 {
+            for (major, minor) in self.throttled_devices("io.max")? {
+                let content = format!("{}:{} rbps=max wbps=max riops=max wiops=max", major, minor);
+                self.write_file("io.max", &content)?;
+            }
+            return Ok(());
+        }
+
+        // a rate of 0 removes the rule of the device
+        for file_name in [
+            "blkio.throttle.read_bps_device",
+            "blkio.throttle.read_iops_device",
+            "blkio.throttle.write_bps_device",
+            "blkio.throttle.write_iops_device",
+        ] {
+            for (major, minor) in self.throttled_devices(file_name)? {
+                let content = format!("{}:{} 0", major, minor);
+                self.write_file(file_name, &content)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_resources(&self, res: &mut Resources) -> Result<()> {
+        let res = &mut res.blkio;
+        let read = |f: &str| self.read_if_exists::<String>(f);
+
+        if self.v2 {
+            if let Some(content) = read("io.bfq.weight")? {
+                res.weight = content
+                    .lines()
+                    .find_map(|l| l.strip_prefix("default "))
+                    .and_then(|w| w.trim().parse().ok());
+                for (major, minor, weight) in parse_device_values(&content) {
+                    device_weight(&mut res.weight_device, major, minor).weight =
+                        Some(weight as u16);
+                }
+            }
+            if let Some(content) = read("io.max")? {
+                for (major, minor, max) in parse_io_max(&content) {
+                    for (list, rate) in [
+                        (&mut res.throttle_read_bps_device, max.rbps),
+                        (&mut res.throttle_write_bps_device, max.wbps),
+                        (&mut res.throttle_read_iops_device, max.riops),
+                        (&mut res.throttle_write_iops_device, max.wiops),
+                    ] {
+                        if let Some(rate) = rate {
+                            list.push(BlkIoDeviceThrottleResource { major, minor, rate });
+                        }
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        res.weight = self.read_if_exists("blkio.weight")?;
+        res.leaf_weight = self.read_if_exists("blkio.leaf_weight")?;
+        if let Some(content) = read("blkio.weight_device")? {
+            for (major, minor, weight) in parse_device_values(&content) {
+                device_weight(&mut res.weight_device, major, minor).weight = Some(weight as u16);
+            }
+        }
+        if let Some(content) = read("blkio.leaf_weight_device")? {
+            for (major, minor, weight) in parse_device_values(&content) {
+                device_weight(&mut res.weight_device, major, minor).leaf_weight =
+                    Some(weight as u16);
+            }
+        }
+        for (file_name, list) in [
+            (
+                "blkio.throttle.read_bps_device",
+                &mut res.throttle_read_bps_device,
+            ),
+            (
+                "blkio.throttle.write_bps_device",
+                &mut res.throttle_write_bps_device,
+            ),
+            (
+                "blkio.throttle.read_iops_device",
+                &mut res.throttle_read_iops_device,
+            ),
+            (
+                "blkio.throttle.write_iops_device",
+                &mut res.throttle_write_iops_device,
+            ),
+        ] {
+            if let Some(content) = read(file_name)? {
+                list.extend(parse_device_values(&content).into_iter().map(
+                    |(major, minor, rate)| BlkIoDeviceThrottleResource { major, minor, rate },
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl ControllIdentifier for BlkIoController {
@@ -414,17 +808,15 @@ impl ControllIdentifier for BlkIoController {
     }
 }
 
-impl<'a> From<&'a Subsystem> for &'a BlkIoController {
-    fn from(sub: &'a Subsystem) -> &'a BlkIoController {
-        unsafe {
-            match sub {
-                Subsystem::BlkIo(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a BlkIoController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a BlkIoController> {
+        match sub {
+            Subsystem::BlkIo(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                BlkIoController::controller_type().to_string(),
+            ))),
         }
     }
 }
@@ -439,6 +831,174 @@ impl BlkIoController {
         }
     }
 
+    /// Returns the activity of the control group on each device, as found in `io.stat` (cgroup
+    /// v2 only).
+    pub fn io_stat(&self) -> Result<Vec<IoStat>> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "io.stat",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.open_path("io.stat", false)
+            .and_then(read_string_from)
+            .map(parse_io_stat)
+    }
+
+    /// Returns the activity of the control group from `io.stat` summed over all the devices,
+    /// discards being counted separately from reads and writes (cgroup v2 only).
+    pub fn io_stat_total(&self) -> Result<IoStatTotal> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "io.stat",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.open_path("io.stat", false)
+            .and_then(read_string_from)
+            .map(parse_io_stat)
+            .map(|stats| io_stat_total(&stats))
+    }
+
+    /// Returns the activity of a single device from `io.stat`, or `None` if the control group
+    /// didn't use it (cgroup v2 only).
+    pub fn io_stat_for(&self, major: u64, minor: u64) -> Result<Option<IoStat>> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "io.stat",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.open_path("io.stat", false)
+            .and_then(read_string_from)
+            .map(|s| parse_io_stat_for(&s, major, minor))
+    }
+
+    /// Returns the activity of the control group per device from `io.stat`, along with the name
+    /// of each device in `/dev` given by `devices::resolve_block_device()`, or `major:minor` for
+    /// the devices that can't be resolved (cgroup v2 only).
+    pub fn io_stat_named(&self) -> Result<Vec<(String, IoStat)>> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "io.stat",
+                "only available on cgroup v2",
+            ));
+        }
+
+        let stats = self
+            .open_path("io.stat", false)
+            .and_then(read_string_from)
+            .map(parse_io_stat)?;
+        Ok(stats
+            .into_iter()
+            .map(|stat| {
+                let name = resolve_block_device(stat.major as u64, stat.minor as u64)
+                    .unwrap_or_else(|| format!("{}:{}", stat.major, stat.minor));
+                (name, stat)
+            })
+            .collect())
+    }
+
+    /// Returns the throughput of the control group on the device `major`:`minor` over `window`
+    /// as a percentage of its `io.max` bandwidth cap, or `None` if no `rbps` or `wbps` cap is
+    /// set (cgroup v2 only).
+    ///
+    /// Only the directions with a cap count: with `rbps=X wbps=Y` the bytes read and written are
+    /// compared to `X + Y`, with only `rbps=X` the bytes read are compared to `X`. A counter that
+    /// went backwards, e.g. because the device was removed and added back, is counted from zero.
+    pub fn io_utilization(&self, major: u64, minor: u64, window: Duration) -> Result<Option<f64>> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "io.max",
+                "only available on cgroup v2",
+            ));
+        }
+
+        let max = self
+            .open_path("io.max", false)
+            .and_then(read_string_from)
+            .map(|s| parse_io_max(&s))?
+            .into_iter()
+            .find(|(ma, mi, _)| (*ma, *mi) == (major, minor))
+            .map(|(_, _, max)| max)
+            .unwrap_or_default();
+        if max.rbps.is_none() && max.wbps.is_none() {
+            return Ok(None);
+        }
+
+        let start = Instant::now();
+        let before = self.io_stat_for(major, minor)?;
+        std::thread::sleep(window);
+        let after = self.io_stat_for(major, minor)?;
+        let secs = start.elapsed().as_secs_f64();
+
+        Ok(Some(io_utilization(&max, before, after, secs)))
+    }
+
+    /// Returns the per-device, per-operation time in nanoseconds between request dispatch and
+    /// request completion for the IOs done by the control group (cgroup v1 only).
+    ///
+    /// Devices that the control group didn't use are omitted.
+    pub fn io_service_time(&self) -> Result<Vec<IoService>> {
+        self.io_service_v1("io_service_time")
+    }
+
+    /// Returns the per-device, per-operation time in nanoseconds the IOs of the control group
+    /// spent waiting in the scheduler queues for service (cgroup v1 only).
+    ///
+    /// Devices that the control group didn't use are omitted.
+    pub fn io_wait_time(&self) -> Result<Vec<IoService>> {
+        self.io_service_v1("io_wait_time")
+    }
+
+    // The statistic is exported by CFQ as `blkio.<name>` and by BFQ as `blkio.bfq.<name>`.
+    fn io_service_v1(&self, name: &str) -> Result<Vec<IoService>> {
+        if self.v2 {
+            return Err(Error::not_supported(
+                &format!("blkio.{}", name),
+                "only available on cgroup v1",
+            ));
+        }
+
+        let file = format!("blkio.{}", name);
+        let file = if self.get_path().join(&file).exists() {
+            file
+        } else {
+            format!("blkio.bfq.{}", name)
+        };
+        self.open_path(&file, false)
+            .and_then(read_string_from)
+            .and_then(parse_io_service)
+    }
+
+    /// Returns the statistics of the `io.latency` controller for the devices it throttles, found
+    /// in `io.stat` (cgroup v2 only).
+    ///
+    /// The kernel only reports them when `io.latency` is configured, and newer kernels only with
+    /// the `blkcg_debug_stats` module parameter of `blk_cgroup` set, otherwise the result is
+    /// empty. Fails with `NotSupported` if the kernel has no `io.latency` controller.
+    pub fn io_latency_debug(&self) -> Result<Vec<IoLatencyStat>> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "io.latency",
+                "only available on cgroup v2",
+            ));
+        }
+        if !self.get_path().join("io.latency").exists() {
+            return Err(Error::not_supported(
+                "io.latency",
+                "requires a kernel built with CONFIG_BLK_CGROUP_IOLATENCY",
+            ));
+        }
+
+        self.open_path("io.stat", false)
+            .and_then(read_string_from)
+            .map(|s| parse_io_latency_stat(&s))
+    }
+
     fn blkio_v2(&self) -> BlkIo {
         BlkIo {
             io_stat: self
@@ -682,47 +1242,104 @@ impl BlkIoController {
     }
 
     /// Set the leaf weight on the control group's tasks, i.e., how are they weighted against the
-    /// descendant control groups' tasks.
+    /// descendant control groups' tasks. The weight must be in the range 10-1000.
+    ///
+    /// Only the CFQ I/O scheduler of cgroup v1 has leaf weights, `NotSupported` is returned
+    /// otherwise, e.g. with BFQ or the blk-mq schedulers.
     pub fn set_leaf_weight(&self, w: u64) -> Result<()> {
-        self.open_path("blkio.leaf_weight", true)
-            .and_then(|mut file| {
-                file.write_all(w.to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("blkio.leaf_weight".to_string(), w.to_string()),
-                        e,
-                    )
-                })
-            })
+        let file_name = self.leaf_weight_file("blkio.leaf_weight")?;
+        check_leaf_weight(w)?;
+        self.write_file(file_name, &w.to_string())
     }
 
     /// Same as `set_leaf_weight()`, but settable per each block device.
     pub fn set_leaf_weight_for_device(&self, major: u64, minor: u64, weight: u64) -> Result<()> {
-        self.open_path("blkio.leaf_weight_device", true)
-            .and_then(|mut file| {
-                file.write_all(format!("{}:{} {}", major, minor, weight).as_ref())
-                    .map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed(
-                                "blkio.leaf_weight_device".to_string(),
-                                format!("{}:{} {}", major, minor, weight),
-                            ),
-                            e,
-                        )
-                    })
-            })
+        let file_name = self.leaf_weight_file("blkio.leaf_weight_device")?;
+        check_leaf_weight(weight)?;
+        self.write_file(file_name, &format!("{}:{} {}", major, minor, weight))
     }
 
-    /// Reset the statistics the kernel has gathered so far and start fresh.
+    /// Retrieve the leaf weight of the control group's tasks, see `set_leaf_weight()`.
+    pub fn leaf_weight(&self) -> Result<u64> {
+        let file_name = self.leaf_weight_file("blkio.leaf_weight")?;
+        self.open_path(file_name, false).and_then(read_u64_from)
+    }
+
+    /// Retrieve the per-device leaf weights of the control group's tasks, see
+    /// `set_leaf_weight_for_device()`.
+    pub fn leaf_weight_device(&self) -> Result<Vec<BlkIoData>> {
+        let file_name = self.leaf_weight_file("blkio.leaf_weight_device")?;
+        self.open_path(file_name, false)
+            .and_then(read_string_from)
+            .and_then(parse_blkio_data)
+    }
+
+    // The leaf weights are only exported by CFQ.
+    fn leaf_weight_file<'a>(&self, file_name: &'a str) -> Result<&'a str> {
+        if self.v2 {
+            return Err(Error::not_supported(
+                file_name,
+                "only available on cgroup v1",
+            ));
+        }
+        if !self.get_path().join(file_name).exists() {
+            return Err(Error::not_supported(
+                file_name,
+                "only available with the CFQ I/O scheduler",
+            ));
+        }
+        Ok(file_name)
+    }
+
+    /// Reset the statistics the kernel has gathered so far and start fresh, which for interval
+    /// accounting avoids diffing the counters.
+    ///
+    /// Only cgroup v1 supports resetting statistics: besides `blkio.reset_stats`, `cpuacct.usage`
+    /// (see `CpuAcctController::reset_usage()`) and the memory fail counters and maximum usage (see
+    /// `MemController::reset_fail_count()` and `MemController::reset_max_usage()`) can be
+    /// reset. Returns `NotSupported` on v2, whose counters can't be reset, and when the kernel
+    /// doesn't provide `blkio.reset_stats`.
     pub fn reset_stats(&self) -> Result<()> {
-        self.open_path("blkio.reset_stats", true)
-            .and_then(|mut file| {
-                file.write_all("1".to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("blkio.reset_stats".to_string(), "1".to_string()),
-                        e,
-                    )
-                })
+        if self.v2 {
+            return Err(Error::not_supported(
+                "blkio.reset_stats",
+                "only available on cgroup v1",
+            ));
+        }
+        if !self.get_path().join("blkio.reset_stats").exists() {
+            return Err(Error::not_supported(
+                "blkio.reset_stats",
+                "not provided by the kernel",
+            ));
+        }
+
+        self.write_file("blkio.reset_stats", "1")
+    }
+
+    // Returns the devices listed in the throttling file `file_name`, if it exists.
+    fn throttled_devices(&self, file_name: &str) -> Result<Vec<(u64, u64)>> {
+        if !self.get_path().join(file_name).exists() {
+            return Ok(vec![]);
+        }
+
+        let content = self
+            .open_path(file_name, false)
+            .and_then(read_string_from)?;
+        content
+            .lines()
+            .filter_map(|l| l.split_whitespace().next())
+            .map(|dev| {
+                let (major, minor) = dev.split_once(':').ok_or_else(|| Error::new(ParseError))?;
+                Ok((
+                    major
+                        .parse()
+                        .map_err(|e| Error::with_cause(ParseError, e))?,
+                    minor
+                        .parse()
+                        .map_err(|e| Error::with_cause(ParseError, e))?,
+                ))
             })
+            .collect()
     }
 
     /// Throttle the bytes per second rate of read operation affecting the block device
@@ -734,11 +1351,7 @@ impl BlkIoController {
             file_name = "io.max";
             content = format!("{}:{} rbps={}", major, minor, bps);
         }
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(content.as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed(file_name.to_string(), content.to_string()), e)
-            })
-        })
+        self.write_file(file_name, &content)
     }
 
     /// Throttle the I/O operations per second rate of read operation affecting the block device
@@ -750,11 +1363,7 @@ impl BlkIoController {
             file_name = "io.max";
             content = format!("{}:{} riops={}", major, minor, iops);
         }
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(content.as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed(file_name.to_string(), content.to_string()), e)
-            })
-        })
+        self.write_file(file_name, &content)
     }
     /// Throttle the bytes per second rate of write operation affecting the block device
     /// `major:minor` to `bps`.
@@ -765,11 +1374,7 @@ impl BlkIoController {
             file_name = "io.max";
             content = format!("{}:{} wbps={}", major, minor, bps);
         }
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(content.as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed(file_name.to_string(), content.to_string()), e)
-            })
-        })
+        self.write_file(file_name, &content)
     }
 
     /// Throttle the I/O operations per second rate of write operation affecting the block device
@@ -781,11 +1386,144 @@ impl BlkIoController {
             file_name = "io.max";
             content = format!("{}:{} wiops={}", major, minor, iops);
         }
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(content.as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed(file_name.to_string(), content.to_string()), e)
-            })
-        })
+        self.write_file(file_name, &content)
+    }
+
+    /// Returns the per-device limits on the bytes per second rate of read operations, found in
+    /// `blkio.throttle.read_bps_device` (cgroup v1 only, see `io_max()` on v2).
+    pub fn throttle_read_bps_device(&self) -> Result<Vec<BlkIoData>> {
+        self.throttle_limits_v1("blkio.throttle.read_bps_device")
+    }
+
+    /// Returns the per-device limits on the I/O operations per second rate of read operations,
+    /// found in `blkio.throttle.read_iops_device` (cgroup v1 only, see `io_max()` on v2).
+    pub fn throttle_read_iops_device(&self) -> Result<Vec<BlkIoData>> {
+        self.throttle_limits_v1("blkio.throttle.read_iops_device")
+    }
+
+    /// Returns the per-device limits on the bytes per second rate of write operations, found in
+    /// `blkio.throttle.write_bps_device` (cgroup v1 only, see `io_max()` on v2).
+    pub fn throttle_write_bps_device(&self) -> Result<Vec<BlkIoData>> {
+        self.throttle_limits_v1("blkio.throttle.write_bps_device")
+    }
+
+    /// Returns the per-device limits on the I/O operations per second rate of write operations,
+    /// found in `blkio.throttle.write_iops_device` (cgroup v1 only, see `io_max()` on v2).
+    pub fn throttle_write_iops_device(&self) -> Result<Vec<BlkIoData>> {
+        self.throttle_limits_v1("blkio.throttle.write_iops_device")
+    }
+
+    /// Returns the per-device, per-operation number of IOs seen by the throttling policy, found
+    /// in `blkio.throttle.io_serviced` (cgroup v1 only, see `io_stat()` on v2).
+    pub fn throttle_io_serviced(&self) -> Result<Vec<IoService>> {
+        self.throttle_stats_v1("blkio.throttle.io_serviced")
+    }
+
+    /// Returns the per-device, per-operation number of bytes seen by the throttling policy,
+    /// found in `blkio.throttle.io_service_bytes` (cgroup v1 only, see `io_stat()` on v2).
+    pub fn throttle_io_service_bytes(&self) -> Result<Vec<IoService>> {
+        self.throttle_stats_v1("blkio.throttle.io_service_bytes")
+    }
+
+    fn throttle_limits_v1(&self, file_name: &str) -> Result<Vec<BlkIoData>> {
+        if self.v2 {
+            return Err(Error::not_supported(
+                file_name,
+                "only available on cgroup v1",
+            ));
+        }
+        self.open_path(file_name, false)
+            .and_then(read_string_from)
+            .and_then(parse_blkio_data)
+    }
+
+    fn throttle_stats_v1(&self, file_name: &str) -> Result<Vec<IoService>> {
+        if self.v2 {
+            return Err(Error::not_supported(
+                file_name,
+                "only available on cgroup v1",
+            ));
+        }
+        self.open_path(file_name, false)
+            .and_then(read_string_from)
+            .and_then(parse_io_service)
+    }
+
+    /// Sets the `io.max` limits of the block device `major:minor`, replacing all four of them
+    /// (cgroup v2 only). See `set_io_qos()` to also set a latency target.
+    pub fn set_io_max(&self, major: u64, minor: u64, max: IoMax) -> Result<()> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "io.max",
+                "only available on cgroup v2",
+            ));
+        }
+
+        let content = io_max_line(major, minor, &max);
+        self.write_file("io.max", &content)
+    }
+
+    /// Returns the `io.max` limits of every throttled block device, as `(major, minor, limits)`
+    /// (cgroup v2 only).
+    pub fn io_max(&self) -> Result<Vec<(u64, u64, IoMax)>> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "io.max",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.open_path("io.max", false)
+            .and_then(read_string_from)
+            .map(|s| parse_io_max(&s))
+    }
+
+    /// Configures the `io.max` limits and the `io.latency` target (in microseconds) of the
+    /// block device `major:minor` together (cgroup v2 only).
+    ///
+    /// `None` leaves the corresponding setting untouched. `IoMax::default()` removes the limits
+    /// and `MaxValue::Max` the latency target, without touching the other one. Both can be set
+    /// on the same device, but the throttling of `io.max` then adds to the latency the control
+    /// group sees, and may keep `io.latency` from ever meeting the target while it throttles its
+    /// siblings, so a warning is logged. Fails with `NotSupported` if a latency target is given
+    /// and the kernel has no `io.latency` controller.
+    pub fn set_io_qos(
+        &self,
+        major: u64,
+        minor: u64,
+        max: Option<IoMax>,
+        latency_target: Option<MaxValue>,
+    ) -> Result<()> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "io.max and io.latency",
+                "only available on cgroup v2",
+            ));
+        }
+        if latency_target.is_some() && !self.get_path().join("io.latency").exists() {
+            return Err(Error::not_supported(
+                "io.latency",
+                "the kernel has no io.latency controller",
+            ));
+        }
+
+        if let (Some(max), Some(MaxValue::Value(_))) = (max, latency_target) {
+            if max != IoMax::default() {
+                warn!(
+                    "both io.max and io.latency are set on device {}:{}, the throttling may \
+                     keep the latency target from being met",
+                    major, minor
+                );
+            }
+        }
+
+        if let Some(max) = max {
+            self.write_file("io.max", &io_max_line(major, minor, &max))?;
+        }
+        if let Some(target) = latency_target {
+            self.write_file("io.latency", &io_latency_line(major, minor, target))?;
+        }
+        Ok(())
     }
 
     /// Set the weight of the control group's tasks.
@@ -795,11 +1533,7 @@ impl BlkIoController {
         if self.v2 {
             file_name = "io.bfq.weight";
         }
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(w.to_string().as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed(file_name.to_string(), w.to_string()), e)
-            })
-        })
+        self.write_file(file_name, &w.to_string())
     }
 
     /// Same as `set_weight()`, but settable per each block device.
@@ -811,27 +1545,98 @@ impl BlkIoController {
             // may depends on IO schedulers https://wiki.ubuntu.com/Kernel/Reference/IOSchedulers
             file_name = "io.bfq.weight";
         }
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(format!("{}:{} {}", major, minor, weight).as_ref())
-                .map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed(
-                            file_name.to_string(),
-                            format!("{}:{} {}", major, minor, weight),
-                        ),
-                        e,
-                    )
-                })
-        })
+        self.write_file(file_name, &format!("{}:{} {}", major, minor, weight))
     }
+
+    /// Returns the pressure stall information of the control group on I/O, from
+    /// `io.pressure` (cgroup v2 only).
+    ///
+    /// The file is missing when the kernel doesn't account pressure, see
+    /// `Cgroup::set_pressure_accounting()`.
+    pub fn pressure(&self) -> Result<Pressure> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "io.pressure",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.open_path("io.pressure", false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_pressure(&s))
+    }
+
+    /// Returns the I/O control models usable in the control group, i.e. whose control file
+    /// exists and is writable, in order of preference: `Cost`, `Weight`, `Bfq` and `Max`.
+    ///
+    /// `Cost` is only reported if it is enabled for at least one device in `io.cost.qos`, which
+    /// only exists in the root control group and is read from there.
+    pub fn io_models(&self) -> Vec<IoModel> {
+        let writable = |file: &str| access(&self.get_path().join(file), AccessFlags::W_OK).is_ok();
+        let mut models = vec![];
+        if self.v2 {
+            let iocost = self
+                .path
+                .ancestors()
+                .map(|dir| dir.join("io.cost.qos"))
+                .find(|p| p.exists())
+                .and_then(|p| fs::read_to_string(p).ok())
+                .is_some_and(|s| iocost_enabled(&s));
+            if iocost && writable("io.weight") {
+                models.push(IoModel::Cost);
+            }
+            if writable("io.weight") {
+                models.push(IoModel::Weight);
+            }
+            if writable("io.bfq.weight") {
+                models.push(IoModel::Bfq);
+            }
+            if writable("io.max") {
+                models.push(IoModel::Max);
+            }
+        } else {
+            if writable("blkio.weight") {
+                models.push(IoModel::Weight);
+            }
+            if writable("blkio.bfq.weight") {
+                models.push(IoModel::Bfq);
+            }
+            if writable("blkio.throttle.read_bps_device") {
+                models.push(IoModel::Max);
+            }
+        }
+        models
+    }
+
+    /// Returns the preferred I/O control model of `io_models()`, or `IoModel::Unknown` if none is
+    /// usable.
+    pub fn io_model(&self) -> IoModel {
+        self.io_models()
+            .first()
+            .copied()
+            .unwrap_or(IoModel::Unknown)
+    }
+}
+
+// Whether `io.cost.qos` enables the cost model for any device.
+fn iocost_enabled(qos: &str) -> bool {
+    qos.lines()
+        .any(|l| l.split_whitespace().any(|kv| kv == "enable=1"))
 }
 
 impl CustomizedAttribute for BlkIoController {}
 #[cfg(test)]
 mod test {
+    use crate::fs::blkio::{
+        io_latency_line, io_max_line, io_utilization, iocost_enabled, parse_device_values,
+        parse_io_max, IoMax,
+    };
+    use crate::fs::blkio::{io_stat_total, parse_io_stat, IoStatTotal};
     use crate::fs::blkio::{parse_blkio_data, BlkIoData};
+    use crate::fs::blkio::{parse_io_latency_stat, parse_io_stat_for, IoLatencyStat, IoStat};
     use crate::fs::blkio::{parse_io_service, parse_io_service_total, IoService};
     use crate::fs::error::*;
+    use crate::fs::MaxValue;
 
     static TEST_VALUE: &str = "\
 8:32 Read 4280320
@@ -865,6 +1670,50 @@ Total 61823067136
 8:0 559583764
  ";
 
+    #[test]
+    fn test_iocost_enabled() {
+        assert!(iocost_enabled(
+            "8:0 enable=1 ctrl=auto rpct=0.00 rlat=250000 wpct=0.00 wlat=250000 min=1.00 max=10000.00\n"
+        ));
+        assert!(!iocost_enabled(
+            "8:0 enable=0 ctrl=auto rpct=0.00 rlat=250000 wpct=0.00 wlat=250000 min=1.00 max=10000.00\n"
+        ));
+        assert!(!iocost_enabled(""));
+    }
+
+    #[test]
+    fn test_io_utilization() {
+        let stat = |rbytes, wbytes| {
+            Some(IoStat {
+                rbytes,
+                wbytes,
+                ..Default::default()
+            })
+        };
+        let both = IoMax {
+            rbps: Some(1000),
+            wbps: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(io_utilization(&both, stat(0, 0), stat(500, 500), 1.0), 50.0);
+        assert_eq!(io_utilization(&both, None, stat(1000, 1000), 2.0), 50.0);
+        // the counters restarted
+        assert_eq!(
+            io_utilization(&both, stat(5000, 0), stat(200, 0), 1.0),
+            10.0
+        );
+
+        let read_only = IoMax {
+            rbps: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(
+            io_utilization(&read_only, stat(0, 0), stat(250, 9000), 1.0),
+            25.0
+        );
+        assert_eq!(io_utilization(&read_only, None, None, 1.0), 0.0);
+    }
+
     #[test]
     fn test_parse_io_service_total() {
         let ok = parse_io_service_total(TEST_VALUE.to_string()).unwrap();
@@ -972,4 +1821,149 @@ b:32 Write 1
             ]
         );
     }
+
+    #[test]
+    fn test_parse_io_stat_for() {
+        let content = "\
+8:16 rbytes=1 wbytes=2 rios=3 wios=4 dbytes=5 dios=6
+8:0 rbytes=180224 wbytes=0 rios=3 wios=0 dbytes=0 dios=0
+";
+        assert_eq!(
+            parse_io_stat_for(content, 8, 0),
+            Some(IoStat {
+                major: 8,
+                minor: 0,
+                rbytes: 180224,
+                wbytes: 0,
+                rios: 3,
+                wios: 0,
+                dbytes: 0,
+                dios: 0,
+            })
+        );
+        assert_eq!(parse_io_stat_for(content, 8, 1), None);
+        assert_eq!(parse_io_stat_for(content, 8, 32), None);
+    }
+
+    #[test]
+    fn test_io_stat_total() {
+        let content = "\
+8:16 rbytes=1 wbytes=2 rios=3 wios=4 dbytes=5 dios=6
+8:0 rbytes=180224 wbytes=0 rios=3 wios=0 dbytes=4096 dios=1 use_delay=0 depth=64
+8:32 rbytes=10 wbytes=20 rios=1 wios=2
+"
+        .to_string();
+        let stats = parse_io_stat(content);
+        assert_eq!(stats.len(), 3);
+        // kernels older than 5.0 don't report discards
+        assert_eq!(stats[2].dbytes, 0);
+        assert_eq!(stats[2].dios, 0);
+        assert_eq!(
+            io_stat_total(&stats),
+            IoStatTotal {
+                rbytes: 180235,
+                wbytes: 22,
+                rios: 7,
+                wios: 6,
+                dbytes: 4101,
+                dios: 7,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_io_latency_stat() {
+        let content = "\
+8:16 rbytes=1 wbytes=2 rios=3 wios=4 dbytes=5 dios=6
+8:0 rbytes=180224 wbytes=0 rios=3 wios=0 dbytes=0 dios=0 use_delay=0 delay_nsec=0 depth=64 avg_lat=1200 win=100
+259:0 rbytes=1 wbytes=2 rios=3 wios=4 dbytes=5 dios=6 missed=1 total=10 depth=1 avg_lat=300 cur_win=250
+";
+        let stats = parse_io_latency_stat(content);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(
+            stats[0],
+            IoLatencyStat {
+                major: 8,
+                minor: 0,
+                depth: Some(64),
+                avg_lat: Some(1200),
+                cur_win: Some(100),
+                raw: [
+                    ("use_delay", 0),
+                    ("delay_nsec", 0),
+                    ("depth", 64),
+                    ("avg_lat", 1200),
+                    ("win", 100),
+                ]
+                .iter()
+                .map(|(k, v)| (k.to_string(), *v))
+                .collect(),
+            }
+        );
+        assert_eq!((stats[1].major, stats[1].minor), (259, 0));
+        assert_eq!(stats[1].cur_win, Some(250));
+        assert_eq!(stats[1].raw.get("missed"), Some(&1));
+
+        // the generic counters are still parsed with the extra keys
+        assert_eq!(parse_io_stat_for(content, 8, 0).unwrap().rbytes, 180224);
+    }
+
+    #[test]
+    fn test_io_qos_lines() {
+        // both set
+        let max = IoMax {
+            rbps: Some(1048576),
+            wiops: Some(120),
+            ..Default::default()
+        };
+        assert_eq!(
+            io_max_line(8, 16, &max),
+            "8:16 rbps=1048576 wbps=max riops=max wiops=120"
+        );
+        assert_eq!(
+            io_latency_line(8, 16, MaxValue::Value(75)),
+            "8:16 target=75"
+        );
+
+        // one cleared
+        assert_eq!(
+            io_max_line(8, 16, &IoMax::default()),
+            "8:16 rbps=max wbps=max riops=max wiops=max"
+        );
+        assert_eq!(io_latency_line(8, 16, MaxValue::Max), "8:16 target=max");
+
+        // u64::MAX is unlimited
+        let max = IoMax {
+            rbps: Some(u64::MAX),
+            wbps: Some(4096),
+            ..Default::default()
+        };
+        assert_eq!(
+            io_max_line(8, 16, &max),
+            "8:16 rbps=max wbps=4096 riops=max wiops=max"
+        );
+    }
+
+    #[test]
+    fn test_parse_device_values() {
+        assert_eq!(
+            parse_device_values("default 100\n8:0 200\n8:16 50\n"),
+            vec![(8, 0, 200), (8, 16, 50)]
+        );
+        assert!(parse_device_values("").is_empty());
+
+        let max = parse_io_max("8:16 rbps=2097152 wbps=max riops=max wiops=120\n");
+        assert_eq!(
+            max,
+            vec![(
+                8,
+                16,
+                IoMax {
+                    rbps: Some(2097152),
+                    wiops: Some(120),
+                    ..Default::default()
+                }
+            )]
+        );
+    }
 }
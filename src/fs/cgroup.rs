@@ -6,22 +6,151 @@
 
 //! This module handles cgroup operations. Start here!
 
+use log::warn;
+use nix::unistd::{access, chown, AccessFlags, Gid, Uid};
+
 use crate::fs::error::ErrorKind::*;
 use crate::fs::error::*;
 
+use crate::fs::cpu::CpuController;
+use crate::fs::devices::devices_not_supported;
+use crate::fs::events;
 use crate::fs::hierarchies::V1;
-use crate::fs::{CgroupPid, ControllIdentifier, Controller, Hierarchy, Resources, Subsystem};
-
-use std::collections::HashMap;
-use std::convert::From;
+use crate::fs::memory::MemController;
+use crate::fs::pid::PidController;
+use crate::fs::{
+    cache_handles, is_root_path, parse_max_value, CgroupPid, ControllIdentifier, Controller,
+    Controllers, Hierarchy, MaxValue, PidIter, Resources, Subsystem,
+};
+
+use std::collections::{HashMap, HashSet};
+use std::convert::{From, TryFrom, TryInto};
+use std::fmt;
 use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub const CGROUP_MODE_DOMAIN: &str = "domain";
 pub const CGROUP_MODE_DOMAIN_THREADED: &str = "domain threaded";
 pub const CGROUP_MODE_DOMAIN_INVALID: &str = "domain invalid";
 pub const CGROUP_MODE_THREADED: &str = "threaded";
 
+// The inode number of the initial cgroup namespace, `CGROUP_NS_INIT_INO` in the kernel.
+const CGROUP_NS_INIT_INO: u64 = 0xEFFF_FFFB;
+
+// The files given to the delegatee by `delegate_to()` when the kernel doesn't list them.
+const DELEGATE_FILES: &[&str] = &["cgroup.procs", "cgroup.threads", "cgroup.subtree_control"];
+
+// How many times `move_all_tasks_to()` reads the processes left in the source control group.
+const MOVE_ALL_TASKS_RETRIES: usize = 10;
+
+/// How many more times `Cgroup::procs_snapshot()` reads the processes when they changed while
+/// being read.
+pub const PROCS_SNAPSHOT_RETRIES: usize = 3;
+
+/// The statistics found in `cgroup.stat` on cgroup v2.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct CgroupStat {
+    /// Total number of visible descendant control groups.
+    pub nr_descendants: u64,
+    /// Total number of dying descendant control groups. A control group is dying after being
+    /// deleted, until the kernel is done releasing its resources.
+    pub nr_dying_descendants: u64,
+    /// Number of live control groups, this one included, in which each controller is enabled.
+    ///
+    /// Only reported by recent kernels, empty otherwise.
+    pub nr_subsys: HashMap<Controllers, u64>,
+    /// Number of dying control groups, this one included, in which each controller is enabled.
+    ///
+    /// Only reported by recent kernels, empty otherwise.
+    pub nr_dying_subsys: HashMap<Controllers, u64>,
+}
+
+/// The state found in `cgroup.events` on cgroup v2, see `Cgroup::events()`.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct CgroupEvents {
+    /// The control group or one of its descendants has live processes.
+    pub populated: bool,
+    /// The control group is frozen.
+    pub frozen: bool,
+}
+
+/// Whether a control group is frozen, see `Cgroup::freeze_status()`.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct FreezeStatus {
+    /// The control group itself was asked to freeze.
+    pub self_requested: bool,
+    /// The processes of the control group are frozen, either because the control group itself
+    /// or one of its ancestors was asked to freeze.
+    pub effectively_frozen: bool,
+}
+
+/// The processes of a control group, see `Cgroup::procs_snapshot()`.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct ProcsSnapshot {
+    /// The processes found during the last read of `cgroup.procs`.
+    pub procs: Vec<CgroupPid>,
+    /// Whether no fork or exit was noticed while `procs` was being read.
+    pub stabilized: bool,
+}
+
+/// Whether `Cgroup::get_or_create()` created the control group.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CreateOutcome {
+    /// The control group was created by this call.
+    Created,
+    /// The control group already existed and is used as-is.
+    Adopted,
+}
+
+/// The type of a v2 control group, found in `cgroup.type`, see `Cgroup::cgroup_type()`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CgroupType {
+    /// A normal control group, whose processes are subject to all the enabled controllers.
+    Domain,
+    /// The domain at the root of a threaded subtree.
+    DomainThreaded,
+    /// A domain control group inside a threaded subtree, which can't have processes.
+    ///
+    /// It arises when a sibling is turned threaded: their parent becomes the root of a threaded
+    /// subtree and the children that are still domains become invalid. Adding processes to it
+    /// fails with `ErrorKind::DomainInvalid` until it is turned threaded as well, by writing
+    /// `threaded` to its `cgroup.type` with `Cgroup::set_cgroup_type()`.
+    DomainInvalid,
+    /// A control group of a threaded subtree, whose threads can be spread across its children.
+    Threaded,
+}
+
+impl FromStr for CgroupType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            CGROUP_MODE_DOMAIN => Ok(CgroupType::Domain),
+            CGROUP_MODE_DOMAIN_THREADED => Ok(CgroupType::DomainThreaded),
+            CGROUP_MODE_DOMAIN_INVALID => Ok(CgroupType::DomainInvalid),
+            CGROUP_MODE_THREADED => Ok(CgroupType::Threaded),
+            _ => Err(Error::new(ParseError)),
+        }
+    }
+}
+
+impl fmt::Display for CgroupType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            CgroupType::Domain => CGROUP_MODE_DOMAIN,
+            CgroupType::DomainThreaded => CGROUP_MODE_DOMAIN_THREADED,
+            CgroupType::DomainInvalid => CGROUP_MODE_DOMAIN_INVALID,
+            CgroupType::Threaded => CGROUP_MODE_THREADED,
+        };
+        write!(f, "{}", s)
+    }
+}
+
 /// A control group is the central structure to this crate.
 ///
 ///
@@ -79,11 +208,138 @@ impl Cgroup {
         &self.path
     }
 
+    /// Return the path of the control group relative to the root of the hierarchy, which is
+    /// empty for the root control group.
+    pub fn relative_path(&self) -> &str {
+        self.path.trim_start_matches('/')
+    }
+
+    /// Return whether the current process runs in a cgroup namespace other than the initial one,
+    /// e.g. inside a container.
+    ///
+    /// In a cgroup namespace `/proc/<pid>/cgroup` and the roots of the cgroup mounts in
+    /// `/proc/self/mountinfo` are relative to the root of the namespace, which a cgroup file
+    /// system mounted inside of the namespace shows as its root. Paths of the control groups
+    /// are then relative to that root, rather than to the root of the host hierarchy.
+    pub fn is_namespaced(&self) -> Result<bool> {
+        match fs::metadata("/proc/self/ns/cgroup") {
+            Ok(m) => Ok(m.ino() != CGROUP_NS_INIT_INO),
+            // Without support for cgroup namespaces, there's only the initial one.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(Error::with_cause(
+                ReadFailed("/proc/self/ns/cgroup".to_string()),
+                e,
+            )),
+        }
+    }
+
+    /// Return whether this is the root control group of the hierarchy, whose interface files
+    /// mostly can't be configured.
+    ///
+    /// This is the root as seen by the kernel: the top of a hierarchy rooted at a control group,
+    /// e.g. the one delegated to a container, isn't.
+    pub fn is_root(&self) -> bool {
+        if !self.relative_path().is_empty() {
+            return false;
+        }
+
+        // without any mounted controller there is nothing to tell the top of the hierarchy from
+        let path = self.fs_path();
+        self.subsystems.is_empty() || !path.exists() || is_root_path(&path)
+    }
+
+    /// Return the absolute path of the control group in the file system.
+    ///
+    /// On v1 every controller has its own hierarchy, this returns the path in the hierarchy of the
+    /// first controller, see `fs_path_for()` to pick a controller.
+    pub fn fs_path(&self) -> PathBuf {
+        if self.v2() {
+            let root = self.hier.root();
+            if self.relative_path().is_empty() {
+                return root;
+            }
+            return root.join(self.relative_path());
+        }
+
+        self.subsystems
+            .first()
+            .map(|s| s.to_controller().path().to_path_buf())
+            .unwrap_or_else(|| self.hier.root())
+    }
+
+    /// Return the absolute path of the control group in the hierarchy of `controller`, or `None`
+    /// if the controller isn't available.
+    pub fn fs_path_for(&self, controller: Controllers) -> Option<PathBuf> {
+        self.subsystems
+            .iter()
+            .map(|s| s.to_controller())
+            .find(|c| c.control_type() == controller)
+            .map(|c| c.path().to_path_buf())
+    }
+
+    // The directories of the control group, one per hierarchy: a single one on v2, and on v1
+    // co-mounted controllers share theirs.
+    fn fs_dirs(&self) -> Vec<PathBuf> {
+        if self.v2() {
+            return vec![self.fs_path()];
+        }
+
+        let mut dirs: Vec<_> = self
+            .subsystems
+            .iter()
+            .map(|s| s.to_controller().path().to_path_buf())
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// Returns whether the current process may write the settings of `controller` in the control
+    /// group, e.g. to tell up front that root privileges or a delegation are needed.
+    ///
+    /// This checks write access with `access(2)` to the main file of the controller, the first
+    /// one of `Controller::managed_files()`, or to `cgroup.procs` when there is none, e.g. for
+    /// hugetlb whose files are named after the page sizes. Returns `false` if the controller isn't
+    /// available or the file doesn't exist, e.g. for the limits of the root control group.
+    pub fn can_write(&self, controller: Controllers) -> bool {
+        let c = match self
+            .subsystems
+            .iter()
+            .map(|s| s.to_controller())
+            .find(|c| c.control_type() == controller)
+        {
+            Some(c) => c,
+            None => return false,
+        };
+
+        let file = c
+            .managed_files()
+            .iter()
+            .find(|f| !f.contains('<'))
+            .copied()
+            .unwrap_or("cgroup.procs");
+        access(&c.path().join(file), AccessFlags::W_OK).is_ok()
+    }
+
     /// Create this control group.
+    ///
+    /// Fails with `ErrorKind::InvalidName` if a component of the name is `.` or `..`, contains a
+    /// NUL byte, or starts with `cgroup.`, which the kernel reserves for its interface files.
+    ///
+    /// Names are used literally: each `/`-separated component is one directory, and dots have
+    /// no special meaning. A systemd-style name such as `system.slice/foo.service` is therefore
+    /// the `foo.service` directory inside `system.slice`, and `a-b.slice` is a single directory
+    /// rather than being expanded into `a.slice/a-b.slice`, which is left to the systemd
+    /// manager.
     pub fn create(&self) -> Result<()> {
+        check_name(&self.path)?;
         if self.hier.v2() {
-            create_v2_cgroup(self.hier.root(), &self.path, &self.specified_controllers)
+            create_v2_cgroup(self.hier.root(), &self.path, &self.specified_controllers).map(|_| ())
         } else {
+            // e.g. no cgroup file system is mounted
+            if self.subsystems.is_empty() {
+                return Err(Error::new(SubsystemsEmpty));
+            }
             for subsystem in &self.subsystems {
                 subsystem.to_controller().create();
             }
@@ -93,6 +349,11 @@ impl Cgroup {
 
     /// Create a new control group in the hierarchy `hier`, with name `path`.
     ///
+    /// Like `mkdir -p`, the missing ancestors are created as well, e.g. `a` and `a/b` for
+    /// `a/b/c`, and the directories that already exist are kept. On v2 the controllers are
+    /// enabled in the `cgroup.subtree_control` of each ancestor, see
+    /// `enable_controllers_in_ancestors()` to find out about the failures.
+    ///
     /// Returns a handle to the control group that can be used to manipulate it.
     pub fn new<P: AsRef<Path>>(hier: Box<dyn Hierarchy>, path: P) -> Result<Cgroup> {
         let cg = Cgroup::load(hier, path);
@@ -100,6 +361,96 @@ impl Cgroup {
         Ok(cg)
     }
 
+    /// Same as `new()`, but sets the permission bits of the directory of the control group to
+    /// `mode`, e.g. `0o755`, in every hierarchy on v1.
+    ///
+    /// The mode is applied with an explicit `chmod` after the directory is created, so the umask
+    /// of the process doesn't restrict it. Missing parent directories get the default mode.
+    pub fn create_with_mode<P: AsRef<Path>>(
+        hier: Box<dyn Hierarchy>,
+        path: P,
+        mode: u32,
+    ) -> Result<Cgroup> {
+        let cg = Cgroup::new(hier, path)?;
+        for dir in cg.fs_dirs() {
+            fs::set_permissions(&dir, fs::Permissions::from_mode(mode))
+                .map_err(|e| Error::with_cause(ErrorKind::FsError, e))?;
+        }
+        Ok(cg)
+    }
+
+    /// Creates the control group `path` in the hierarchy `hier` along with its missing
+    /// ancestors, e.g. `a` and `a/b` for `a/b/c`, and applies `res` to it.
+    ///
+    /// The ancestors keep their default settings, and on v2 the controllers are enabled in their
+    /// `cgroup.subtree_control` as with `new()`. If creating the control group or applying `res`
+    /// fails, the directories created by this call are removed again, while the ones that
+    /// already existed are left alone.
+    pub fn create_tree<P: AsRef<Path>>(
+        hier: Box<dyn Hierarchy>,
+        path: P,
+        res: &Resources,
+    ) -> Result<Cgroup> {
+        let cg = Cgroup::load(hier, path);
+
+        // the missing levels, deepest first
+        let mut missing = vec![];
+        let mut level = cg.parent_control_group();
+        while !level.relative_path().is_empty() && !level.exists() {
+            let parent = level.parent_control_group();
+            missing.push(level);
+            level = parent;
+        }
+        let leaf_missing = !cg.exists();
+
+        let result = cg.create().and_then(|_| cg.apply(res));
+        if let Err(e) = result {
+            let created = leaf_missing.then_some(&cg).into_iter().chain(&missing);
+            for level in created {
+                if let Err(e) = level.delete() {
+                    warn!("failed to remove control group {}: {}", level.path(), e);
+                }
+            }
+            return Err(e);
+        }
+
+        Ok(cg)
+    }
+
+    /// Same as `new()`, but also tells whether the control group was created by this call or
+    /// already existed, e.g. because another process created it concurrently.
+    ///
+    /// The outcome is decided by the `mkdir` of the control group directory, in the first
+    /// hierarchy on v1: among concurrent callers exactly one gets `Created`, the others get
+    /// `Adopted`. Any other error of `mkdir` is returned.
+    pub fn get_or_create<P: AsRef<Path>>(
+        hier: Box<dyn Hierarchy>,
+        path: P,
+    ) -> Result<(Cgroup, CreateOutcome)> {
+        let cg = Cgroup::load(hier, path);
+        let created = if cg.hier.v2() {
+            create_v2_cgroup(cg.hier.root(), &cg.path, &cg.specified_controllers)?
+        } else {
+            let dir = cg
+                .subsystems
+                .first()
+                .ok_or_else(|| Error::new(SubsystemsEmpty))?
+                .to_controller()
+                .path()
+                .to_path_buf();
+            let created = create_dir(&dir)?;
+            cg.create()?;
+            created
+        };
+
+        let outcome = if created {
+            CreateOutcome::Created
+        } else {
+            CreateOutcome::Adopted
+        };
+        Ok((cg, outcome))
+    }
+
     /// Create a new control group in the hierarchy `hier`, with name `path`.
     ///
     /// Returns a handle to the control group that can be used to manipulate it.
@@ -135,7 +486,9 @@ impl Cgroup {
     /// Create a handle for a control group in the hierarchy `hier`, with name `path`.
     ///
     /// Returns a handle to the control group (that possibly does not exist until `create()` has
-    /// been called on the cgroup.
+    /// been called on the cgroup. See `load_existing()` to fail instead.
+    ///
+    /// The name isn't validated here, `create()` and `load_existing()` do it.
     pub fn load<P: AsRef<Path>>(hier: Box<dyn Hierarchy>, path: P) -> Cgroup {
         let path = path.as_ref();
         let mut subsystems = hier.subsystems();
@@ -154,6 +507,25 @@ impl Cgroup {
         }
     }
 
+    /// Same as `load()`, but fails with `ErrorKind::NotFound` if the control group doesn't exist,
+    /// see `exists()`.
+    pub fn load_existing<P: AsRef<Path>>(hier: Box<dyn Hierarchy>, path: P) -> Result<Cgroup> {
+        let cg = Cgroup::load(hier, path);
+        check_name(&cg.path)?;
+        if !cg.exists() {
+            return Err(Error::new(NotFound(cg.path.clone())));
+        }
+        Ok(cg)
+    }
+
+    /// Returns whether the control group `path` exists in the hierarchy `hier`, without creating
+    /// a handle for it, e.g. to decide between `load()` and `new()`.
+    ///
+    /// Same as `load(hier, path).exists()`.
+    pub fn exists_in<P: AsRef<Path>>(hier: Box<dyn Hierarchy>, path: P) -> bool {
+        Cgroup::load(hier, path).exists()
+    }
+
     /// Create a handle for a specified control group in the hierarchy `hier`, with name `path`.
     ///
     /// Returns a handle to the control group (that possibly does not exist until `create()` has
@@ -234,9 +606,9 @@ impl Cgroup {
     ///
     /// Note that this function makes no effort in cleaning up the descendant and the underlying
     /// system call will fail if there are any descendants. Thus, one should check whether it was
-    /// actually removed, and remove the descendants first if not. In the future, this behavior
-    /// will change.
+    /// actually removed, and remove the descendants first if not, see `delete_recursive()`.
     pub fn delete(&self) -> Result<()> {
+        self.release_cached_handles();
         if self.v2() {
             if !self.path.is_empty() {
                 let mut p = self.hier.root();
@@ -260,17 +632,319 @@ impl Cgroup {
             Subsystem::NetPrio(c) => c.delete(),
             Subsystem::HugeTlb(c) => c.delete(),
             Subsystem::Rdma(c) => c.delete(),
+            Subsystem::Misc(c) => c.delete(),
             Subsystem::Systemd(c) => c.delete(),
         })
     }
 
+    /// Deletes the control group together with its descendants, the deepest ones first.
+    ///
+    /// Fails like `delete()` if any of them still has processes, which must be killed or moved
+    /// first, e.g. with `kill_and_wait()` or `move_all_tasks_to()`. The root control group is
+    /// left alone.
+    pub fn delete_recursive(&self) -> Result<()> {
+        if self.path.is_empty() {
+            return Ok(());
+        }
+
+        self.release_cached_handles();
+        if self.v2() {
+            return remove_subtree(&self.fs_path());
+        }
+        // the controllers sharing a hierarchy share the directory, already gone the second time
+        self.subsystems
+            .iter()
+            .try_for_each(|sub| remove_subtree(sub.to_controller().path()))
+    }
+
+    /// Same as `delete_recursive()`, but tries up to `attempts` times, waiting `backoff` and then
+    /// twice as long every time, as long as the subtree has no processes left.
+    ///
+    /// Removing a control group fails with `EBUSY` for a moment after its last processes exited,
+    /// until the kernel is done releasing them, while the descendants deleted meanwhile show up
+    /// as dying in `cgroup_stat()`. A subtree that still has processes fails right away, as
+    /// waiting wouldn't help.
+    pub fn delete_with_retry(&self, attempts: usize, backoff: Duration) -> Result<()> {
+        let mut delay = backoff;
+        let mut attempt = 1;
+        loop {
+            let err = match self.delete_recursive() {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            };
+            let populated = matches!(self.all_procs_recursive(), Ok(pids) if !pids.is_empty());
+            if attempt >= attempts || populated {
+                return Err(err);
+            }
+            thread::sleep(delay);
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+
+    /// Keeps the control files of the control group open once written, the later writes to the
+    /// same file reusing the handle, e.g. to update limits in a loop or apply large `Resources`.
+    ///
+    /// The writes skip the path lookup of `open()` in the cgroup file system, a handle gone
+    /// stale being opened anew. The handles are closed by `delete()`, `delete_recursive()` or
+    /// `release_cached_handles()`, and other `Cgroup` values of the same control group share
+    /// them. Reads always open the file anew.
+    pub fn with_cached_handles(self) -> Cgroup {
+        cache_handles(
+            self.subsystems.iter().map(|s| s.to_controller().path()),
+            true,
+        );
+        self
+    }
+
+    /// Closes the handles cached since `with_cached_handles()`, if any.
+    pub fn release_cached_handles(&self) {
+        cache_handles(
+            self.subsystems.iter().map(|s| s.to_controller().path()),
+            false,
+        );
+    }
+
     /// Apply a set of resource limits to the control group.
+    ///
+    /// Fails with `RootCgroupNotConfigurable` if this is the root control group and `res` sets a
+    /// limit the kernel doesn't allow on it, and with `NotSupported` if `res` has device rules on
+    /// v2, which has no devices controller.
     pub fn apply(&self, res: &Resources) -> Result<()> {
+        self.check_applicable(res)?;
+        self.subsystems
+            .iter()
+            .try_fold((), |_, e| e.to_controller().apply(res))
+    }
+
+    /// Same as `apply()`, but either applies all of `res` or restores the previous settings.
+    ///
+    /// The current settings are saved with `read_resources()` first. If a controller fails to
+    /// apply `res`, the saved settings are applied again to it and to the controllers already
+    /// configured, in reverse order, and an `ApplyRolledBack` error caused by the failure is
+    /// returned. Its `not_restored` list names the settings that can't be read back, and so
+    /// weren't restored (the `attrs` maps and the device rules, see `read_resources()`), and the
+    /// controllers whose restoration failed.
+    pub fn apply_transactional(&self, res: &Resources) -> Result<()> {
+        self.check_applicable(res)?;
+
+        let saved = self.read_resources()?;
+        let mut touched = Vec::new();
+        for sub in &self.subsystems {
+            touched.push(sub);
+            let err = match sub.to_controller().apply(res) {
+                Ok(()) => continue,
+                Err(err) => err,
+            };
+
+            let mut not_restored = non_reversible_settings(res);
+            for sub in touched.iter().rev() {
+                if let Err(e) = sub.to_controller().apply(&saved) {
+                    warn!(
+                        "failed to restore the {} controller of {}: {}",
+                        sub.controller_name(),
+                        self.path,
+                        e
+                    );
+                    not_restored.push(sub.controller_name());
+                }
+            }
+            return Err(Error::with_cause(
+                ApplyRolledBack {
+                    failed: sub.controller_name(),
+                    not_restored,
+                },
+                err,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Same as `apply()`, but gives up after `timeout` in case writing a file blocks, e.g. when
+    /// freezing interacts with tasks in uninterruptible sleep.
+    ///
+    /// The files are written on a worker thread. On timeout, the returned `ApplyTimedOut` error
+    /// tells which controllers were configured and which one was being configured. The pending
+    /// write can't be cancelled: the worker thread keeps going and may still complete it, along
+    /// with the remaining controllers, after this returns.
+    pub fn apply_with_timeout(&self, res: &Resources, timeout: Duration) -> Result<()> {
+        self.check_applicable(res)?;
+
+        let subsystems = self.subsystems.clone();
+        let res = res.clone();
+        let applied = Arc::new(Mutex::new(Vec::new()));
+        let progress = Arc::clone(&applied);
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let result = subsystems.iter().try_for_each(|sub| {
+                sub.to_controller().apply(&res)?;
+                progress.lock().unwrap().push(sub.controller_name());
+                Ok(())
+            });
+            // Nobody listens anymore after a timeout.
+            let _ = tx.send(result);
+        });
+
+        match rx.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let applied = applied.lock().unwrap().clone();
+                let pending = self
+                    .subsystems
+                    .get(applied.len())
+                    .map(|sub| sub.controller_name())
+                    .unwrap_or_default();
+                Err(Error::new(ApplyTimedOut { applied, pending }))
+            }
+            // The worker thread panicked.
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(Error::new(Other)),
+        }
+    }
+
+    /// Reconfigures the control group with `res` while keeping it as close as possible to a
+    /// consistent configuration at every point in time.
+    ///
+    /// The kernel cannot update several cgroup files in one step, and swapping in a freshly
+    /// configured control group would mean migrating every task (memory charges do not follow the
+    /// tasks on cgroup v2), so the existing control group is updated in place, in this order:
+    ///
+    /// 1. The `cpuset` controller is configured before any other controller.
+    /// 2. Limits that grow are raised: memory limits, the pids limit and the CPU quota are set to
+    ///    the larger of their current and requested values.
+    /// 3. The whole of `res` is applied, which leaves only limits that shrink to be lowered.
+    ///
+    /// As a result a limit is never temporarily lower than both its old and new values, and
+    /// dependent limits such as `memory.limit_in_bytes` and `memory.memsw.limit_in_bytes` are
+    /// updated in an order the kernel accepts.
+    pub fn reconfigure_atomic(&self, res: &Resources) -> Result<()> {
+        self.check_applicable(res)?;
+        let raised = self.raised_resources(res);
+        self.apply_ordered(&raised)?;
+        self.apply_ordered(res)
+    }
+
+    /// Resets the settings of every controller of the control group to their defaults, e.g. to
+    /// repurpose it without deleting and recreating it. See `Controller::reset()` for the files
+    /// written by each controller.
+    ///
+    /// The root control group can't be reset, which fails with `RootCgroupNotConfigurable`
+    /// naming its controllers.
+    pub fn reset_all(&self) -> Result<()> {
+        if self.is_root() {
+            let controllers: Vec<String> = self
+                .subsystems
+                .iter()
+                .map(|s| s.controller_name())
+                .collect();
+            return Err(Error::new(RootCgroupNotConfigurable(
+                controllers.join(", "),
+            )));
+        }
+
         self.subsystems
+            .iter()
+            .try_for_each(|s| s.to_controller().reset())
+    }
+
+    /// Reads back the current settings of every controller of the control group, e.g. to save
+    /// them and `apply()` them again later. See `Controller::read_resources()`.
+    ///
+    /// Not everything can be recovered:
+    /// - the `attrs` maps are left empty, as the files they were written to are unknown.
+    /// - devices: only the rules allowed in `devices.list` are returned, the order and the deny
+    ///   rules that led to them are lost. Nothing is returned on v2, which uses eBPF programs.
+    /// - hugetlb: unlimited sizes are left out on v2.
+    /// - memory: `memory.low` is returned as the soft limit on v2, as `apply()` writes it there.
+    pub fn read_resources(&self) -> Result<Resources> {
+        let mut res = Resources::default();
+        for sub in &self.subsystems {
+            sub.to_controller().read_resources(&mut res)?;
+        }
+        Ok(res)
+    }
+
+    /// Same as `read_resources()`, but never fails: a controller whose settings can't be read
+    /// leaves the fields it didn't fill at their defaults, and the others are still read. Meant
+    /// for comparing the desired settings with the actual ones, where a partial answer is better
+    /// than none.
+    pub fn snapshot_resources(&self) -> Resources {
+        let mut res = Resources::default();
+        for sub in &self.subsystems {
+            let _ = sub.to_controller().read_resources(&mut res);
+        }
+        res
+    }
+
+    // Rejects the resources that can't be applied to the control group, rather than silently
+    // skipping them.
+    fn check_applicable(&self, res: &Resources) -> Result<()> {
+        if self.v2() && !res.devices.devices.is_empty() {
+            return Err(devices_not_supported("the devices controller"));
+        }
+
+        if !self.is_root() {
+            return Ok(());
+        }
+
+        match root_unconfigurable_files(res, self.v2())
+            .into_iter()
+            .find(|(controller, _)| self.fs_path_for(controller.clone()).is_some())
+        {
+            Some((_, file)) => Err(Error::new(RootCgroupNotConfigurable(file.to_string()))),
+            None => Ok(()),
+        }
+    }
+
+    fn apply_ordered(&self, res: &Resources) -> Result<()> {
+        let mut subsystems: Vec<&Subsystem> = self.subsystems.iter().collect();
+        subsystems.sort_by_key(|s| !matches!(s, Subsystem::CpuSet(_)));
+        subsystems
             .iter()
             .try_fold((), |_, e| e.to_controller().apply(res))
     }
 
+    /// Returns the limits of `res` that are higher than the current ones, the others being left
+    /// at their current value.
+    fn raised_resources(&self, res: &Resources) -> Resources {
+        let mut raised = Resources::default();
+
+        if let Some(mem) = self.controller_of::<MemController>() {
+            let memres = &res.memory;
+            raised.memory.memory_hard_limit = memres
+                .memory_hard_limit
+                .map(|v| raise_limit(mem.memory_stat().limit_in_bytes, v));
+            raised.memory.memory_swap_limit = memres
+                .memory_swap_limit
+                .map(|v| raise_limit(mem.memswap_limit().unwrap_or(0), v));
+            if !self.v2() {
+                raised.memory.kernel_memory_limit = memres
+                    .kernel_memory_limit
+                    .map(|v| raise_limit(mem.kmem_stat().limit_in_bytes, v));
+                raised.memory.kernel_tcp_memory_limit = memres
+                    .kernel_tcp_memory_limit
+                    .map(|v| raise_limit(mem.kmem_tcp_stat().limit_in_bytes, v));
+            }
+        }
+
+        if let Some(pids) = self.controller_of::<PidController>() {
+            if let (Some(new), Ok(cur)) = (res.pid.maximum_number_of_processes, pids.get_pid_max())
+            {
+                raised.pid.maximum_number_of_processes = match (cur, new) {
+                    (MaxValue::Value(c), MaxValue::Value(n)) => Some(MaxValue::Value(c.max(n))),
+                    _ => Some(MaxValue::Max),
+                };
+            }
+        }
+
+        if let Some(cpu) = self.controller_of::<CpuController>() {
+            if let (Some(new), Ok(cur)) = (res.cpu.quota, cpu.cfs_quota()) {
+                raised.cpu.quota = Some(raise_limit(cur, new));
+            }
+        }
+
+        raised
+    }
+
     /// Retrieve a container based on type inference.
     ///
     /// ## Example:
@@ -283,17 +957,38 @@ impl Cgroup {
     /// ```
     pub fn controller_of<'a, T>(&'a self) -> Option<&'a T>
     where
-        &'a T: From<&'a Subsystem>,
+        &'a T: TryFrom<&'a Subsystem, Error = Error>,
         T: Controller + ControllIdentifier,
     {
-        for i in &self.subsystems {
-            if i.to_controller().control_type() == T::controller_type() {
-                // N.B.:
-                // https://play.rust-lang.org/?gist=978b2846bacebdaa00be62374f4f4334&version=stable&mode=debug&edition=2015
-                return Some(i.into());
+        self.subsystems
+            .iter()
+            .filter(|s| s.to_controller().control_type() == T::controller_type())
+            .find_map(|s| s.try_into().ok())
+    }
+
+    /// Same as `controller_of()`, but tells why the controller is missing:
+    /// `ControllerNotAvailable` if it isn't attached to the hierarchy, and on v2
+    /// `ControllerNotEnabled` if it isn't enabled in the control group, i.e. in the
+    /// `cgroup.subtree_control` of its parent, in which case its files don't exist.
+    pub fn try_controller_of<'a, T>(&'a self) -> Result<&'a T>
+    where
+        &'a T: TryFrom<&'a Subsystem, Error = Error>,
+        T: Controller + ControllIdentifier,
+    {
+        let controller = self
+            .controller_of::<T>()
+            .ok_or_else(|| Error::new(ControllerNotAvailable(T::controller_type().to_string())))?;
+
+        // The freezer is part of the v2 core and always enabled, as is everything in the root.
+        if self.v2() && !self.is_root() && T::controller_type() != Controllers::Freezer {
+            let name = v2_controller_name(&T::controller_type());
+            let enabled = read_flat_file(&self.fs_path().join("cgroup.controllers"))?;
+            if !enabled.split_whitespace().any(|c| c == name) {
+                return Err(Error::new(ControllerNotEnabled(name)));
             }
         }
-        None
+
+        Ok(controller)
     }
 
     /// Removes tasks from the control group by thread group id.
@@ -324,17 +1019,47 @@ impl Cgroup {
         self.hier.parent_control_group(&self.path).add_task(tid)
     }
 
+    /// Moves every process of the control group to `dest`, e.g. to empty it before deleting it,
+    /// and returns how many were moved.
+    ///
+    /// The processes can fork while they are moved, so the list is read again until it is empty,
+    /// up to 10 times; check `procs()` afterwards if it must be empty.
+    /// Processes that exit before being moved are skipped rather than failing.
+    pub fn move_all_tasks_to(&self, dest: &Cgroup) -> Result<usize> {
+        let mut moved = 0;
+        for _ in 0..MOVE_ALL_TASKS_RETRIES {
+            let procs = self.procs();
+            if procs.is_empty() {
+                break;
+            }
+            for pid in procs {
+                match dest.add_task_by_tgid(pid) {
+                    Ok(()) => moved += 1,
+                    Err(e) if is_esrch(&e) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(moved)
+    }
+
     /// Return a handle to the parent control group in the hierarchy.
+    ///
+    /// The parent is found by dropping the last `/`-separated component of the name, see
+    /// `create()` for how names map to directories. The root control group is its own parent.
     pub fn parent_control_group(&self) -> Cgroup {
         self.hier.parent_control_group(&self.path)
     }
 
     /// Kill every process in the control group. Only supported for v2 cgroups and on
-    /// kernels 5.14+. This will fail with InvalidOperation if the 'cgroup.kill' file does
+    /// kernels 5.14+. This will fail with NotSupported if the 'cgroup.kill' file does
     /// not exist.
     pub fn kill(&self) -> Result<()> {
         if !self.v2() {
-            return Err(Error::new(CgroupVersion));
+            return Err(Error::not_supported(
+                "cgroup.kill",
+                "only available on cgroup v2",
+            ));
         }
 
         let val = "1";
@@ -344,24 +1069,401 @@ impl Cgroup {
         // If cgroup.kill doesn't exist they're not on 5.14+ so lets
         // surface some error the caller can check against.
         if !p.exists() {
-            return Err(Error::new(InvalidOperation));
+            return Err(Error::not_supported(file_name, "requires kernel 5.14+"));
         }
 
         fs::write(p, val)
             .map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), val.to_string()), e))
     }
 
+    /// Kill every process in the control group and its descendants with `kill()`, then wait up
+    /// to `timeout` for them to be gone, e.g. before deleting the control group.
+    ///
+    /// Killing is asynchronous: the processes exit once they get to handle the signal, which
+    /// processes in uninterruptible sleep may not do in time. On timeout, a `KillTimedOut` error
+    /// lists the processes that remain.
+    pub fn kill_and_wait(&self, timeout: Duration) -> Result<()> {
+        self.kill()?;
+
+        let events = self.fs_path().join("cgroup.events");
+        let deadline = Instant::now() + timeout;
+        loop {
+            // The control group is empty once its subtree isn't populated anymore.
+            let populated = match read_flat_file(&events) {
+                Ok(content) => parse_cgroup_events(&content).populated,
+                Err(_) => !self.all_procs_recursive()?.is_empty(),
+            };
+            if !populated {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::new(KillTimedOut(self.all_procs_recursive()?)));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Enable or disable the PSI (pressure stall information) accounting of the control group.
+    /// Only supported for v2 cgroups and on kernels 6.1+. This will fail with NotSupported
+    /// if the 'cgroup.pressure' file does not exist.
+    pub fn set_pressure_accounting(&self, enable: bool) -> Result<()> {
+        let p = self.pressure_file()?;
+        let val = if enable { "1" } else { "0" };
+
+        fs::write(p, val).map_err(|e| {
+            Error::with_cause(
+                WriteFailed("cgroup.pressure".to_string(), val.to_string()),
+                e,
+            )
+        })
+    }
+
+    /// Returns whether the PSI (pressure stall information) accounting of the control group is
+    /// enabled. Only supported for v2 cgroups and on kernels 6.1+.
+    pub fn pressure_accounting(&self) -> Result<bool> {
+        let p = self.pressure_file()?;
+        let val = fs::read_to_string(p)
+            .map_err(|e| Error::with_cause(ReadFailed("cgroup.pressure".to_string()), e))?;
+
+        match val.trim() {
+            "1" => Ok(true),
+            "0" => Ok(false),
+            _ => Err(Error::new(ParseError)),
+        }
+    }
+
+    fn pressure_file(&self) -> Result<PathBuf> {
+        if !self.v2() {
+            return Err(Error::not_supported(
+                "cgroup.pressure",
+                "only available on cgroup v2",
+            ));
+        }
+
+        let p = self
+            .hier
+            .root()
+            .join(self.path.clone())
+            .join("cgroup.pressure");
+        if !p.exists() {
+            return Err(Error::not_supported(
+                "cgroup.pressure",
+                "requires kernel 6.1+",
+            ));
+        }
+
+        Ok(p)
+    }
+
+    /// Limits the number of descendant control groups of the control group, found in
+    /// `cgroup.max.descendants`. Only supported for v2 cgroups.
+    ///
+    /// Creating a control group beyond the limit fails with `EAGAIN`.
+    pub fn set_max_descendants(&self, max: MaxValue) -> Result<()> {
+        self.write_tree_limit("cgroup.max.descendants", max)
+    }
+
+    /// Returns the limit on the number of descendant control groups of the control group.
+    /// Only supported for v2 cgroups.
+    pub fn max_descendants(&self) -> Result<MaxValue> {
+        self.read_tree_limit("cgroup.max.descendants")
+    }
+
+    /// Limits the depth of the subtree below the control group, found in `cgroup.max.depth`.
+    /// Only supported for v2 cgroups.
+    ///
+    /// A depth of 1 allows children but no grandchildren.
+    pub fn set_max_depth(&self, max: MaxValue) -> Result<()> {
+        self.write_tree_limit("cgroup.max.depth", max)
+    }
+
+    /// Returns the limit on the depth of the subtree below the control group. Only supported
+    /// for v2 cgroups.
+    pub fn max_depth(&self) -> Result<MaxValue> {
+        self.read_tree_limit("cgroup.max.depth")
+    }
+
+    fn write_tree_limit(&self, file_name: &str, max: MaxValue) -> Result<()> {
+        if !self.v2() {
+            return Err(Error::not_supported(
+                file_name,
+                "only available on cgroup v2",
+            ));
+        }
+
+        let value = max.to_string();
+        fs::write(self.fs_path().join(file_name), &value)
+            .map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), value), e))
+    }
+
+    fn read_tree_limit(&self, file_name: &str) -> Result<MaxValue> {
+        if !self.v2() {
+            return Err(Error::not_supported(
+                file_name,
+                "only available on cgroup v2",
+            ));
+        }
+
+        let value = fs::read_to_string(self.fs_path().join(file_name))
+            .map_err(|e| Error::with_cause(ReadFailed(file_name.to_string()), e))?;
+        parse_max_value(value.trim())
+    }
+
+    /// Returns the controllers enabled for the children of the control group, found in
+    /// `cgroup.subtree_control`. Only supported for v2 cgroups.
+    ///
+    /// Controllers unknown to this crate are left out.
+    pub fn subtree_control(&self) -> Result<Vec<Controllers>> {
+        read_subtree_control(&self.subtree_control_file()?)
+    }
+
+    /// Makes `desired` the set of controllers enabled for the children of the control group.
+    /// Only supported for v2 cgroups.
+    ///
+    /// `cgroup.subtree_control` only accepts changes, so the current set is read first and each
+    /// missing controller is enabled before each extra one is disabled. Disabling a controller
+    /// that a child control group still enables for its own children fails, with `EBUSY` as the
+    /// cause of the error. Controllers unknown to this crate are left untouched.
+    ///
+    /// Enabling a controller fails with `ControllerEnableBlockedByProcesses` while the control
+    /// group has processes of its own, see `own_procs()`: they have to be moved to a child
+    /// control group first. The root control group isn't subject to this rule.
+    pub fn set_subtree_controllers(&self, desired: &[Controllers]) -> Result<()> {
+        let current = self.subtree_control()?;
+
+        let enable = desired.iter().filter(|c| !current.contains(c));
+        let disable = current.iter().filter(|c| !desired.contains(c));
+        let changes = enable
+            .map(|c| format!("+{}", v2_controller_name(c)))
+            .chain(disable.map(|c| format!("-{}", v2_controller_name(c))));
+
+        write_subtree_control(&self.subtree_control_file()?, changes)
+    }
+
+    /// Enables `controllers` for the children of the control group, leaving the other ones
+    /// enabled. Only supported for v2 cgroups.
+    ///
+    /// Fails with `ControllerEnableBlockedByProcesses` while the control group has processes of
+    /// its own, as with `set_subtree_controllers()`.
+    pub fn enable_subtree_controllers(&self, controllers: &[Controllers]) -> Result<()> {
+        enable_subtree_control(&self.subtree_control_file()?, controllers)
+    }
+
+    /// Disables `controllers` for the children of the control group, leaving the other ones
+    /// enabled. Only supported for v2 cgroups.
+    ///
+    /// Fails with `EBUSY` as the cause of the error while a child control group still enables
+    /// one of them for its own children.
+    pub fn disable_subtree_controllers(&self, controllers: &[Controllers]) -> Result<()> {
+        let current = self.subtree_control()?;
+        let changes = controllers
+            .iter()
+            .filter(|c| current.contains(c))
+            .map(|c| format!("-{}", v2_controller_name(c)));
+
+        write_subtree_control(&self.subtree_control_file()?, changes)
+    }
+
+    /// Enables `controllers` in the `cgroup.subtree_control` of every ancestor of the control
+    /// group, from the root of the hierarchy down to its parent, so that the control group
+    /// gets them. Only supported for v2 cgroups.
+    ///
+    /// `new()` already tries this for all the controllers but ignores the failures, which leaves
+    /// a nested control group without the controllers that an ancestor couldn't enable, e.g.
+    /// because it has processes of its own. This returns the first failure instead.
+    pub fn enable_controllers_in_ancestors(&self, controllers: &[Controllers]) -> Result<()> {
+        // fails on v1
+        self.subtree_control_file()?;
+
+        let root = self.hier.root();
+        let path = self.fs_path();
+        let mut ancestors: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&root))
+            .collect();
+        ancestors.reverse();
+
+        for dir in ancestors {
+            enable_subtree_control(&dir.join("cgroup.subtree_control"), controllers)?;
+        }
+
+        Ok(())
+    }
+
+    /// Delegates the control group to the user `uid` and group `gid`, who can then manage the
+    /// subtree below it without privileges. Only supported for v2 cgroups.
+    ///
+    /// As the kernel's delegation model requires, the directory and the files listed in
+    /// `/sys/kernel/cgroup/delegate` (`cgroup.procs`, `cgroup.threads` and
+    /// `cgroup.subtree_control` on older kernels) are given to them, while the interface files
+    /// setting the limits of the control group itself stay owned by the delegator. This fails
+    /// with `ChownFailed` if a file can't be given away, with `EPERM` as the cause without
+    /// `CAP_CHOWN`.
+    pub fn delegate_to(&self, uid: u32, gid: u32) -> Result<()> {
+        if !self.v2() {
+            return Err(Error::not_supported(
+                "delegation",
+                "only available on cgroup v2",
+            ));
+        }
+
+        let files: Vec<String> = match fs::read_to_string("/sys/kernel/cgroup/delegate") {
+            Ok(content) => content.lines().map(|l| l.trim().to_string()).collect(),
+            Err(_) => DELEGATE_FILES.iter().map(|f| f.to_string()).collect(),
+        };
+        let dir = self.fs_path();
+        let paths = std::iter::once(dir.clone()).chain(
+            files
+                .iter()
+                .filter(|f| !f.is_empty())
+                .map(|f| dir.join(f))
+                .filter(|p| p.exists()),
+        );
+        for path in paths {
+            chown(&path, Some(Uid::from_raw(uid)), Some(Gid::from_raw(gid))).map_err(|e| {
+                Error::with_cause(ChownFailed(path.to_string_lossy().to_string()), e)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn subtree_control_file(&self) -> Result<PathBuf> {
+        if !self.v2() {
+            return Err(Error::not_supported(
+                "cgroup.subtree_control",
+                "only available on cgroup v2",
+            ));
+        }
+
+        Ok(self.fs_path().join("cgroup.subtree_control"))
+    }
+
+    /// Returns the statistics of the control group's descendants, found in `cgroup.stat`. Only
+    /// supported for v2 cgroups.
+    ///
+    /// Deleted descendants keep being counted in `nr_dying_descendants` as long as they still
+    /// have memory charged to them, e.g. page cache, which tells why deleting them didn't free
+    /// it.
+    pub fn cgroup_stat(&self) -> Result<CgroupStat> {
+        if !self.v2() {
+            return Err(Error::not_supported(
+                "cgroup.stat",
+                "only available on cgroup v2",
+            ));
+        }
+
+        let file_name = "cgroup.stat";
+        let p = self.hier.root().join(self.path.clone()).join(file_name);
+        let content = fs::read_to_string(p)
+            .map_err(|e| Error::with_cause(ReadFailed(file_name.to_string()), e))?;
+
+        Ok(parse_cgroup_stat(&content))
+    }
+
+    /// Returns whether the control group's subtree has live processes and whether it is frozen,
+    /// found in `cgroup.events`, e.g. to wait for it to be empty before `delete()` instead of
+    /// failing with `EBUSY`.
+    ///
+    /// The file only exists in the non-root control groups of v2, the default of neither
+    /// populated nor frozen is returned when it is missing, e.g. on v1.
+    pub fn events(&self) -> Result<CgroupEvents> {
+        let path = self.fs_path().join("cgroup.events");
+        if !path.exists() {
+            return Ok(CgroupEvents::default());
+        }
+
+        read_flat_file(&path).map(|content| parse_cgroup_events(&content))
+    }
+
+    /// Watches `cgroup.events`, sending the new state on the returned channel every time the
+    /// kernel reports a change, e.g. when the last process of the subtree exits. Only supported
+    /// for the non-root control groups of v2.
+    ///
+    /// Only the actual changes are sent, and the state is read again every second in case a
+    /// notification is missed, see `events::Watch` for how long the watch lasts.
+    pub fn watch_events(&self) -> Result<events::Watch<CgroupEvents>> {
+        if !self.v2() {
+            return Err(Error::not_supported(
+                "cgroup.events",
+                "only available on cgroup v2",
+            ));
+        }
+
+        let path = self.fs_path().join("cgroup.events");
+        events::watch_changes(&path, |p| {
+            read_flat_file(p).map(|content| parse_cgroup_events(&content))
+        })
+    }
+
+    /// Returns whether the control group asked to be frozen and whether it actually is, e.g.
+    /// to know if thawing it resumes its processes or if an ancestor keeps them frozen.
+    ///
+    /// On v2 this reads `cgroup.freeze` and the `frozen` key of `cgroup.events`. On v1 it reads
+    /// `freezer.state`, and `freezer.self_freezing` if present, falling back to checking the
+    /// state of the ancestors otherwise.
+    pub fn freeze_status(&self) -> Result<FreezeStatus> {
+        if self.v2() {
+            let path = self.fs_path();
+            // The root control group can't be frozen and has none of these files.
+            if self.is_root() {
+                return Ok(FreezeStatus::default());
+            }
+            let self_requested = read_flat_file(&path.join("cgroup.freeze"))? == "1";
+            let effectively_frozen =
+                parse_cgroup_events(&read_flat_file(&path.join("cgroup.events"))?).frozen;
+            return Ok(FreezeStatus {
+                self_requested,
+                effectively_frozen,
+            });
+        }
+
+        let path = self
+            .fs_path_for(Controllers::Freezer)
+            .ok_or_else(|| Error::new(SubsystemsEmpty))?;
+        // The root control group of the freezer hierarchy can't be frozen and has no state file.
+        if !path.join("freezer.state").exists() {
+            return Ok(FreezeStatus::default());
+        }
+        let effectively_frozen = read_flat_file(&path.join("freezer.state"))? == "FROZEN";
+
+        let self_freezing = path.join("freezer.self_freezing");
+        let self_requested = if self_freezing.exists() {
+            read_flat_file(&self_freezing)? == "1"
+        } else if read_flat_file(&path.join("freezer.state"))? == "THAWED" {
+            false
+        } else {
+            // Without `freezer.self_freezing`, a freezing control group asked for it itself
+            // unless one of its ancestors is freezing as well.
+            let mut parent_freezing = false;
+            for dir in path.ancestors().skip(1) {
+                let state = dir.join("freezer.state");
+                if !state.exists() {
+                    break;
+                }
+                if read_flat_file(&state)? != "THAWED" {
+                    parent_freezing = true;
+                    break;
+                }
+            }
+            !parent_freezing
+        };
+
+        Ok(FreezeStatus {
+            self_requested,
+            effectively_frozen,
+        })
+    }
+
     /// Attach a task to the control group.
     pub fn add_task(&self, tid: CgroupPid) -> Result<()> {
         if self.v2() {
             let subsystems = self.subsystems();
             if !subsystems.is_empty() {
                 let c = subsystems[0].to_controller();
-                let cgroup_type = self.get_cgroup_type()?;
                 // In cgroup v2, writing to the cgroup.threads file is only supported in thread mode.
-                if cgroup_type == *CGROUP_MODE_DOMAIN_THREADED
-                    || cgroup_type == *CGROUP_MODE_THREADED
-                {
+                if self.is_threaded()? {
                     // It is used to move the threads of a process into a cgroup in thread mode.
                     c.add_task(&tid)
                 } else {
@@ -387,7 +1489,13 @@ impl Cgroup {
                 let c = subsystems[0].to_controller();
                 // It is used to move a thread of the process to a cgroup,
                 // and other threads of the process will also move together.
-                c.add_task_by_tgid(&tgid)
+                c.add_task_by_tgid(&tgid).map_err(|e| {
+                    // The kernel only reports EOPNOTSUPP, tell why.
+                    match self.cgroup_type() {
+                        Ok(CgroupType::DomainInvalid) => Error::with_cause(DomainInvalid, e),
+                        _ => e,
+                    }
+                })
             } else {
                 Err(Error::new(SubsystemsEmpty))
             }
@@ -398,6 +1506,31 @@ impl Cgroup {
         }
     }
 
+    /// Attach processes to the control group by thread group id, like `add_task_by_tgid()` but
+    /// opening `cgroup.procs` once for all of them.
+    ///
+    /// A process that can't be attached, e.g. because it exited, doesn't stop the others: the
+    /// failed ones are returned with their error. On v1, where every hierarchy is written, the
+    /// first error of each process is kept.
+    pub fn add_tasks(&self, pids: &[CgroupPid]) -> Result<Vec<(CgroupPid, Error)>> {
+        let subsystems = self.subsystems();
+        if subsystems.is_empty() {
+            return Err(Error::new(SubsystemsEmpty));
+        }
+        // all the controllers share the directory on v2
+        let count = if self.v2() { 1 } else { subsystems.len() };
+
+        let mut failed: Vec<(CgroupPid, Error)> = vec![];
+        for sub in subsystems.iter().take(count) {
+            for (pid, e) in sub.to_controller().add_tasks_by_tgid(pids)? {
+                if !failed.iter().any(|(p, _)| *p == pid) {
+                    failed.push((pid, e));
+                }
+            }
+        }
+        Ok(failed)
+    }
+
     /// set cgroup.type
     pub fn set_cgroup_type(&self, cgroup_type: &str) -> Result<()> {
         if self.v2() {
@@ -409,7 +1542,10 @@ impl Cgroup {
                 Err(Error::new(SubsystemsEmpty))
             }
         } else {
-            Err(Error::new(CgroupVersion))
+            Err(Error::not_supported(
+                "cgroup.type",
+                "only available on cgroup v2",
+            ))
         }
     }
 
@@ -425,8 +1561,79 @@ impl Cgroup {
                 Err(Error::new(SubsystemsEmpty))
             }
         } else {
-            Err(Error::new(CgroupVersion))
+            Err(Error::not_supported(
+                "cgroup.type",
+                "only available on cgroup v2",
+            ))
+        }
+    }
+
+    /// Returns the type of the control group, found in `cgroup.type`. Only supported for v2
+    /// cgroups.
+    ///
+    /// Callers should check for `CgroupType::DomainInvalid` before populating a control group,
+    /// as such a control group rejects processes.
+    pub fn cgroup_type(&self) -> Result<CgroupType> {
+        self.get_cgroup_type()?.parse()
+    }
+
+    /// Sets the type of the control group in `cgroup.type`. Only supported for v2 cgroups.
+    ///
+    /// The kernel only accepts turning a domain control group threaded, which makes its parent
+    /// the domain at the root of a threaded subtree, and only if the parent can become one,
+    /// e.g. it has no domain controllers enabled for its children. The other transitions, like
+    /// going back to `Domain`, fail with `ErrorKind::CgroupTypeRejected` rather than
+    /// `WriteFailed`, with the kernel error as the cause.
+    pub fn set_type(&self, cgroup_type: CgroupType) -> Result<()> {
+        if !self.v2() {
+            return Err(Error::not_supported(
+                "cgroup.type",
+                "only available on cgroup v2",
+            ));
+        }
+
+        let value = cgroup_type.to_string();
+        fs::write(self.fs_path().join("cgroup.type"), &value).map_err(|e| match e.raw_os_error() {
+            Some(libc::EOPNOTSUPP) | Some(libc::EINVAL) => {
+                Error::with_cause(CgroupTypeRejected(value), e)
+            }
+            _ => Error::with_cause(WriteFailed("cgroup.type".to_string(), value), e),
+        })
+    }
+
+    /// Returns whether the control group is in threaded mode, i.e. is either a threaded control
+    /// group or the domain at the root of a threaded subtree. Only supported for v2 cgroups.
+    pub fn is_threaded(&self) -> Result<bool> {
+        let cgroup_type = self.get_cgroup_type()?;
+        Ok(cgroup_type == CGROUP_MODE_DOMAIN_THREADED || cgroup_type == CGROUP_MODE_THREADED)
+    }
+
+    /// Returns the number of threads in the control group, found in `cgroup.threads`. Only
+    /// supported for v2 cgroups in threaded mode, see `is_threaded()`.
+    pub fn thread_count(&self) -> Result<usize> {
+        if !self.is_threaded()? {
+            return Err(Error::new(CgroupMode));
         }
+
+        let file_name = "cgroup.threads";
+        let content = fs::read_to_string(self.fs_path().join(file_name))
+            .map_err(|e| Error::with_cause(ReadFailed(file_name.to_string()), e))?;
+
+        Ok(content.lines().filter(|l| !l.trim().is_empty()).count())
+    }
+
+    /// Moves the thread `tid` to the control group, same as `add_task()`.
+    ///
+    /// On v2 the control group must be in threaded mode, see `is_threaded()`, and the thread
+    /// must belong to a process of the same threaded subtree.
+    pub fn add_thread(&self, tid: CgroupPid) -> Result<()> {
+        self.add_task(tid)
+    }
+
+    /// Returns the threads in the control group, found in `cgroup.threads` on v2, same as
+    /// `tasks()`.
+    pub fn threads(&self) -> Vec<CgroupPid> {
+        self.tasks()
     }
 
     /// Set notify_on_release to the control group.
@@ -447,6 +1654,8 @@ impl Cgroup {
 
     /// Returns an Iterator that can be used to iterate over the procs that are currently in the
     /// control group.
+    ///
+    /// Only the processes directly in the control group are listed, see `own_procs()`.
     pub fn procs(&self) -> Vec<CgroupPid> {
         // Collect the procs from all subsystems
         let mut v = if self.v2() {
@@ -472,6 +1681,166 @@ impl Cgroup {
         v
     }
 
+    /// Same as `procs()`, but reads the list lazily, which avoids reading the whole list of a
+    /// large control group when only checking membership or taking the first processes.
+    ///
+    /// Unlike `procs()` the processes aren't sorted. On v1 the list of each hierarchy is read in
+    /// turn, the processes already returned being skipped.
+    pub fn procs_iter(&self) -> impl Iterator<Item = CgroupPid> + '_ {
+        self.pids_iter(|c| c.procs_iter())
+    }
+
+    /// Same as `procs()`, but re-reads the list, up to `PROCS_SNAPSHOT_RETRIES` more times, as
+    /// long as processes forked or exited in the control group while it was being read.
+    ///
+    /// Changes are noticed through `pids.current` when the pids controller is available and, on
+    /// v2, through `cgroup.events`. This only narrows the window for torn reads: a process can
+    /// still fork and exit in between, and only freezing the control group gives a perfectly
+    /// consistent view. `stabilized` tells whether the returned list was read without noticing
+    /// any change.
+    pub fn procs_snapshot(&self) -> ProcsSnapshot {
+        let mut procs = vec![];
+        for _ in 0..=PROCS_SNAPSHOT_RETRIES {
+            let before = self.procs_counters();
+            procs = self.procs();
+            if self.procs_counters() == before {
+                return ProcsSnapshot {
+                    procs,
+                    stabilized: true,
+                };
+            }
+        }
+
+        ProcsSnapshot {
+            procs,
+            stabilized: false,
+        }
+    }
+
+    // Values changing whenever a process joins or leaves the control group, if any can be read.
+    fn procs_counters(&self) -> Vec<Option<String>> {
+        let mut counters = vec![];
+        if let Some(pids) = self.controller_of::<PidController>() {
+            counters.push(read_flat_file(&pids.path().join("pids.current")).ok());
+        }
+        if self.v2() && !self.is_root() {
+            counters.push(read_flat_file(&self.fs_path().join("cgroup.events")).ok());
+        }
+        counters
+    }
+
+    /// Returns the processes directly in the control group, leaving out the ones of its
+    /// descendants, same as `procs()`.
+    ///
+    /// On v2, processes can only live in the leaves of the hierarchy (the "no internal process"
+    /// rule): once a control group enables controllers for its children in
+    /// `cgroup.subtree_control`, its own `cgroup.procs` stays empty, even though its subtree
+    /// holds processes. Use `all_procs_recursive()` to list the processes of the whole subtree.
+    pub fn own_procs(&self) -> Vec<CgroupPid> {
+        self.procs()
+    }
+
+    /// Same as `procs()`, but leaves out kernel threads, which are only ever found in the root
+    /// control group.
+    ///
+    /// This costs an extra read of `/proc/<pid>/stat` for each process. Processes that exit
+    /// while the list is being filtered are left out as well.
+    pub fn user_procs(&self) -> Vec<CgroupPid> {
+        self.procs()
+            .into_iter()
+            .filter(|pid| is_kernel_thread(pid.pid) == Some(false))
+            .collect()
+    }
+
+    /// Returns the processes of the control group and of all its descendants, as opposed to
+    /// `own_procs()`.
+    ///
+    /// Descendants that can't be read, e.g. because they got removed during the walk, are
+    /// skipped with a warning.
+    pub fn all_procs_recursive(&self) -> Result<Vec<CgroupPid>> {
+        let mut pids = vec![];
+        for dir in self.fs_dirs() {
+            pids.append(&mut read_procs(&dir)?);
+            collect_descendant_procs(&dir, &mut pids);
+        }
+
+        pids.sort();
+        pids.dedup();
+        Ok(pids)
+    }
+
+    /// Returns the number of tasks in the control group and all its descendants, as counted by
+    /// the pids controller.
+    ///
+    /// The kernel already accounts the tasks of the descendants in `pids.current`, so it is read
+    /// as-is rather than summed over the subtree, which would count them several times. The root
+    /// control group has no `pids.current` on v2, the ones of its children are summed instead,
+    /// leaving out the tasks directly in the root.
+    pub fn pids_current_recursive(&self) -> Result<u64> {
+        let pids = self
+            .controller_of::<PidController>()
+            .ok_or_else(|| Error::new(SubsystemsEmpty))?;
+        let path = pids.path();
+        if path.join("pids.current").exists() {
+            return pids.get_pid_current();
+        }
+
+        let entries = fs::read_dir(path)
+            .map_err(|e| Error::with_cause(ReadFailed(path.display().to_string()), e))?;
+        let mut total = 0;
+        for entry in entries.flatten() {
+            let current = entry.path().join("pids.current");
+            if !current.exists() {
+                continue;
+            }
+            total += read_flat_file(&current)?
+                .parse::<u64>()
+                .map_err(|e| Error::with_cause(ParseError, e))?;
+        }
+        Ok(total)
+    }
+
+    /// Returns the processes listed in `cgroup.procs` that `/proc/<pid>/cgroup` still reports as
+    /// members of the control group, e.g. to confirm that all the tasks were migrated out of it
+    /// despite `cgroup.procs` being momentarily stale.
+    ///
+    /// Processes that exited in the meantime are ignored.
+    pub fn verify_empty_via_proc(&self) -> Result<Vec<CgroupPid>> {
+        let own_path = format!("/{}", self.relative_path());
+        let mut stragglers = vec![];
+
+        for pid in self.procs() {
+            let path = format!("/proc/{}/cgroup", pid.pid);
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e)
+                    if e.kind() == std::io::ErrorKind::NotFound
+                        || e.raw_os_error() == Some(libc::ESRCH) =>
+                {
+                    continue
+                }
+                Err(e) => return Err(Error::with_cause(ReadFailed(path), e)),
+            };
+            let paths = parse_proc_cgroup(&content);
+
+            let member = if self.v2() {
+                paths.get("") == Some(&own_path)
+            } else {
+                self.subsystems.iter().any(|s| {
+                    paths.get(&s.controller_name()).is_some_and(|p| {
+                        let base = s.to_controller().base().to_string_lossy().to_string();
+                        get_cgroup_destination(base, p.to_owned()) == own_path
+                    })
+                })
+            };
+            if member {
+                stragglers.push(pid);
+            }
+        }
+
+        Ok(stragglers)
+    }
+
     /// Returns an Iterator that can be used to iterate over the tasks that are currently in the
     /// control group.
     pub fn tasks(&self) -> Vec<CgroupPid> {
@@ -499,6 +1868,25 @@ impl Cgroup {
         v
     }
 
+    /// Same as `tasks()`, but reads the list lazily, see `procs_iter()`.
+    pub fn tasks_iter(&self) -> impl Iterator<Item = CgroupPid> + '_ {
+        self.pids_iter(|c| c.tasks_iter())
+    }
+
+    fn pids_iter<'a>(
+        &'a self,
+        pids: fn(&dyn Controller) -> PidIter,
+    ) -> impl Iterator<Item = CgroupPid> + 'a {
+        // all the controllers share the same directory on v2
+        let count = if self.v2() { 1 } else { usize::MAX };
+        let mut seen = HashSet::new();
+        self.subsystems()
+            .iter()
+            .take(count)
+            .flat_map(move |sub| pids(sub.to_controller()))
+            .filter(move |pid| seen.insert(pid.pid))
+    }
+
     /// Checks if the cgroup exists.
     ///
     /// Returns true if at least one subsystem exists.
@@ -517,29 +1905,67 @@ fn enable_controllers(controllers: &[String], path: &Path) {
     }
 }
 
-fn supported_controllers() -> Vec<String> {
-    let p = format!("{}/{}", UNIFIED_MOUNTPOINT, "cgroup.controllers");
-    let ret = fs::read_to_string(p.as_str());
+// Returns the controllers known to this crate in the `cgroup.subtree_control` file `p`.
+fn read_subtree_control(p: &Path) -> Result<Vec<Controllers>> {
+    let content = fs::read_to_string(p)
+        .map_err(|e| Error::with_cause(ReadFailed("cgroup.subtree_control".to_string()), e))?;
+
+    Ok(content
+        .split_whitespace()
+        .filter_map(|c| c.parse().ok())
+        .collect())
+}
+
+// Writes the `+<controller>` and `-<controller>` changes one by one to the
+// `cgroup.subtree_control` file `p`.
+fn write_subtree_control<I: Iterator<Item = String>>(p: &Path, changes: I) -> Result<()> {
+    for change in changes {
+        fs::write(p, &change).map_err(|e| {
+            if change.starts_with('+') && e.raw_os_error() == Some(libc::EBUSY) {
+                let controller = change[1..].to_string();
+                return Error::with_cause(ControllerEnableBlockedByProcesses(controller), e);
+            }
+            Error::with_cause(WriteFailed("cgroup.subtree_control".to_string(), change), e)
+        })?;
+    }
+
+    Ok(())
+}
+
+// Enables the missing `controllers` in the `cgroup.subtree_control` file `p`.
+fn enable_subtree_control(p: &Path, controllers: &[Controllers]) -> Result<()> {
+    let current = read_subtree_control(p)?;
+    let changes = controllers
+        .iter()
+        .filter(|c| !current.contains(c))
+        .map(|c| format!("+{}", v2_controller_name(c)));
+
+    write_subtree_control(p, changes)
+}
+
+fn supported_controllers(root: &Path) -> Vec<String> {
+    let ret = fs::read_to_string(root.join("cgroup.controllers"));
     ret.unwrap_or_default()
         .split(' ')
         .map(|x| x.trim().to_string())
         .collect::<Vec<String>>()
 }
 
+// Returns whether the control group directory was created, false if it already existed.
 fn create_v2_cgroup(
     root: PathBuf,
     path: &str,
     specified_controllers: &Option<Vec<String>>,
-) -> Result<()> {
+) -> Result<bool> {
     // controler list ["memory", "cpu"]
     let controllers = if let Some(s_controllers) = specified_controllers.clone() {
-        if verify_supported_controllers(s_controllers.as_ref()) {
+        if controllers_supported(&root, s_controllers.as_ref()) {
             s_controllers
         } else {
             return Err(Error::new(ErrorKind::SpecifiedControllers));
         }
     } else {
-        supported_controllers()
+        supported_controllers(&root)
     };
 
     let mut fp = root;
@@ -550,15 +1976,12 @@ fn create_v2_cgroup(
     // path: "a/b/c"
     let elements = path.split('/').collect::<Vec<&str>>();
     let last_index = elements.len() - 1;
+    let mut created = false;
     for (i, ele) in elements.iter().enumerate() {
         // ROOT/a
         fp.push(ele);
         // create dir, need not check if is a file or directory
-        if !fp.exists() {
-            if let Err(e) = std::fs::create_dir(fp.clone()) {
-                return Err(Error::with_cause(ErrorKind::FsError, e));
-            }
-        }
+        created = create_dir(&fp)?;
 
         if i < last_index {
             // enable controllers for substree
@@ -566,11 +1989,42 @@ fn create_v2_cgroup(
         }
     }
 
-    Ok(())
+    Ok(created)
+}
+
+// Creates the directory `dir` and its missing parents. Returns false if `dir` already exists,
+// which isn't an error so that concurrent creations don't fail.
+fn create_dir(dir: &Path) -> Result<bool> {
+    if let Some(parent) = dir.parent() {
+        fs::create_dir_all(parent).map_err(|e| Error::with_cause(ErrorKind::FsError, e))?;
+    }
+    match fs::create_dir(dir) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(Error::with_cause(ErrorKind::FsError, e)),
+    }
+}
+
+// Rejects the names the kernel refuses or that escape the control group, see `Cgroup::create()`.
+fn check_name(path: &str) -> Result<()> {
+    let invalid = |component: &str| {
+        component == "."
+            || component == ".."
+            || component.contains('\0')
+            || component.starts_with("cgroup.")
+    };
+    match path.split('/').find(|c| invalid(c)) {
+        Some(component) => Err(Error::new(InvalidName(component.to_string()))),
+        None => Ok(()),
+    }
 }
 
 pub fn verify_supported_controllers(controllers: &[String]) -> bool {
-    let sc = supported_controllers();
+    controllers_supported(Path::new(UNIFIED_MOUNTPOINT), controllers)
+}
+
+fn controllers_supported(root: &Path, controllers: &[String]) -> bool {
+    let sc = supported_controllers(root);
     for controller in controllers.iter() {
         if !sc.contains(controller) {
             return false;
@@ -579,6 +2033,11 @@ pub fn verify_supported_controllers(controllers: &[String]) -> bool {
     true
 }
 
+/// Returns the control groups of the current process, per controller (v1) or with an empty
+/// key (v2), as found in `/proc/self/cgroup`.
+///
+/// Inside a cgroup namespace the paths are relative to the root of the namespace rather than to
+/// the root of the hierarchy, see `Cgroup::is_namespaced()`.
 pub fn get_cgroups_relative_paths() -> Result<HashMap<String, String>> {
     let path = "/proc/self/cgroup".to_string();
     get_cgroups_relative_paths_by_path(path)
@@ -589,11 +2048,60 @@ pub fn get_cgroups_relative_paths_by_pid(pid: u32) -> Result<HashMap<String, Str
     get_cgroups_relative_paths_by_path(path)
 }
 
-fn get_cgroup_destination(mut mount_root: String, pidpath: String) -> String {
+fn read_flat_file(path: &Path) -> Result<String> {
+    fs::read_to_string(path)
+        .map(|s| s.trim().to_string())
+        .map_err(|e| Error::with_cause(ReadFailed(path.display().to_string()), e))
+}
+
+fn read_procs(dir: &Path) -> Result<Vec<CgroupPid>> {
+    let path = dir.join("cgroup.procs");
+    let content = fs::read_to_string(&path)
+        .map_err(|e| Error::with_cause(ReadFailed(path.display().to_string()), e))?;
+
+    Ok(content
+        .lines()
+        .filter_map(|l| l.trim().parse::<u64>().ok())
+        .map(CgroupPid::from)
+        .collect())
+}
+
+fn collect_descendant_procs(dir: &Path, pids: &mut Vec<CgroupPid>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("skipping control group {}: {}", dir.display(), e);
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let child = entry.path();
+        if !child.is_dir() {
+            continue;
+        }
+        match read_procs(&child) {
+            Ok(mut procs) => pids.append(&mut procs),
+            Err(e) => {
+                warn!("skipping control group {}: {}", child.display(), e);
+                continue;
+            }
+        }
+        collect_descendant_procs(&child, pids);
+    }
+}
+
+// Both paths are relative to the root of the cgroup namespace of the reader. The path is kept
+// as-is when it isn't below the mount root, e.g. for a hierarchy mounted from outside of the
+// namespace, whose mount root starts with "/..".
+fn get_cgroup_destination(mount_root: String, pidpath: String) -> String {
     if mount_root == "/" {
-        mount_root = String::from("");
+        return pidpath;
+    }
+    match Path::new(&pidpath).strip_prefix(&mount_root) {
+        Ok(rest) => format!("/{}", rest.display()),
+        Err(_) => pidpath,
     }
-    pidpath.trim_start_matches(&mount_root).to_string()
 }
 
 pub fn existing_path(paths: HashMap<String, String>) -> Result<HashMap<String, String>> {
@@ -617,9 +2125,13 @@ pub fn existing_path(paths: HashMap<String, String>) -> Result<HashMap<String, S
 }
 
 fn get_cgroups_relative_paths_by_path(path: String) -> Result<HashMap<String, String>> {
-    let mut m = HashMap::new();
     let content =
         fs::read_to_string(path.clone()).map_err(|e| Error::with_cause(ReadFailed(path), e))?;
+    Ok(parse_proc_cgroup(&content))
+}
+
+fn parse_proc_cgroup(content: &str) -> HashMap<String, String> {
+    let mut m = HashMap::new();
     // cgroup path may have ":" , likes
     // "2:cpu,cpuacct:/system.slice/containerd.service/test.slice:cri-containerd:96b37a2edf84351487f42039e137427f1812f678850675fac214caf597ee5e4a"
     for line in content.lines() {
@@ -632,5 +2144,315 @@ fn get_cgroups_relative_paths_by_path(path: String) -> Result<HashMap<String, St
             });
         }
     }
-    Ok(m)
+    m
+}
+
+/// Returns the files of the limits of `res` that the kernel refuses to set on the root control
+/// group, along with their controller.
+fn root_unconfigurable_files(res: &Resources, v2: bool) -> Vec<(Controllers, &'static str)> {
+    let mem = &res.memory;
+    let cpu = &res.cpu;
+    // (controller, is set, v1 file, v2 file), an empty file name means it can be set on the root
+    let limits = [
+        (
+            Controllers::Mem,
+            mem.memory_hard_limit.is_some(),
+            "memory.limit_in_bytes",
+            "memory.max",
+        ),
+        (
+            Controllers::Mem,
+            mem.memory_swap_limit.is_some(),
+            "memory.memsw.limit_in_bytes",
+            "memory.swap.max",
+        ),
+        (
+            Controllers::Mem,
+            mem.memory_soft_limit.is_some(),
+            "",
+            "memory.low",
+        ),
+        (
+            Controllers::Mem,
+            mem.kernel_memory_limit.is_some(),
+            "memory.kmem.limit_in_bytes",
+            "",
+        ),
+        (
+            Controllers::Mem,
+            mem.kernel_tcp_memory_limit.is_some(),
+            "memory.kmem.tcp.limit_in_bytes",
+            "",
+        ),
+        (
+            Controllers::Pids,
+            res.pid.maximum_number_of_processes.is_some(),
+            "pids.max",
+            "pids.max",
+        ),
+        (
+            Controllers::Cpu,
+            cpu.shares.is_some(),
+            "cpu.shares",
+            "cpu.weight",
+        ),
+        (
+            Controllers::Cpu,
+            cpu.quota.is_some(),
+            "cpu.cfs_quota_us",
+            "cpu.max",
+        ),
+        (
+            Controllers::Cpu,
+            cpu.period.is_some(),
+            "cpu.cfs_period_us",
+            "cpu.max",
+        ),
+        (
+            Controllers::CpuSet,
+            cpu.cpus.is_some(),
+            "cpuset.cpus",
+            "cpuset.cpus",
+        ),
+        (
+            Controllers::CpuSet,
+            cpu.mems.is_some(),
+            "cpuset.mems",
+            "cpuset.mems",
+        ),
+    ];
+
+    limits
+        .iter()
+        .filter_map(|(controller, set, v1_file, v2_file)| {
+            let file = if v2 { *v2_file } else { *v1_file };
+            if *set && !file.is_empty() {
+                Some((controller.clone(), file))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Returns the settings of `res` that `Cgroup::read_resources()` can't read back.
+fn non_reversible_settings(res: &Resources) -> Vec<String> {
+    let mut settings = Vec::new();
+    if !res.memory.attrs.is_empty() {
+        settings.push("memory attrs".to_string());
+    }
+    if !res.cpu.attrs.is_empty() {
+        settings.push("cpu attrs".to_string());
+    }
+    if !res.blkio.attrs.is_empty() {
+        settings.push("blkio attrs".to_string());
+    }
+    if !res.devices.devices.is_empty() {
+        settings.push("devices".to_string());
+    }
+    settings
+}
+
+/// Returns the name of a controller on cgroup v2, where `blkio` is named `io`.
+fn v2_controller_name(controller: &Controllers) -> String {
+    match controller {
+        Controllers::BlkIo => "io".to_string(),
+        c => c.to_string(),
+    }
+}
+
+fn parse_cgroup_stat(content: &str) -> CgroupStat {
+    let mut stat = CgroupStat::default();
+    for line in content.lines() {
+        let (key, value) = match line.split_once(' ') {
+            Some((key, value)) => match value.trim().parse::<u64>() {
+                Ok(value) => (key, value),
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+
+        if key == "nr_descendants" {
+            stat.nr_descendants = value;
+        } else if key == "nr_dying_descendants" {
+            stat.nr_dying_descendants = value;
+        } else if let Some(name) = key.strip_prefix("nr_dying_subsys_") {
+            if let Ok(controller) = name.parse() {
+                stat.nr_dying_subsys.insert(controller, value);
+            }
+        } else if let Some(name) = key.strip_prefix("nr_subsys_") {
+            if let Ok(controller) = name.parse() {
+                stat.nr_subsys.insert(controller, value);
+            }
+        }
+    }
+
+    stat
+}
+
+fn parse_cgroup_events(content: &str) -> CgroupEvents {
+    let mut events = CgroupEvents::default();
+    for line in content.lines() {
+        match line.split_once(' ') {
+            Some(("populated", value)) => events.populated = value.trim() == "1",
+            Some(("frozen", value)) => events.frozen = value.trim() == "1",
+            _ => {}
+        }
+    }
+
+    events
+}
+
+// PF_KTHREAD from include/linux/sched.h
+const PF_KTHREAD: u64 = 0x0020_0000;
+
+// Removes the control group directory `dir` after its descendants, the deepest ones first.
+fn remove_subtree(dir: &Path) -> Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    let entries = fs::read_dir(dir)
+        .map_err(|e| Error::with_cause(ReadFailed(dir.display().to_string()), e))?;
+    for entry in entries.flatten() {
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            remove_subtree(&entry.path())?;
+        }
+    }
+    fs::remove_dir(dir).map_err(|e| Error::with_cause(RemoveFailed, e))
+}
+
+// Whether writing a pid failed because the process is gone.
+fn is_esrch(e: &Error) -> bool {
+    std::error::Error::source(e)
+        .and_then(|cause| cause.downcast_ref::<std::io::Error>())
+        .and_then(|cause| cause.raw_os_error())
+        == Some(libc::ESRCH)
+}
+
+/// Tells whether `pid` is a kernel thread from the flags in `/proc/<pid>/stat`, or `None` if the
+/// process doesn't exist anymore.
+fn is_kernel_thread(pid: u64) -> Option<bool> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name may contain spaces and parentheses, the fields after it don't.
+    let (_, fields) = stat.rsplit_once(')')?;
+    // state, ppid, pgrp, session, tty_nr, tpgid, flags
+    let flags = fields.split_whitespace().nth(6)?.parse::<u64>().ok()?;
+    Some(flags & PF_KTHREAD != 0)
+}
+
+/// Returns the less restrictive of two limits, a negative limit meaning unlimited.
+fn raise_limit(current: i64, new: i64) -> i64 {
+    if current < 0 || new < 0 {
+        -1
+    } else {
+        current.max(new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::cgroup::{
+        check_name, get_cgroup_destination, is_esrch, parse_cgroup_events, parse_cgroup_stat,
+        CgroupEvents,
+    };
+    use crate::fs::error::{Error, ErrorKind};
+    use crate::fs::Controllers;
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("").is_ok());
+        assert!(check_name("a/b").is_ok());
+        assert!(check_name("/a/b/").is_ok());
+        assert!(check_name("system.slice/foo.service").is_ok());
+        assert!(check_name("a/cgroupfoo").is_ok());
+
+        for (name, component) in [
+            ("..", ".."),
+            ("a/../b", ".."),
+            ("a/./b", "."),
+            ("a/cgroup.procs", "cgroup.procs"),
+            ("cgroup.foo/a", "cgroup.foo"),
+            ("a/b\0c", "b\0c"),
+        ] {
+            assert_eq!(
+                check_name(name).unwrap_err().kind(),
+                &ErrorKind::InvalidName(component.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_esrch() {
+        let write_failed = || ErrorKind::WriteFailed("cgroup.procs".to_string(), "1".to_string());
+        let esrch = std::io::Error::from_raw_os_error(libc::ESRCH);
+        assert!(is_esrch(&Error::with_cause(write_failed(), esrch)));
+        let einval = std::io::Error::from_raw_os_error(libc::EINVAL);
+        assert!(!is_esrch(&Error::with_cause(write_failed(), einval)));
+        assert!(!is_esrch(&Error::new(write_failed())));
+    }
+
+    #[test]
+    fn test_parse_cgroup_stat() {
+        let content = "nr_descendants 3
+nr_dying_descendants 1
+nr_subsys_cpu 4
+nr_subsys_io 2
+nr_subsys_misc 1
+nr_dying_subsys_memory 1
+";
+        let stat = parse_cgroup_stat(content);
+        assert_eq!(stat.nr_descendants, 3);
+        assert_eq!(stat.nr_dying_descendants, 1);
+        assert_eq!(stat.nr_subsys.len(), 3);
+        assert_eq!(stat.nr_subsys[&Controllers::Cpu], 4);
+        assert_eq!(stat.nr_subsys[&Controllers::BlkIo], 2);
+        assert_eq!(stat.nr_subsys[&Controllers::Misc], 1);
+        assert_eq!(stat.nr_dying_subsys.len(), 1);
+        assert_eq!(stat.nr_dying_subsys[&Controllers::Mem], 1);
+
+        // older kernels only have the descendant counts
+        let stat = parse_cgroup_stat("nr_descendants 0\nnr_dying_descendants 0\n");
+        assert_eq!(stat.nr_descendants, 0);
+        assert!(stat.nr_subsys.is_empty());
+        assert!(stat.nr_dying_subsys.is_empty());
+    }
+
+    #[test]
+    fn test_parse_cgroup_events() {
+        assert_eq!(
+            parse_cgroup_events("populated 1\nfrozen 0\n"),
+            CgroupEvents {
+                populated: true,
+                frozen: false
+            }
+        );
+        assert_eq!(
+            parse_cgroup_events("populated 0\nfrozen 1\n"),
+            CgroupEvents {
+                populated: false,
+                frozen: true
+            }
+        );
+        // kernels older than 5.2 have no frozen key
+        assert_eq!(
+            parse_cgroup_events("populated 1\n"),
+            CgroupEvents {
+                populated: true,
+                frozen: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_cgroup_destination() {
+        let dest = |root: &str, path: &str| get_cgroup_destination(root.into(), path.into());
+        assert_eq!(dest("/", "/user.slice/a"), "/user.slice/a");
+        assert_eq!(dest("/docker/abc", "/docker/abc/a"), "/a");
+        assert_eq!(dest("/docker/abc", "/docker/abc"), "/");
+        assert_eq!(dest("/a", "/a/a/b"), "/a/b");
+        assert_eq!(dest("/a", "/ab/c"), "/ab/c");
+        // mounted from outside of the cgroup namespace
+        assert_eq!(dest("/../..", "/"), "/");
+    }
 }
@@ -60,9 +60,13 @@
 //!      .build(h).unwrap();
 //! ```
 
+use crate::fs::cpuset::CpuList;
+use crate::fs::error::ErrorKind;
+use crate::fs::rdma::RdmaLimit;
 use crate::fs::{
-    BlkIoDeviceResource, BlkIoDeviceThrottleResource, Cgroup, DeviceResource, Error, Hierarchy,
-    HugePageResource, MaxValue, NetworkPriority, Resources,
+    parse_limit, BlkIoDeviceResource, BlkIoDeviceThrottleResource, Cgroup, Controllers,
+    DeviceResource, Error, Hierarchy, HugePageResource, MaxValue, MiscResource, NetworkPriority,
+    RdmaResource, Resources,
 };
 
 macro_rules! gen_setter {
@@ -82,6 +86,12 @@ pub struct CgroupBuilder {
     resources: Resources,
     /// List of controllers specifically enabled in the control group.
     specified_controllers: Option<Vec<String>>,
+    /// The first invalid value given to a builder, reported by `build()`.
+    error: Option<Error>,
+    /// Whether to enable the controllers in the ancestors, see `enable_ancestor_controllers()`.
+    enable_ancestor_controllers: bool,
+    /// The user and group to delegate the control group to, see `delegate_to()`.
+    delegate_to: Option<(u32, u32)>,
 }
 
 impl CgroupBuilder {
@@ -93,6 +103,9 @@ impl CgroupBuilder {
             name: name.to_owned(),
             resources: Resources::default(),
             specified_controllers: None,
+            error: None,
+            enable_ancestor_controllers: false,
+            delegate_to: None,
         }
     }
 
@@ -111,6 +124,12 @@ impl CgroupBuilder {
         CpuResourceBuilder { cgroup: self }
     }
 
+    /// Builds the cpuset resources of the control group, the CPUs and memory nodes its tasks can
+    /// use.
+    pub fn cpuset(self) -> CpuSetResourceBuilder {
+        CpuSetResourceBuilder { cgroup: self }
+    }
+
     /// Builds the devices resources of the control group, disallowing or
     /// allowing access to certain devices in the system.
     pub fn devices(self) -> DeviceResourceBuilder {
@@ -128,6 +147,16 @@ impl CgroupBuilder {
         HugepagesResourceBuilder { cgroup: self }
     }
 
+    /// Builds the misc resources available to the control group (cgroup v2 only).
+    pub fn misc(self) -> MiscResourceBuilder {
+        MiscResourceBuilder { cgroup: self }
+    }
+
+    /// Builds the RDMA/IB resources available to the control group.
+    pub fn rdma(self) -> RdmaResourceBuilder {
+        RdmaResourceBuilder { cgroup: self }
+    }
+
     /// Builds the block I/O resources available for the control group.
     pub fn blkio(self) -> BlkIoResourcesBuilder {
         BlkIoResourcesBuilder {
@@ -138,15 +167,62 @@ impl CgroupBuilder {
 
     /// Finalize the control group, consuming the builder and creating the control group.
     pub fn build(self, hier: Box<dyn Hierarchy>) -> Result<Cgroup, Error> {
-        if let Some(controllers) = self.specified_controllers {
-            let cg = Cgroup::new_with_specified_controllers(hier, self.name, Some(controllers))?;
-            cg.apply(&self.resources)?;
-            Ok(cg)
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        let cg = if let Some(controllers) = self.specified_controllers.clone() {
+            Cgroup::new_with_specified_controllers(hier, self.name, Some(controllers))?
         } else {
-            let cg = Cgroup::new(hier, self.name)?;
-            cg.apply(&self.resources)?;
-            Ok(cg)
+            Cgroup::new(hier, self.name)?
+        };
+
+        // the cpuset controller isn't always enabled by the ancestors on v2
+        let cpuset = &self.resources.cpu;
+        if cg.v2() && (cpuset.cpus.is_some() || cpuset.mems.is_some()) {
+            cg.enable_controllers_in_ancestors(&[Controllers::CpuSet])?;
+        }
+
+        if self.enable_ancestor_controllers && cg.v2() {
+            let controllers = match &self.specified_controllers {
+                Some(names) => names
+                    .iter()
+                    .map(|name| name.parse())
+                    .collect::<Result<Vec<Controllers>, Error>>()?,
+                // the freezer is part of the core on v2, not a controller to enable
+                None => cg
+                    .subsystems()
+                    .iter()
+                    .map(|s| s.to_controller().control_type())
+                    .filter(|c| *c != Controllers::Freezer)
+                    .collect(),
+            };
+            cg.enable_controllers_in_ancestors(&controllers)?;
         }
+
+        cg.apply(&self.resources)?;
+        if let Some((uid, gid)) = self.delegate_to {
+            cg.delegate_to(uid, gid)?;
+        }
+        Ok(cg)
+    }
+
+    /// Enables the controllers of the control group in the `cgroup.subtree_control` of all its
+    /// ancestors when building it, failing if one of them can't be enabled (cgroup v2 only).
+    ///
+    /// The controllers are the specified ones, see `set_specified_controllers()`, or else all
+    /// the controllers of the hierarchy. Without this, a nested control group silently lacks
+    /// the controllers that an ancestor couldn't enable for its children.
+    pub fn enable_ancestor_controllers(mut self) -> Self {
+        self.enable_ancestor_controllers = true;
+        self
+    }
+
+    /// Delegates the control group to the user `uid` and group `gid` once it is built and its
+    /// resources applied, see `Cgroup::delegate_to()` (cgroup v2 only).
+    pub fn delegate_to(mut self, uid: u32, gid: u32) -> Self {
+        self.delegate_to = Some((uid, gid));
+        self
     }
 
     /// Specifically enable some controllers in the control group.
@@ -193,6 +269,43 @@ impl MemoryResourceBuilder {
     );
     gen_setter!(memory, MemController, set_swappiness, swappiness, u64);
 
+    /// Sets the swap limit in bytes, `-1` for no limit, written to `memory.swap.max` on v2 and
+    /// ignored on v1.
+    ///
+    /// Unlike `memory_swap_limit()`, which limits memory and swap together on both versions,
+    /// this limits the swap alone.
+    pub fn swap_max(mut self, bytes: i64) -> Self {
+        self.cgroup.resources.memory.swap_max = Some(if bytes == -1 {
+            MaxValue::Max
+        } else {
+            MaxValue::Value(bytes)
+        });
+        self
+    }
+
+    /// Sets the throttling limit in bytes, `-1` for no limit, written to `memory.high` on v2 and
+    /// ignored on v1.
+    pub fn high(mut self, bytes: i64) -> Self {
+        self.cgroup.resources.memory.memory_high = Some(if bytes == -1 {
+            MaxValue::Max
+        } else {
+            MaxValue::Value(bytes)
+        });
+        self
+    }
+
+    /// Sets the memory hard limit from a human readable size such as `"512M"`, or `"max"` for no
+    /// limit. See `parse_size()` for the accepted units; an invalid size makes `build()` fail.
+    pub fn limit_str(mut self, limit: &str) -> Self {
+        match parse_limit(limit) {
+            Ok(limit) => self.cgroup.resources.memory.memory_hard_limit = Some(limit),
+            Err(e) => {
+                self.cgroup.error.get_or_insert(e);
+            }
+        }
+        self
+    }
+
     /// Finish the construction of the memory resources of a control group.
     pub fn done(self) -> CgroupBuilder {
         self.cgroup
@@ -233,12 +346,69 @@ impl CpuResourceBuilder {
     gen_setter!(cpu, CpuController, set_rt_runtime, realtime_runtime, i64);
     gen_setter!(cpu, CpuController, set_rt_period, realtime_period, u64);
 
+    /// Sets the CFS bandwidth of the tasks: at most `quota` microseconds of CPU time every
+    /// `period` microseconds, `-1` as `quota` meaning unlimited. Same as `quota()` and
+    /// `period()` together, written to `cpu.max` on v2 and to `cpu.cfs_quota_us` and
+    /// `cpu.cfs_period_us` on v1.
+    pub fn max(mut self, quota: i64, period: u64) -> Self {
+        self.cgroup.resources.cpu.quota = Some(quota);
+        self.cgroup.resources.cpu.period = Some(period);
+        self
+    }
+
     /// Finish the construction of the cpu resources of a control group.
     pub fn done(self) -> CgroupBuilder {
         self.cgroup
     }
 }
 
+/// A builder that configures the cpuset controller of a control group.
+pub struct CpuSetResourceBuilder {
+    cgroup: CgroupBuilder,
+}
+
+impl CpuSetResourceBuilder {
+    /// Sets the CPUs the tasks can run on, as a list such as `"0-3,6"`. An invalid list makes
+    /// `build()` fail.
+    pub fn cpus(mut self, cpus: &str) -> Self {
+        match check_cpuset_list(cpus) {
+            Ok(()) => self.cgroup.resources.cpu.cpus = Some(cpus.to_string()),
+            Err(e) => {
+                self.cgroup.error.get_or_insert(e);
+            }
+        }
+        self
+    }
+
+    /// Sets the memory nodes the tasks can use, with the same syntax as `cpus()`. An invalid
+    /// list makes `build()` fail.
+    pub fn mems(mut self, mems: &str) -> Self {
+        match check_cpuset_list(mems) {
+            Ok(()) => self.cgroup.resources.cpu.mems = Some(mems.to_string()),
+            Err(e) => {
+                self.cgroup.error.get_or_insert(e);
+            }
+        }
+        self
+    }
+
+    /// Finish the construction of the cpuset resources of a control group.
+    pub fn done(self) -> CgroupBuilder {
+        self.cgroup
+    }
+}
+
+// Checks that `list` is a valid list of CPUs or memory nodes, e.g. `0-3,6`.
+fn check_cpuset_list(list: &str) -> Result<(), Error> {
+    match list.parse::<CpuList>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::InvalidArgument(format!(
+            "invalid cpu or memory node list: {:?}",
+            list
+        )))),
+    }
+}
+
 /// A builder that configures the devices controller of a control group.
 pub struct DeviceResourceBuilder {
     cgroup: CgroupBuilder,
@@ -264,6 +434,13 @@ impl DeviceResourceBuilder {
         self
     }
 
+    /// Add a device rule, e.g. one built with `DeviceResource::mknod_only()` or
+    /// `DeviceResource::rw()`.
+    pub fn rule(mut self, rule: DeviceResource) -> DeviceResourceBuilder {
+        self.cgroup.resources.devices.devices.push(rule);
+        self
+    }
+
     /// Finish the construction of the devices resources of a control group.
     pub fn done(self) -> CgroupBuilder {
         self.cgroup
@@ -289,6 +466,17 @@ impl NetworkResourceBuilder {
         self
     }
 
+    /// Set the priority of the tasks for each networking device of `priorities`, see
+    /// `priority()`.
+    pub fn priorities(mut self, priorities: Vec<(String, u64)>) -> NetworkResourceBuilder {
+        self.cgroup.resources.network.priorities.extend(
+            priorities
+                .into_iter()
+                .map(|(name, priority)| NetworkPriority { name, priority }),
+        );
+        self
+    }
+
     /// Finish the construction of the network resources of a control group.
     pub fn done(self) -> CgroupBuilder {
         self.cgroup
@@ -302,12 +490,13 @@ pub struct HugepagesResourceBuilder {
 
 impl HugepagesResourceBuilder {
     /// Limit the usage of certain hugepages (determined by `size`) to be at most `limit` bytes.
+    ///
+    /// Can be chained to limit several sizes, e.g. both `2MB` and `1GB`, a later limit of the
+    /// same size replacing the earlier one.
     pub fn limit(mut self, size: String, limit: u64) -> HugepagesResourceBuilder {
-        self.cgroup
-            .resources
-            .hugepages
-            .limits
-            .push(HugePageResource { size, limit });
+        let limits = &mut self.cgroup.resources.hugepages.limits;
+        limits.retain(|l| l.size != size);
+        limits.push(HugePageResource { size, limit });
         self
     }
 
@@ -317,6 +506,48 @@ impl HugepagesResourceBuilder {
     }
 }
 
+/// A builder that configures the misc controller of a control group.
+pub struct MiscResourceBuilder {
+    cgroup: CgroupBuilder,
+}
+
+impl MiscResourceBuilder {
+    /// Limit the usage of the misc resource `key`, e.g. `sev`, to be at most `max`, a later
+    /// limit of the same resource replacing the earlier one.
+    pub fn max(mut self, key: String, max: MaxValue) -> MiscResourceBuilder {
+        let limits = &mut self.cgroup.resources.misc.limits;
+        limits.retain(|l| l.key != key);
+        limits.push(MiscResource { key, max });
+        self
+    }
+
+    /// Finish the construction of the misc resources of a control group.
+    pub fn done(self) -> CgroupBuilder {
+        self.cgroup
+    }
+}
+
+/// A builder that configures the rdma controller of a control group.
+pub struct RdmaResourceBuilder {
+    cgroup: CgroupBuilder,
+}
+
+impl RdmaResourceBuilder {
+    /// Limit the RDMA/IB resources of the device `device`, e.g. `mlx4_0`, to be at most `limit`,
+    /// a later limit of the same device replacing the earlier one.
+    pub fn max(mut self, device: String, limit: RdmaLimit) -> RdmaResourceBuilder {
+        let limits = &mut self.cgroup.resources.rdma.limits;
+        limits.retain(|l| l.device != device);
+        limits.push(RdmaResource { device, limit });
+        self
+    }
+
+    /// Finish the construction of the rdma resources of a control group.
+    pub fn done(self) -> CgroupBuilder {
+        self.cgroup
+    }
+}
+
 /// A builder that configures the blkio controller of a control group.
 pub struct BlkIoResourcesBuilder {
     cgroup: CgroupBuilder,
@@ -379,6 +610,70 @@ impl BlkIoResourcesBuilder {
         self
     }
 
+    /// Limit the bytes per second rate of read operations for a certain device, regardless of
+    /// the current metric.
+    pub fn read_bps(mut self, major: u64, minor: u64, bps: u64) -> BlkIoResourcesBuilder {
+        let throttle = BlkIoDeviceThrottleResource {
+            major,
+            minor,
+            rate: bps,
+        };
+        self.cgroup
+            .resources
+            .blkio
+            .throttle_read_bps_device
+            .push(throttle);
+        self
+    }
+
+    /// Limit the I/O operations per second rate of read operations for a certain device,
+    /// regardless of the current metric.
+    pub fn read_iops(mut self, major: u64, minor: u64, iops: u64) -> BlkIoResourcesBuilder {
+        let throttle = BlkIoDeviceThrottleResource {
+            major,
+            minor,
+            rate: iops,
+        };
+        self.cgroup
+            .resources
+            .blkio
+            .throttle_read_iops_device
+            .push(throttle);
+        self
+    }
+
+    /// Limit the bytes per second rate of write operations for a certain device, regardless of
+    /// the current metric.
+    pub fn write_bps(mut self, major: u64, minor: u64, bps: u64) -> BlkIoResourcesBuilder {
+        let throttle = BlkIoDeviceThrottleResource {
+            major,
+            minor,
+            rate: bps,
+        };
+        self.cgroup
+            .resources
+            .blkio
+            .throttle_write_bps_device
+            .push(throttle);
+        self
+    }
+
+    /// Limit the I/O operations per second rate of write operations for a certain device,
+    /// regardless of the current metric.
+    pub fn write_iops(mut self, major: u64, minor: u64, iops: u64) -> BlkIoResourcesBuilder {
+        let throttle = BlkIoDeviceThrottleResource {
+            major,
+            minor,
+            rate: iops,
+        };
+        self.cgroup
+            .resources
+            .blkio
+            .throttle_write_iops_device
+            .push(throttle);
+        self
+    }
+
     /// Limit the write rate of the current metric for a certain device.
     pub fn write(mut self, major: u64, minor: u64, rate: u64) -> BlkIoResourcesBuilder {
         let throttle = BlkIoDeviceThrottleResource { major, minor, rate };
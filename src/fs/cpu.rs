@@ -9,18 +9,30 @@
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/scheduler/sched-design-CFS.txt](https://www.kernel.org/doc/Documentation/scheduler/sched-design-CFS.txt)
 //!  paragraph 7 ("GROUP SCHEDULER EXTENSIONS TO CFS").
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::Read;
 use std::path::PathBuf;
 
 use crate::fs::error::ErrorKind::*;
 use crate::fs::error::*;
-use crate::fs::{parse_max_value, read_i64_from, read_u64_from};
+use crate::fs::hierarchies::V1;
+use crate::fs::{
+    flat_keyed_to_hashmap, parse_max_value, parse_pressure, read_i64_from, read_string_from,
+    read_u64_from,
+};
 
 use crate::fs::{
     ControllIdentifier, ControllerInternal, Controllers, CpuResources, CustomizedAttribute,
-    MaxValue, Resources, Subsystem,
+    MaxValue, Pressure, Resources, Subsystem,
 };
+use crate::{CPU_SHARES_V1_MAX, CPU_WEIGHT_V2_MAX};
+
+const CPU_SHARES_V1_MIN: u64 = 2;
+const CPU_SHARES_V1_DEFAULT: u64 = 1024;
+const CPU_WEIGHT_V2_DEFAULT: u64 = 100;
+const CFS_PERIOD_DEFAULT: u64 = 100000;
 
 /// A controller that allows controlling the `cpu` subsystem of a Cgroup.
 ///
@@ -44,17 +56,71 @@ pub struct Cpu {
     pub stat: String,
 }
 
+/// The CPU usage and throttling of the tasks in the control group, as found in `cpu.stat`.
+///
+/// On cgroup v2 the `cpu` controller also does the accounting that the `cpuacct` controller
+/// did on cgroup v1, whose `cpu.stat` only has the throttling counters: the usage is left at 0
+/// there, see `CpuController::unified_stat()` to get it from `cpuacct`.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CpuStat {
+    /// Total CPU time (in microseconds) spent by the tasks.
+    pub usage_usec: u64,
+    /// CPU time (in microseconds) spent by the tasks that counted for `user` time.
+    pub user_usec: u64,
+    /// CPU time (in microseconds) spent by the tasks that counted for `system` time.
+    pub system_usec: u64,
+    /// Number of enforcement periods that elapsed.
+    pub nr_periods: u64,
+    /// Number of times the tasks were throttled because they exhausted the quota.
+    pub nr_throttled: u64,
+    /// Total time (in microseconds) the tasks were throttled for.
+    pub throttled_usec: u64,
+}
+
+/// The CPU usage and throttling of the tasks in the control group, in the shape of `cpu.stat` on
+/// cgroup v2 whatever the cgroup version, see `CpuController::unified_stat()`.
+pub type UnifiedCpuStat = CpuStat;
+
 /// The current state of the control group and its processes.
 #[derive(Debug)]
 struct CfsQuotaAndPeriod {
     quota: MaxValue,
     period: u64,
+    burst: Option<u64>,
 }
 
+// The control files used by the controller on cgroup v1, see `Controller::managed_files()`.
+const MANAGED_FILES_V1: &[&str] = &[
+    "cpu.shares",
+    "cpu.cfs_quota_us",
+    "cpu.cfs_period_us",
+    "cpu.rt_runtime_us",
+    "cpu.rt_period_us",
+    "cpu.stat",
+];
+
+// The control files used by the controller on cgroup v2.
+const MANAGED_FILES_V2: &[&str] = &[
+    "cpu.weight",
+    "cpu.max",
+    "cpu.stat",
+    "cpu.uclamp.min",
+    "cpu.uclamp.max",
+    "cpu.pressure",
+];
+
 impl ControllerInternal for CpuController {
     fn control_type(&self) -> Controllers {
         Controllers::Cpu
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        if self.is_v2() {
+            MANAGED_FILES_V2
+        } else {
+            MANAGED_FILES_V1
+        }
+    }
 
     fn get_path(&self) -> &PathBuf {
         &self.path
@@ -75,7 +141,13 @@ impl ControllerInternal for CpuController {
         // get the resources that apply to this controller
         let res: &CpuResources = &res.cpu;
 
-        update_and_test!(self, set_shares, res.shares, shares);
+        // the shares are converted to a weight on v2, see `CpuResources::shares`
+        let shares = if self.v2 {
+            res.shares.map(shares_to_weight)
+        } else {
+            res.shares
+        };
+        update_and_test!(self, set_shares, shares, shares);
         update_and_test!(self, set_cfs_period, res.period, cfs_period);
         update_and_test!(self, set_cfs_quota, res.quota, cfs_quota);
 
@@ -87,6 +159,36 @@ impl ControllerInternal for CpuController {
 
         Ok(())
     }
+
+    fn reset(&self) -> Result<()> {
+        if self.v2 {
+            self.set_shares(CPU_WEIGHT_V2_DEFAULT)?;
+            return self.set_cfs_quota_and_period(Some(-1), Some(CFS_PERIOD_DEFAULT));
+        }
+
+        self.set_shares(CPU_SHARES_V1_DEFAULT)?;
+        self.set_cfs_quota(-1)?;
+        self.set_cfs_period(CFS_PERIOD_DEFAULT)
+    }
+
+    fn read_resources(&self, res: &mut Resources) -> Result<()> {
+        let res = &mut res.cpu;
+        if self.v2 {
+            res.shares = self.read_if_exists("cpu.weight")?.map(weight_to_shares);
+            if self.get_path().join("cpu.max").exists() {
+                res.quota = Some(self.cfs_quota()?);
+                res.period = Some(self.cfs_period()?);
+            }
+            return Ok(());
+        }
+
+        res.shares = self.read_if_exists("cpu.shares")?;
+        res.quota = self.read_if_exists("cpu.cfs_quota_us")?;
+        res.period = self.read_if_exists("cpu.cfs_period_us")?;
+        res.realtime_runtime = self.read_if_exists("cpu.rt_runtime_us")?;
+        res.realtime_period = self.read_if_exists("cpu.rt_period_us")?;
+        Ok(())
+    }
 }
 
 impl ControllIdentifier for CpuController {
@@ -95,17 +197,15 @@ impl ControllIdentifier for CpuController {
     }
 }
 
-impl<'a> From<&'a Subsystem> for &'a CpuController {
-    fn from(sub: &'a Subsystem) -> &'a CpuController {
-        unsafe {
-            match sub {
-                Subsystem::Cpu(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a CpuController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a CpuController> {
+        match sub {
+            Subsystem::Cpu(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                CpuController::controller_type().to_string(),
+            ))),
         }
     }
 }
@@ -137,6 +237,78 @@ impl CpuController {
         }
     }
 
+    /// Returns the CPU usage and throttling of the tasks in the control group, as found in
+    /// `cpu.stat`.
+    ///
+    /// The usage is only there on cgroup v2, where it is the counterpart of the accounting of
+    /// `CpuAcctController`. On v1 the throttled time is converted from nanoseconds.
+    pub fn cpu_stat(&self) -> Result<CpuStat> {
+        self.open_path("cpu.stat", false)
+            .and_then(flat_keyed_to_hashmap)
+            .map(|stat| parse_cpu_stat(&stat, self.v2))
+    }
+
+    /// Returns the CPU usage and throttling of the tasks in the control group, in the same shape
+    /// on both cgroup versions.
+    ///
+    /// On v2 everything comes from `cpu.stat`. On v1 the throttling comes from `cpu.stat` and the
+    /// usage from the `cpuacct.usage*` files of the `cpuacct` controller, whether it is mounted
+    /// along with `cpu` or on its own, in which case its mount point is found in the mounts of
+    /// the current process. The nanoseconds of v1 are converted to microseconds.
+    pub fn unified_stat(&self) -> Result<UnifiedCpuStat> {
+        let stat = self.cpu_stat()?;
+
+        if self.v2 {
+            return Ok(stat);
+        }
+
+        let dir = self.cpuacct_dir().ok_or_else(|| {
+            Error::not_supported("cpuacct usage", "the cpuacct controller isn't mounted")
+        })?;
+        let read = |name: &str| {
+            let file = dir.join(name);
+            File::open(&file)
+                .map_err(|e| Error::with_cause(ReadFailed(file.display().to_string()), e))
+                .and_then(read_u64_from)
+        };
+
+        let (user_usec, system_usec) = if dir.join("cpuacct.usage_user").exists() {
+            (
+                read("cpuacct.usage_user")? / 1000,
+                read("cpuacct.usage_sys")? / 1000,
+            )
+        } else {
+            // Older kernels only report the split in `cpuacct.stat`, in clock ticks.
+            let file = dir.join("cpuacct.stat");
+            let times = File::open(&file)
+                .map_err(|e| Error::with_cause(ReadFailed(file.display().to_string()), e))
+                .and_then(flat_keyed_to_hashmap)?;
+            let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as u64;
+            let usec = |key: &str| times.get(key).copied().unwrap_or(0) as u64 * 1_000_000 / ticks;
+            (usec("user"), usec("system"))
+        };
+
+        Ok(UnifiedCpuStat {
+            usage_usec: read("cpuacct.usage")? / 1000,
+            user_usec,
+            system_usec,
+            ..stat
+        })
+    }
+
+    // The directory of the control group in the v1 `cpuacct` hierarchy.
+    fn cpuacct_dir(&self) -> Option<PathBuf> {
+        if self.path.join("cpuacct.usage").exists() {
+            return Some(self.path.clone());
+        }
+
+        let hierarchy = V1::new();
+        let (cpu_point, _) = hierarchy.get_mount_point(Controllers::Cpu)?;
+        let (cpuacct_point, _) = hierarchy.get_mount_point(Controllers::CpuAcct)?;
+        let relative = self.path.strip_prefix(cpu_point).ok()?;
+        Some(cpuacct_point.join(relative))
+    }
+
     /// Configures the CPU bandwidth (in relative relation to other control groups and this control
     /// group's parent).
     ///
@@ -149,11 +321,7 @@ impl CpuController {
             file_name = "cpu.weight";
         }
         // NOTE: .CpuShares is not used here. Conversion is the caller's responsibility.
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(shares.to_string().as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed(file_name.to_string(), shares.to_string()), e)
-            })
-        })
+        self.write_file(file_name, &shares.to_string())
     }
 
     /// Retrieve the CPU bandwidth that this control group (relative to other control groups and
@@ -172,15 +340,7 @@ impl CpuController {
         if self.v2 {
             return self.set_cfs_quota_and_period(None, Some(us));
         }
-        self.open_path("cpu.cfs_period_us", true)
-            .and_then(|mut file| {
-                file.write_all(us.to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("cpu.cfs_period_us".to_string(), us.to_string()),
-                        e,
-                    )
-                })
-            })
+        self.write_file("cpu.cfs_period_us", &us.to_string())
     }
 
     /// Retrieve the period of time of how often this cgroup's access to the CPU should be
@@ -202,15 +362,7 @@ impl CpuController {
         if self.v2 {
             return self.set_cfs_quota_and_period(Some(us), None);
         }
-        self.open_path("cpu.cfs_quota_us", true)
-            .and_then(|mut file| {
-                file.write_all(us.to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("cpu.cfs_quota_us".to_string(), us.to_string()),
-                        e,
-                    )
-                })
-            })
+        self.write_file("cpu.cfs_quota_us", &us.to_string())
     }
 
     /// Retrieve the quota of time for which all tasks in this cgroup can run during one period, in
@@ -246,6 +398,9 @@ impl CpuController {
         // $MAX $PERIOD
         // which indicates that the group may consume upto $MAX in each $PERIOD duration.
         // “max” for $MAX indicates no limit. If only one number is written, $MAX is updated.
+        //
+        // Some configurations expose a third $BURST field, which is carried over unchanged so
+        // that updating the quota or the period doesn't reset it.
 
         let current_value = self
             .open_path("cpu.max", false)
@@ -253,61 +408,297 @@ impl CpuController {
 
         let new_quota = if let Some(q) = quota {
             if q > 0 {
-                q.to_string()
+                MaxValue::Value(q)
             } else {
-                "max".to_string()
+                MaxValue::Max
             }
         } else {
-            current_value.quota.to_string()
+            current_value.quota
+        };
+
+        let new_period = period.unwrap_or(current_value.period);
+
+        self.write_cpu_max(new_quota, new_period, current_value.burst)
+    }
+
+    /// Returns the share of the enforcement periods in which the control group was throttled,
+    /// `nr_throttled / nr_periods` from `cpu.stat`, between 0 and 1.
+    ///
+    /// Returns 0 when no period elapsed yet, e.g. when the control group has no CPU quota.
+    pub fn throttle_ratio(&self) -> Result<f64> {
+        self.open_path("cpu.stat", false)
+            .and_then(flat_keyed_to_hashmap)
+            .map(|stat| throttle_ratio(&stat))
+    }
+
+    /// Returns the pressure stall information of the control group on the CPU, from
+    /// `cpu.pressure` (cgroup v2 only).
+    ///
+    /// The file is missing when the kernel doesn't account pressure, see
+    /// `Cgroup::set_pressure_accounting()`.
+    pub fn pressure(&self) -> Result<Pressure> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "cpu.pressure",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.open_path("cpu.pressure", false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_pressure(&s))
+    }
+
+    /// Limits the control group to `cores` CPUs worth of bandwidth, e.g. 1.5, by setting the
+    /// quota to `cores` times a 100ms period, rounded to the microsecond. This writes `cpu.max`
+    /// on v2, `cpu.cfs_quota_us` and `cpu.cfs_period_us` on v1.
+    ///
+    /// A `cores` of zero removes the limit, same as `clear_cpu_limit()`.
+    pub fn set_cpu_limit(&self, cores: f64) -> Result<()> {
+        if !cores.is_finite() || cores < 0.0 {
+            return Err(Error::new(InvalidArgument(format!(
+                "invalid CPU limit {}, must be a positive number of CPUs",
+                cores
+            ))));
+        }
+        if cores == 0.0 {
+            return self.clear_cpu_limit();
+        }
+
+        let quota = (cores * CFS_PERIOD_DEFAULT as f64).round() as i64;
+        self.set_cfs_quota_and_period(Some(quota), Some(CFS_PERIOD_DEFAULT))
+    }
+
+    /// Removes the CPU bandwidth limit of the control group, see `set_cpu_limit()`.
+    pub fn clear_cpu_limit(&self) -> Result<()> {
+        self.set_cfs_quota_and_period(Some(-1), None)
+    }
+
+    /// The CPU bandwidth the control group can effectively use, as a number of CPUs, which is
+    /// the lowest quota to period ratio of the control group and its ancestors.
+    ///
+    /// Returns `None` if none of them has a quota.
+    pub fn effective_quota_ratio(&self) -> Result<Option<f64>> {
+        let mut ratio: Option<f64> = None;
+        // The walk ends at the root control group on v2, which has no `cpu.max`, or at the top of
+        // a hierarchy mounted from a non-root control group.
+        for dir in self.get_path().ancestors() {
+            let limit = if self.v2 {
+                let file = dir.join("cpu.max");
+                if !file.exists() {
+                    break;
+                }
+                let quota = std::fs::read_to_string(&file)
+                    .map_err(|e| Error::with_cause(ReadFailed(file.display().to_string()), e))
+                    .and_then(|s| parse_cpu_max(&s))?;
+                match quota.quota {
+                    MaxValue::Value(q) if quota.period > 0 => Some(q as f64 / quota.period as f64),
+                    _ => None,
+                }
+            } else {
+                let (quota_file, period_file) =
+                    (dir.join("cpu.cfs_quota_us"), dir.join("cpu.cfs_period_us"));
+                if !quota_file.exists() {
+                    break;
+                }
+                let quota = File::open(&quota_file)
+                    .map_err(|e| Error::with_cause(ReadFailed(quota_file.display().to_string()), e))
+                    .and_then(read_i64_from)?;
+                let period = File::open(&period_file)
+                    .map_err(|e| {
+                        Error::with_cause(ReadFailed(period_file.display().to_string()), e)
+                    })
+                    .and_then(read_u64_from)?;
+                if quota > 0 && period > 0 {
+                    Some(quota as f64 / period as f64)
+                } else {
+                    None
+                }
+            };
+
+            ratio = match (ratio, limit) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        }
+
+        Ok(ratio)
+    }
+
+    /// Sets all the fields of `cpu.max` at once (cgroup v2 only).
+    ///
+    /// A `quota` less than or equal to zero means no limit ("max"). The `burst` field is only
+    /// written when it is `Some`.
+    pub fn set_cpu_max(&self, quota: i64, period: u64, burst: Option<u64>) -> Result<()> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "cpu.max",
+                "only available on cgroup v2",
+            ));
+        }
+
+        let quota = if quota > 0 {
+            MaxValue::Value(quota)
+        } else {
+            MaxValue::Max
         };
 
-        let new_period = if let Some(p) = period {
-            p.to_string()
+        self.write_cpu_max(quota, period, burst)
+    }
+
+    /// Retrieve the burst field of `cpu.max`, if the kernel reports one (cgroup v2 only).
+    pub fn cpu_max_burst(&self) -> Result<Option<u64>> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "cpu.max",
+                "only available on cgroup v2",
+            ));
+        }
+
+        let current_value = self
+            .open_path("cpu.max", false)
+            .and_then(parse_cfs_quota_and_period)?;
+        Ok(current_value.burst)
+    }
+
+    /// Sets the minimum utilization the scheduler assumes for the tasks (`cpu.uclamp.min`), as a
+    /// percentage between 0 and 100 with up to two decimals, e.g. to keep the CPU frequency up
+    /// for latency-sensitive tasks. Only available on cgroup v2, with a kernel built with
+    /// `CONFIG_UCLAMP_TASK_GROUP`.
+    pub fn set_uclamp_min(&self, percent: f64) -> Result<()> {
+        self.write_uclamp("cpu.uclamp.min", percent)
+    }
+
+    /// Retrieve the minimum utilization of the tasks (`cpu.uclamp.min`), as a percentage. Only
+    /// available on cgroup v2.
+    pub fn uclamp_min(&self) -> Result<f64> {
+        self.read_uclamp("cpu.uclamp.min")
+    }
+
+    /// Sets the maximum utilization the scheduler assumes for the tasks (`cpu.uclamp.max`), as a
+    /// percentage between 0 and 100, `100` meaning no clamping ("max"). Only available on
+    /// cgroup v2, see `set_uclamp_min()`.
+    pub fn set_uclamp_max(&self, percent: f64) -> Result<()> {
+        self.write_uclamp("cpu.uclamp.max", percent)
+    }
+
+    /// Retrieve the maximum utilization of the tasks (`cpu.uclamp.max`), as a percentage, `100`
+    /// when it is `max`. Only available on cgroup v2.
+    pub fn uclamp_max(&self) -> Result<f64> {
+        self.read_uclamp("cpu.uclamp.max")
+    }
+
+    fn write_uclamp(&self, file: &str, percent: f64) -> Result<()> {
+        if !self.v2 {
+            return Err(Error::not_supported(file, "only available on cgroup v2"));
+        }
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(Error::new(InvalidArgument(format!(
+                "invalid {} {}, must be in 0-100",
+                file, percent
+            ))));
+        }
+
+        let value = if percent == 100.0 {
+            "max".to_string()
         } else {
-            current_value.period.to_string()
+            format!("{:.2}", percent)
         };
+        self.write_file(file, &value)
+    }
 
-        let line = format!("{} {}", new_quota, new_period);
-        self.open_path("cpu.max", true).and_then(|mut file| {
-            file.write_all(line.as_ref())
-                .map_err(|e| Error::with_cause(WriteFailed("cpu.max".to_string(), line), e))
-        })
+    fn read_uclamp(&self, file: &str) -> Result<f64> {
+        if !self.v2 {
+            return Err(Error::not_supported(file, "only available on cgroup v2"));
+        }
+
+        self.open_path(file, false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_uclamp(&s))
+    }
+
+    fn write_cpu_max(&self, quota: MaxValue, period: u64, burst: Option<u64>) -> Result<()> {
+        let line = match burst {
+            Some(b) => format!("{} {} {}", quota, period, b),
+            None => format!("{} {}", quota, period),
+        };
+        self.write_file("cpu.max", &line)
     }
 
     pub fn set_rt_runtime(&self, us: i64) -> Result<()> {
-        self.open_path("cpu.rt_runtime_us", true)
-            .and_then(|mut file| {
-                file.write_all(us.to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("cpu.rt_runtime_us".to_string(), us.to_string()),
-                        e,
-                    )
-                })
-            })
+        self.write_file("cpu.rt_runtime_us", &us.to_string())
     }
 
     pub fn set_rt_period_us(&self, us: u64) -> Result<()> {
-        self.open_path("cpu.rt_period_us", true)
-            .and_then(|mut file| {
-                file.write_all(us.to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("cpu.rt_period_us".to_string(), us.to_string()),
-                        e,
-                    )
-                })
-            })
+        self.write_file("cpu.rt_period_us", &us.to_string())
     }
 }
 
 impl CustomizedAttribute for CpuController {}
 
+/// Converts cgroup v1 CPU shares to a cgroup v2 CPU weight.
+///
+/// The shares range `[2, 262144]` is mapped linearly onto the weight range `[1, 10000]`, values
+/// out of range are clamped.
+pub fn shares_to_weight(shares: u64) -> u64 {
+    let shares = shares.clamp(CPU_SHARES_V1_MIN, CPU_SHARES_V1_MAX);
+    1 + ((shares - CPU_SHARES_V1_MIN) * (CPU_WEIGHT_V2_MAX - 1))
+        / (CPU_SHARES_V1_MAX - CPU_SHARES_V1_MIN)
+}
+
+/// Converts a cgroup v2 CPU weight to cgroup v1 CPU shares, the inverse of `shares_to_weight()`.
+///
+/// The weight range `[1, 10000]` is mapped linearly onto the shares range `[2, 262144]`, values
+/// out of range are clamped. The smallest shares giving back `weight` are returned, so that
+/// `shares_to_weight(weight_to_shares(weight)) == weight`.
+pub fn weight_to_shares(weight: u64) -> u64 {
+    let weight = weight.clamp(1, CPU_WEIGHT_V2_MAX);
+    let range = CPU_SHARES_V1_MAX - CPU_SHARES_V1_MIN;
+    CPU_SHARES_V1_MIN + ((weight - 1) * range + CPU_WEIGHT_V2_MAX - 2) / (CPU_WEIGHT_V2_MAX - 1)
+}
+
+// Parses `cpu.stat`: the usage and throttling on v2, only the throttling on v1, whose
+// `throttled_time` is in nanoseconds.
+fn parse_cpu_stat(stat: &HashMap<String, i64>, v2: bool) -> CpuStat {
+    let get = |key: &str| stat.get(key).copied().unwrap_or(0) as u64;
+    if v2 {
+        return CpuStat {
+            usage_usec: get("usage_usec"),
+            user_usec: get("user_usec"),
+            system_usec: get("system_usec"),
+            nr_periods: get("nr_periods"),
+            nr_throttled: get("nr_throttled"),
+            throttled_usec: get("throttled_usec"),
+        };
+    }
+
+    CpuStat {
+        nr_periods: get("nr_periods"),
+        nr_throttled: get("nr_throttled"),
+        throttled_usec: get("throttled_time") / 1000,
+        ..Default::default()
+    }
+}
+
+fn throttle_ratio(stat: &HashMap<String, i64>) -> f64 {
+    let get = |key: &str| stat.get(key).copied().unwrap_or(0);
+    match get("nr_periods") {
+        0 => 0.0,
+        periods => get("nr_throttled") as f64 / periods as f64,
+    }
+}
+
 fn parse_cfs_quota_and_period(mut file: File) -> Result<CfsQuotaAndPeriod> {
     let mut content = String::new();
     file.read_to_string(&mut content)
         .map_err(|e| Error::with_cause(ReadFailed("cpu.max".to_string()), e))?;
 
-    let fields = content.trim().split(' ').collect::<Vec<&str>>();
-    if fields.len() != 2 {
+    parse_cpu_max(&content)
+}
+
+fn parse_cpu_max(content: &str) -> Result<CfsQuotaAndPeriod> {
+    let fields = content.split_whitespace().collect::<Vec<&str>>();
+    if fields.len() != 2 && fields.len() != 3 {
         return Err(Error::from_string(format!("invaild format: {}", content)));
     }
 
@@ -315,6 +706,139 @@ fn parse_cfs_quota_and_period(mut file: File) -> Result<CfsQuotaAndPeriod> {
     let period = fields[1]
         .parse::<u64>()
         .map_err(|e| Error::with_cause(ParseError, e))?;
+    let burst = match fields.get(2) {
+        Some(b) => Some(
+            b.parse::<u64>()
+                .map_err(|e| Error::with_cause(ParseError, e))?,
+        ),
+        None => None,
+    };
+
+    Ok(CfsQuotaAndPeriod {
+        quota,
+        period,
+        burst,
+    })
+}
+
+// Parses the percentage of `cpu.uclamp.min` and `cpu.uclamp.max`, e.g. `20.00`, `max` being
+// 100%.
+fn parse_uclamp(s: &str) -> Result<f64> {
+    match s.trim() {
+        "max" => Ok(100.0),
+        v => v.parse().map_err(|e| Error::with_cause(ParseError, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::cpu::{
+        parse_cpu_max, parse_cpu_stat, parse_uclamp, shares_to_weight, throttle_ratio,
+        weight_to_shares, CpuStat,
+    };
+    use crate::fs::MaxValue;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse_cpu_stat() {
+        let parse = |content: &str, v2: bool| {
+            let stat = content
+                .lines()
+                .filter_map(|l| l.split_once(' '))
+                .map(|(k, v)| (k.to_string(), v.parse().unwrap()))
+                .collect();
+            parse_cpu_stat(&stat, v2)
+        };
+
+        let v2 = parse(
+            "usage_usec 3000\nuser_usec 2000\nsystem_usec 1000\nnr_periods 40\n\
+             nr_throttled 10\nthrottled_usec 5000",
+            true,
+        );
+        assert_eq!(
+            v2,
+            CpuStat {
+                usage_usec: 3000,
+                user_usec: 2000,
+                system_usec: 1000,
+                nr_periods: 40,
+                nr_throttled: 10,
+                throttled_usec: 5000,
+            }
+        );
+
+        // the usage of v1 is in cpuacct
+        let v1 = parse(
+            "nr_periods 40\nnr_throttled 10\nthrottled_time 5000000",
+            false,
+        );
+        assert_eq!(
+            v1,
+            CpuStat {
+                nr_periods: 40,
+                nr_throttled: 10,
+                throttled_usec: 5000,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_throttle_ratio() {
+        let stat = |periods: i64, throttled: i64| {
+            let mut stat = HashMap::new();
+            stat.insert("nr_periods".to_string(), periods);
+            stat.insert("nr_throttled".to_string(), throttled);
+            stat
+        };
+        assert_eq!(throttle_ratio(&stat(0, 0)), 0.0);
+        assert_eq!(throttle_ratio(&stat(200, 50)), 0.25);
+        assert_eq!(throttle_ratio(&stat(10, 10)), 1.0);
+        assert_eq!(throttle_ratio(&HashMap::new()), 0.0);
+    }
+
+    #[test]
+    fn test_parse_cpu_max() {
+        let v = parse_cpu_max("max 100000\n").unwrap();
+        assert_eq!(v.quota, MaxValue::Max);
+        assert_eq!(v.period, 100000);
+        assert_eq!(v.burst, None);
+
+        let v = parse_cpu_max("50000 100000 20000\n").unwrap();
+        assert_eq!(v.quota, MaxValue::Value(50000));
+        assert_eq!(v.period, 100000);
+        assert_eq!(v.burst, Some(20000));
+
+        assert!(parse_cpu_max("50000").is_err());
+        assert!(parse_cpu_max("50000 100000 1 2").is_err());
+        assert!(parse_cpu_max("50000 100000 abc").is_err());
+    }
 
-    Ok(CfsQuotaAndPeriod { quota, period })
+    #[test]
+    fn test_shares_weight_conversion() {
+        assert_eq!(shares_to_weight(0), 1);
+        assert_eq!(shares_to_weight(2), 1);
+        assert_eq!(shares_to_weight(1024), 39);
+        assert_eq!(shares_to_weight(262144), 10000);
+        assert_eq!(shares_to_weight(1 << 20), 10000);
+
+        assert_eq!(weight_to_shares(0), 2);
+        assert_eq!(weight_to_shares(1), 2);
+        assert_eq!(weight_to_shares(39), 999);
+        assert_eq!(weight_to_shares(100), 2598);
+        assert_eq!(weight_to_shares(10000), 262144);
+        assert_eq!(weight_to_shares(20000), 262144);
+
+        for weight in 1..=10000 {
+            assert_eq!(shares_to_weight(weight_to_shares(weight)), weight);
+        }
+    }
+
+    #[test]
+    fn test_parse_uclamp() {
+        assert_eq!(parse_uclamp("20.00\n").unwrap(), 20.0);
+        assert_eq!(parse_uclamp("0.00").unwrap(), 0.0);
+        assert_eq!(parse_uclamp("max\n").unwrap(), 100.0);
+        assert!(parse_uclamp("").is_err());
+    }
 }
@@ -7,7 +7,7 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/cpuacct.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/cpuacct.txt)
-use std::io::Write;
+use std::convert::TryFrom;
 use std::path::PathBuf;
 
 use crate::fs::error::ErrorKind::*;
@@ -20,6 +20,9 @@ use crate::fs::{ControllIdentifier, ControllerInternal, Controllers, Resources,
 ///
 /// In essence, this control group provides accounting (hence the name `cpuacct`) for CPU usage of
 /// the tasks in the control group.
+///
+/// The `cpuacct` controller only exists on cgroup v1. On cgroup v2 the same accounting is done by
+/// the `cpu` controller, see `CpuController::cpu_stat()`.
 #[derive(Debug, Clone)]
 pub struct CpuAcctController {
     base: PathBuf,
@@ -46,7 +49,8 @@ pub struct CpuAcct {
     /// ```
     pub usage_all: String,
     /// CPU time (in nanoseconds) spent by the tasks, broken down by each CPU.
-    /// Times spent in each CPU are separated by a space.
+    /// Times spent in each CPU are separated by a space, see `CpuAcctController::usage_percpu()`
+    /// for the parsed values.
     pub usage_percpu: String,
     /// As for `usage_percpu`, but the `system` time spent.
     pub usage_percpu_sys: String,
@@ -58,10 +62,32 @@ pub struct CpuAcct {
     pub usage_user: u64,
 }
 
+// The control files used by the controller on cgroup v1, see `Controller::managed_files()`.
+const MANAGED_FILES_V1: &[&str] = &[
+    "cpuacct.usage",
+    "cpuacct.stat",
+    "cpuacct.usage_all",
+    "cpuacct.usage_percpu",
+    "cpuacct.usage_percpu_sys",
+    "cpuacct.usage_percpu_user",
+    "cpuacct.usage_sys",
+    "cpuacct.usage_user",
+];
+
+// The control files used by the controller on cgroup v2.
+const MANAGED_FILES_V2: &[&str] = &[];
+
 impl ControllerInternal for CpuAcctController {
     fn control_type(&self) -> Controllers {
         Controllers::CpuAcct
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        if self.is_v2() {
+            MANAGED_FILES_V2
+        } else {
+            MANAGED_FILES_V1
+        }
+    }
     fn get_path(&self) -> &PathBuf {
         &self.path
     }
@@ -83,17 +109,15 @@ impl ControllIdentifier for CpuAcctController {
     }
 }
 
-impl<'a> From<&'a Subsystem> for &'a CpuAcctController {
-    fn from(sub: &'a Subsystem) -> &'a CpuAcctController {
-        unsafe {
-            match sub {
-                Subsystem::CpuAcct(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a CpuAcctController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a CpuAcctController> {
+        match sub {
+            Subsystem::CpuAcct(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                CpuAcctController::controller_type().to_string(),
+            ))),
         }
     }
 }
@@ -145,12 +169,59 @@ impl CpuAcctController {
         }
     }
 
+    /// Returns the CPU time (in nanoseconds) spent by the tasks on each logical CPU, indexed by
+    /// CPU number, as found in `cpuacct.usage_percpu`.
+    pub fn usage_percpu(&self) -> Result<Vec<u64>> {
+        self.read_percpu("cpuacct.usage_percpu")
+    }
+
+    /// As for `usage_percpu()`, but the `user` time spent, found in `cpuacct.usage_percpu_user`.
+    pub fn usage_percpu_user(&self) -> Result<Vec<u64>> {
+        self.read_percpu("cpuacct.usage_percpu_user")
+    }
+
+    /// As for `usage_percpu()`, but the `system` time spent, found in `cpuacct.usage_percpu_sys`.
+    pub fn usage_percpu_sys(&self) -> Result<Vec<u64>> {
+        self.read_percpu("cpuacct.usage_percpu_sys")
+    }
+
+    fn read_percpu(&self, file: &str) -> Result<Vec<u64>> {
+        self.open_path(file, false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_percpu(&s))
+    }
+
     /// Reset the statistics the kernel has gathered about the control group.
+    pub fn reset_usage(&self) -> Result<()> {
+        self.write_file("cpuacct.usage", "0")
+    }
+
+    /// Same as `reset_usage()`, which should be used instead: this shadows `Controller::reset()`,
+    /// which resets the settings of the controller and leaves the statistics alone.
+    #[deprecated(note = "use `reset_usage()` instead")]
     pub fn reset(&self) -> Result<()> {
-        self.open_path("cpuacct.usage", true).and_then(|mut file| {
-            file.write_all(b"0").map_err(|e| {
-                Error::with_cause(WriteFailed("cpuacct.usage".to_string(), "0".to_string()), e)
-            })
-        })
+        self.reset_usage()
+    }
+}
+
+// Parses the space-separated per-CPU times of the `cpuacct.usage_percpu*` files.
+fn parse_percpu(s: &str) -> Result<Vec<u64>> {
+    s.split_whitespace()
+        .map(|v| v.parse().map_err(|e| Error::with_cause(ParseError, e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::cpuacct::parse_percpu;
+
+    #[test]
+    fn test_parse_percpu() {
+        assert_eq!(
+            parse_percpu("8348363768 8324369100 0 8648262473 \n").unwrap(),
+            vec![8348363768, 8324369100, 0, 8648262473]
+        );
+        assert!(parse_percpu("").unwrap().is_empty());
+        assert!(parse_percpu("12 x 3").is_err());
     }
 }
@@ -10,12 +10,18 @@
 //!  [Documentation/cgroup-v1/cpusets.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/cpusets.txt)
 
 use log::*;
+use std::convert::TryFrom;
+use std::fmt;
 use std::io::Write;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 use crate::fs::error::ErrorKind::*;
 use crate::fs::error::*;
 
+use crate::fs::cgroup::Cgroup;
+use crate::fs::events;
+use crate::fs::hierarchies::{unified_mount_point_of, V2};
 use crate::fs::{read_string_from, read_u64_from};
 use crate::fs::{
     ControllIdentifier, ControllerInternal, Controllers, CpuResources, Resources, Subsystem,
@@ -32,6 +38,49 @@ pub struct CpuSetController {
     v2: bool,
 }
 
+/// The type of a cpuset partition, as found in `cpuset.cpus.partition` (cgroup v2 only).
+///
+/// The scheduler never balances the load across the boundary of a partition root, so the tasks
+/// of a `Root` partition only run on its CPUs and the other tasks don't. Within the partition,
+/// the load is balanced across its CPUs unless it is `Isolated`, in which case tasks stay on
+/// the CPU they are placed on, which is what real-time workloads want. See `load_balanced()`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CpuSetPartition {
+    /// A non-root member of its parent's partition.
+    Member,
+    /// The root of a partition, which has exclusive use of its CPUs.
+    Root,
+    /// The root of a partition whose CPUs are isolated from the scheduler's load balancing.
+    Isolated,
+    /// A partition root that the kernel invalidated, with the state it reported, e.g.
+    /// `root invalid (Cpu list in cpuset.cpus not exclusive)`.
+    Invalid(String),
+}
+
+impl CpuSetPartition {
+    /// Whether the scheduler balances the load across the CPUs of the partition, i.e. whether
+    /// it isn't `Isolated`. This is the v2 counterpart of `cpuset.sched_load_balance`.
+    ///
+    /// An `Invalid` partition root behaves as a member of its parent's partition and is
+    /// reported as load balanced.
+    pub fn load_balanced(&self) -> bool {
+        *self != CpuSetPartition::Isolated
+    }
+}
+
+/// What `make_root_partition()` does when the requested CPUs overlap the CPUs isolated with the
+/// `isolcpus=` boot parameter.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum IsolatedCpusCheck {
+    /// Fail without touching the control group.
+    Error,
+    /// Log a warning and go on.
+    Warn,
+    /// Don't check.
+    Skip,
+}
+
 /// The current state of the `cpuset` controller for this control group.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CpuSet {
@@ -89,10 +138,44 @@ pub struct CpuSet {
     pub sched_relax_domain_level: u64,
 }
 
+// The control files used by the controller on cgroup v1, see `Controller::managed_files()`.
+const MANAGED_FILES_V1: &[&str] = &[
+    "cpuset.cpus",
+    "cpuset.mems",
+    "cpuset.cpu_exclusive",
+    "cpuset.mem_exclusive",
+    "cpuset.mem_hardwall",
+    "cpuset.memory_migrate",
+    "cpuset.memory_pressure_enabled",
+    "cpuset.memory_spread_page",
+    "cpuset.memory_spread_slab",
+    "cpuset.sched_load_balance",
+    "cpuset.sched_relax_domain_level",
+    "cpuset.effective_cpus",
+    "cpuset.effective_mems",
+    "cpuset.memory_pressure",
+];
+
+// The control files used by the controller on cgroup v2.
+const MANAGED_FILES_V2: &[&str] = &[
+    "cpuset.cpus",
+    "cpuset.mems",
+    "cpuset.cpus.partition",
+    "cpuset.cpus.effective",
+    "cpuset.mems.effective",
+];
+
 impl ControllerInternal for CpuSetController {
     fn control_type(&self) -> Controllers {
         Controllers::CpuSet
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        if self.is_v2() {
+            MANAGED_FILES_V2
+        } else {
+            MANAGED_FILES_V1
+        }
+    }
     fn get_path(&self) -> &PathBuf {
         &self.path
     }
@@ -117,6 +200,48 @@ impl ControllerInternal for CpuSetController {
         Ok(())
     }
 
+    fn reset(&self) -> Result<()> {
+        if self.v2 {
+            // an empty value makes the control group use the CPUs and memory nodes of its parent,
+            // a newline is written as empty writes never reach the kernel
+            self.set_cpus("\n")?;
+            return self.set_mems("\n");
+        }
+
+        // on v1 the values can't be empty, the ones of the parent are copied instead
+        let parent = match self.get_path().parent() {
+            Some(parent) if self.get_path() != self.get_base() => parent.to_path_buf(),
+            _ => return Ok(()),
+        };
+        for (file, set) in [
+            (
+                "cpuset.cpus",
+                Self::set_cpus as fn(&Self, &str) -> Result<()>,
+            ),
+            ("cpuset.mems", Self::set_mems),
+        ] {
+            let path = parent.join(file);
+            let value = ::std::fs::read_to_string(&path)
+                .map_err(|e| Error::with_cause(ReadFailed(path.display().to_string()), e))?;
+            set(self, value.trim())?;
+        }
+
+        Ok(())
+    }
+
+    fn read_resources(&self, res: &mut Resources) -> Result<()> {
+        // an empty value means that the ones of the parent are used on v2
+        let read = |f: &str| -> Result<Option<String>> {
+            Ok(self
+                .read_if_exists::<String>(f)?
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()))
+        };
+        res.cpu.cpus = read("cpuset.cpus")?;
+        res.cpu.mems = read("cpuset.mems")?;
+        Ok(())
+    }
+
     fn post_create(&self) {
         if self.is_v2() {
             return;
@@ -136,6 +261,24 @@ impl ControllerInternal for CpuSetController {
     }
 }
 
+const SCHEDSTAT: &str = "/proc/schedstat";
+
+// Returns the largest number of scheduler domains of a CPU, listed as `domain<N>` lines after
+// each `cpu<N>` line of `/proc/schedstat`.
+fn schedstat_domain_levels(content: &str) -> usize {
+    let mut levels = 0;
+    let mut current = 0;
+    for line in content.lines() {
+        if line.starts_with("cpu") {
+            current = 0;
+        } else if line.starts_with("domain") {
+            current += 1;
+            levels = levels.max(current);
+        }
+    }
+    levels
+}
+
 fn find_no_empty_parent(from: &str, file: &str) -> Result<(String, Vec<PathBuf>)> {
     let mut current_path = ::std::path::Path::new(from).to_path_buf();
     let mut v = vec![];
@@ -200,23 +343,21 @@ impl ControllIdentifier for CpuSetController {
     }
 }
 
-impl<'a> From<&'a Subsystem> for &'a CpuSetController {
-    fn from(sub: &'a Subsystem) -> &'a CpuSetController {
-        unsafe {
-            match sub {
-                Subsystem::CpuSet(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a CpuSetController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a CpuSetController> {
+        match sub {
+            Subsystem::CpuSet(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                CpuSetController::controller_type().to_string(),
+            ))),
         }
     }
 }
 
 /// Parse a string like "1,2,4-5,8" into a list of (start, end) tuples.
-fn parse_range(s: String) -> Result<Vec<(u64, u64)>> {
+pub(crate) fn parse_range(s: String) -> Result<Vec<(u64, u64)>> {
     let mut fin = Vec::new();
 
     if s.is_empty() {
@@ -252,6 +393,137 @@ fn parse_range(s: String) -> Result<Vec<(u64, u64)>> {
     Ok(fin)
 }
 
+/// A list of CPUs or memory nodes in the format of the kernel, e.g. `0-3,6,8-11` as found in
+/// `cpuset.cpus`.
+///
+/// The numbers are kept as sorted and merged `(start, end)` ranges: parsing accepts them in any
+/// order and overlapping, and formatting prints the shortest list, e.g. `3,0-1,2` becomes `0-3`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CpuList {
+    ranges: Vec<(u64, u64)>,
+}
+
+impl CpuList {
+    /// Returns the list as `(start, end)` ranges, sorted and with the adjacent ones merged.
+    pub fn ranges(&self) -> &[(u64, u64)] {
+        &self.ranges
+    }
+
+    /// Returns whether the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns whether `n` is in the list.
+    pub fn contains(&self, n: u64) -> bool {
+        self.ranges
+            .iter()
+            .any(|&(start, end)| (start..=end).contains(&n))
+    }
+
+    /// Returns the numbers in the list, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.ranges.iter().flat_map(|&(start, end)| start..=end)
+    }
+
+    /// Returns the numbers that are in both lists, e.g. the requested CPUs that a parent
+    /// grants.
+    pub fn intersection(&self, other: &CpuList) -> CpuList {
+        let mut ranges = vec![];
+        for &(start, end) in &self.ranges {
+            for &(other_start, other_end) in &other.ranges {
+                let (start, end) = (start.max(other_start), end.min(other_end));
+                if start <= end {
+                    ranges.push((start, end));
+                }
+            }
+        }
+        CpuList::from_ranges(ranges)
+    }
+
+    /// Returns the numbers of the list that aren't in `other`.
+    pub fn difference(&self, other: &CpuList) -> CpuList {
+        let mut ranges = self.ranges.clone();
+        for &(other_start, other_end) in &other.ranges {
+            ranges = ranges
+                .into_iter()
+                .flat_map(|(start, end)| {
+                    if end < other_start || other_end < start {
+                        return vec![(start, end)];
+                    }
+                    let mut left = vec![];
+                    if start < other_start {
+                        left.push((start, other_start - 1));
+                    }
+                    if other_end < end {
+                        left.push((other_end + 1, end));
+                    }
+                    left
+                })
+                .collect();
+        }
+        CpuList::from_ranges(ranges)
+    }
+
+    fn from_ranges(mut ranges: Vec<(u64, u64)>) -> CpuList {
+        ranges.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some((_, last)) if start <= last.saturating_add(1) => *last = end.max(*last),
+                _ => merged.push((start, end)),
+            }
+        }
+        CpuList { ranges: merged }
+    }
+}
+
+impl From<Vec<u64>> for CpuList {
+    fn from(numbers: Vec<u64>) -> CpuList {
+        CpuList::from_ranges(numbers.into_iter().map(|n| (n, n)).collect())
+    }
+}
+
+impl FromStr for CpuList {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<CpuList> {
+        let ranges = parse_range(s.trim().to_string())?;
+        if ranges.iter().any(|(start, end)| start > end) {
+            return Err(Error::new(ParseError));
+        }
+        Ok(CpuList::from_ranges(ranges))
+    }
+}
+
+impl fmt::Display for CpuList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ranges: Vec<String> = self
+            .ranges
+            .iter()
+            .map(|&(start, end)| {
+                if start == end {
+                    start.to_string()
+                } else {
+                    format!("{}-{}", start, end)
+                }
+            })
+            .collect();
+        write!(f, "{}", ranges.join(","))
+    }
+}
+
+const ISOLATED_CPUS: &str = "/sys/devices/system/cpu/isolated";
+
+fn parse_partition(s: &str) -> CpuSetPartition {
+    match s.trim() {
+        "member" => CpuSetPartition::Member,
+        "root" => CpuSetPartition::Root,
+        "isolated" => CpuSetPartition::Isolated,
+        other => CpuSetPartition::Invalid(other.to_string()),
+    }
+}
+
 impl CpuSetController {
     /// Contructs a new `CpuSetController` with `root` serving as the root of the control group.
     pub fn new(point: PathBuf, root: PathBuf, v2: bool) -> Self {
@@ -279,13 +551,13 @@ impl CpuSetController {
                     .unwrap_or_default()
             },
             effective_cpus: {
-                self.open_path("cpuset.effective_cpus", false)
+                self.open_path(self.effective_cpus_file(), false)
                     .and_then(read_string_from)
                     .and_then(parse_range)
                     .unwrap_or_default()
             },
             effective_mems: {
-                self.open_path("cpuset.effective_mems", false)
+                self.open_path(self.effective_mems_file(), false)
                     .and_then(read_string_from)
                     .and_then(parse_range)
                     .unwrap_or_default()
@@ -351,6 +623,208 @@ impl CpuSetController {
         }
     }
 
+    /// Returns the CPUs requested in `cpuset.cpus` that the tasks can't use, because a parent
+    /// doesn't grant them or they are offline, as `(start, end)` ranges like the `cpus` field of
+    /// `CpuSet`.
+    ///
+    /// An empty list means that the pinning fully took effect, which is always the case on v2
+    /// when `cpuset.cpus` is empty as the CPUs of the parent are used.
+    pub fn unmet_cpus(&self) -> Result<Vec<(u64, u64)>> {
+        let granted = CpuList::from_ranges(self.effective_cpus()?);
+        Ok(self.cpus_list()?.difference(&granted).ranges().to_vec())
+    }
+
+    /// Returns the CPUs requested in `cpuset.cpus`, empty on v2 when the CPUs of the parent are
+    /// used.
+    pub fn cpus_list(&self) -> Result<CpuList> {
+        self.open_path("cpuset.cpus", false)
+            .and_then(read_string_from)?
+            .parse()
+    }
+
+    /// Returns the memory nodes requested in `cpuset.mems`, empty on v2 when the nodes of the
+    /// parent are used.
+    pub fn mems_list(&self) -> Result<CpuList> {
+        self.open_path("cpuset.mems", false)
+            .and_then(read_string_from)?
+            .parse()
+    }
+
+    /// Same as `effective_cpus()`, returning a `CpuList`.
+    pub fn effective_cpus_list(&self) -> Result<CpuList> {
+        self.effective_cpus().map(CpuList::from_ranges)
+    }
+
+    /// Same as `effective_mems()`, returning a `CpuList`.
+    pub fn effective_mems_list(&self) -> Result<CpuList> {
+        self.effective_mems().map(CpuList::from_ranges)
+    }
+
+    /// Returns the CPUs that the tasks can effectively run on, as `(start, end)` ranges like the
+    /// `effective_cpus` field of `CpuSet`.
+    ///
+    /// These are the CPUs of `cpuset.cpus` that the parents grant and that are online, e.g.
+    /// `0-3` when asking for `0-7` under a parent limited to `0-3`.
+    pub fn effective_cpus(&self) -> Result<Vec<(u64, u64)>> {
+        self.open_path(self.effective_cpus_file(), false)
+            .and_then(read_string_from)
+            .and_then(parse_range)
+    }
+
+    /// Returns the memory nodes that the tasks can effectively use, as `(start, end)` ranges like
+    /// the `effective_mems` field of `CpuSet`.
+    pub fn effective_mems(&self) -> Result<Vec<(u64, u64)>> {
+        self.open_path(self.effective_mems_file(), false)
+            .and_then(read_string_from)
+            .and_then(parse_range)
+    }
+
+    /// Watches the memory nodes that the tasks can effectively use, which change when nodes go
+    /// offline or when a parent changes its `mems`.
+    ///
+    /// The new set of nodes is sent on the returned channel every time it changes. The kernel
+    /// doesn't notify the changes of `cpuset.mems.effective` (`cpuset.effective_mems` on v1), so
+    /// they show up within a second, when the file is read again.
+    pub fn watch_effective_mems(&self) -> Result<events::Watch<Vec<(u64, u64)>>> {
+        self.verify_path()?;
+        let path = self.get_path().join(self.effective_mems_file());
+        events::watch_changes(&path, |p| {
+            std::fs::read_to_string(p)
+                .map_err(|e| Error::with_cause(ReadFailed(p.display().to_string()), e))
+                .and_then(|s| parse_range(s.trim().to_string()))
+        })
+    }
+
+    fn effective_cpus_file(&self) -> &'static str {
+        if self.v2 {
+            "cpuset.cpus.effective"
+        } else {
+            "cpuset.effective_cpus"
+        }
+    }
+
+    fn effective_mems_file(&self) -> &'static str {
+        if self.v2 {
+            "cpuset.mems.effective"
+        } else {
+            "cpuset.effective_mems"
+        }
+    }
+
+    /// Returns the partition type of the control group (cgroup v2 only).
+    pub fn partition(&self) -> Result<CpuSetPartition> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "cpuset.cpus.partition",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.open_path("cpuset.cpus.partition", false)
+            .and_then(read_string_from)
+            .map(|s| parse_partition(&s))
+    }
+
+    /// Returns whether the scheduler balances the load across the CPUs of the control group,
+    /// from `cpuset.sched_load_balance` on v1 and from the partition type on v2, see
+    /// `CpuSetPartition::load_balanced()`.
+    ///
+    /// On v1 a disabled flag only takes effect if no ancestor sharing the CPUs has load
+    /// balancing enabled, the flag of the root control group in particular.
+    pub fn load_balanced(&self) -> Result<bool> {
+        if self.v2 {
+            return self.partition().map(|p| p.load_balanced());
+        }
+
+        self.open_path("cpuset.sched_load_balance", false)
+            .and_then(read_u64_from)
+            .map(|v| v == 1)
+    }
+
+    /// Makes the control group the root of a partition owning `cpus` (cgroup v2 only).
+    ///
+    /// The kernel handles CPUs isolated with the `isolcpus=` boot parameter specially, and a
+    /// partition including some of them may end up invalid, so `check` selects what to do when
+    /// `cpus` overlaps them. Fails if the kernel reports the resulting partition as invalid.
+    pub fn make_root_partition(&self, cpus: &str, check: IsolatedCpusCheck) -> Result<()> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "cpuset.cpus.partition",
+                "only available on cgroup v2",
+            ));
+        }
+
+        if check != IsolatedCpusCheck::Skip {
+            let requested: CpuList = cpus.parse()?;
+            let isolated: CpuList = std::fs::read_to_string(ISOLATED_CPUS)
+                .map_err(|e| Error::with_cause(ReadFailed(ISOLATED_CPUS.to_string()), e))?
+                .parse()?;
+            if !requested.intersection(&isolated).is_empty() {
+                let msg = format!(
+                    "cpus {} of the partition overlap the isolated cpus {}",
+                    cpus, isolated
+                );
+                if check == IsolatedCpusCheck::Error {
+                    return Err(Error::from_string(msg));
+                }
+                warn!("{}", msg);
+            }
+        }
+
+        self.set_cpus(cpus)?;
+        self.open_path("cpuset.cpus.partition", true)
+            .and_then(|mut file| {
+                file.write_all(b"root").map_err(|e| {
+                    Error::with_cause(
+                        WriteFailed("cpuset.cpus.partition".to_string(), "root".to_string()),
+                        e,
+                    )
+                })
+            })?;
+
+        match self.partition()? {
+            CpuSetPartition::Invalid(state) => {
+                Err(Error::from_string(format!("invalid partition: {}", state)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the nearest control group, starting from this one and going up, that is the root
+    /// of a valid partition, i.e. the partition the tasks' CPUs belong to (cgroup v2 only).
+    ///
+    /// Returns `None` if the control group is in the top-level partition.
+    pub fn partition_root(&self) -> Result<Option<Cgroup>> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "cpuset.cpus.partition",
+                "only available on cgroup v2",
+            ));
+        }
+        self.verify_path()?;
+
+        // the walk stops at the mount point, above which are the directories of other file systems
+        let path = self.get_path();
+        let root = unified_mount_point_of(path).ok_or_else(|| Error::new(InvalidPath))?;
+        let root = root.as_path();
+
+        for dir in path.ancestors().take_while(|p| *p != root) {
+            let file = dir.join("cpuset.cpus.partition");
+            let partition = std::fs::read_to_string(&file)
+                .map(|s| parse_partition(&s))
+                .map_err(|e| Error::with_cause(ReadFailed(file.display().to_string()), e))?;
+            if partition == CpuSetPartition::Root || partition == CpuSetPartition::Isolated {
+                let relative = dir.strip_prefix(root).unwrap_or(dir);
+                return Ok(Some(Cgroup::load(
+                    Box::new(V2::with_root(root.to_string_lossy())),
+                    relative,
+                )));
+            }
+        }
+
+        Ok(None)
+    }
+
     /// Control whether the CPUs selected via `set_cpus()` should be exclusive to this control
     /// group or not.
     pub fn set_cpu_exclusive(&self, b: bool) -> Result<()> {
@@ -400,24 +874,26 @@ impl CpuSetController {
     /// Set the CPUs that the tasks in this control group can run on.
     ///
     /// Syntax is a comma separated list of CPUs, with an additional extension that ranges can
-    /// be represented via dashes.
+    /// be represented via dashes, see `set_cpus_list()` to pass a `CpuList`.
     pub fn set_cpus(&self, cpus: &str) -> Result<()> {
-        self.open_path("cpuset.cpus", true).and_then(|mut file| {
-            file.write_all(cpus.as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed("cpuset.cpus".to_string(), cpus.to_string()), e)
-            })
-        })
+        self.write_file("cpuset.cpus", cpus)
     }
 
     /// Set the memory nodes that the tasks in this control group can use.
     ///
-    /// Syntax is the same as with `set_cpus()`.
+    /// Syntax is the same as with `set_cpus()`, see `set_mems_list()` to pass a `CpuList`.
     pub fn set_mems(&self, mems: &str) -> Result<()> {
-        self.open_path("cpuset.mems", true).and_then(|mut file| {
-            file.write_all(mems.as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed("cpuset.mems".to_string(), mems.to_string()), e)
-            })
-        })
+        self.write_file("cpuset.mems", mems)
+    }
+
+    /// Same as `set_cpus()`, taking the CPUs as a `CpuList`.
+    pub fn set_cpus_list(&self, cpus: &CpuList) -> Result<()> {
+        self.set_cpus(&cpus.to_string())
+    }
+
+    /// Same as `set_mems()`, taking the memory nodes as a `CpuList`.
+    pub fn set_mems_list(&self, mems: &CpuList) -> Result<()> {
+        self.set_mems(&mems.to_string())
     }
 
     /// Controls whether the control group should be "hardwalled", i.e., whether kernel allocations
@@ -473,15 +949,44 @@ impl CpuSetController {
     ///
     /// See @CpuSet 's similar field for more information.
     pub fn set_rebalance_relax_domain_level(&self, i: i64) -> Result<()> {
-        self.open_path("cpuset.sched_relax_domain_level", true)
-            .and_then(|mut file| {
-                file.write_all(i.to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("cpuset.sched_relax_domain_level".to_string(), i.to_string()),
-                        e,
-                    )
-                })
-            })
+        self.write_file("cpuset.sched_relax_domain_level", &i.to_string())
+    }
+
+    /// Like `set_rebalance_relax_domain_level()`, but checks `level` against the scheduler
+    /// domains of the system first (cgroup v1 only).
+    ///
+    /// The valid levels range from -1, the system default, to the number of scheduler domain
+    /// levels, found in `/proc/schedstat`. Higher levels are clamped by the kernel, which is
+    /// logged as a warning. Only the lower bound is checked when `/proc/schedstat` is
+    /// unavailable, i.e. when the kernel is built without `CONFIG_SCHEDSTATS`.
+    pub fn set_relax_domain_level_checked(&self, level: i64) -> Result<()> {
+        if self.v2 {
+            return Err(Error::not_supported(
+                "cpuset.sched_relax_domain_level",
+                "only available on cgroup v1",
+            ));
+        }
+        if level < -1 {
+            return Err(Error::new(InvalidArgument(format!(
+                "invalid scheduler relax domain level {}",
+                level
+            ))));
+        }
+
+        match std::fs::read_to_string(SCHEDSTAT) {
+            Ok(content) => {
+                let max_level = schedstat_domain_levels(&content) as i64;
+                if level > max_level {
+                    warn!(
+                        "scheduler relax domain level {} is clamped to {}",
+                        level, max_level
+                    );
+                }
+            }
+            Err(e) => debug!("unable to read {}: {}", SCHEDSTAT, e),
+        }
+
+        self.set_rebalance_relax_domain_level(level)
     }
 
     /// Control whether when using `set_mems()` the existing memory used by the tasks should be
@@ -592,6 +1097,59 @@ impl CpuSetController {
 #[cfg(test)]
 mod tests {
     use crate::fs::cpuset;
+
+    #[test]
+    fn test_partition_load_balanced() {
+        assert!(cpuset::CpuSetPartition::Member.load_balanced());
+        assert!(cpuset::CpuSetPartition::Root.load_balanced());
+        assert!(!cpuset::CpuSetPartition::Isolated.load_balanced());
+        assert!(
+            cpuset::parse_partition("root invalid (Parent is not a partition root)")
+                .load_balanced()
+        );
+    }
+
+    #[test]
+    fn test_cpu_list_parse_and_format() {
+        use cpuset::CpuList;
+
+        for (s, ranges, formatted) in [
+            ("", vec![], ""),
+            ("5", vec![(5, 5)], "5"),
+            ("0-3,6,8-11\n", vec![(0, 3), (6, 6), (8, 11)], "0-3,6,8-11"),
+            ("3,0-1,2", vec![(0, 3)], "0-3"),
+            ("4-6,0,5-9,1", vec![(0, 1), (4, 9)], "0-1,4-9"),
+        ] {
+            let list: CpuList = s.parse().unwrap();
+            assert_eq!(list.ranges(), &ranges[..], "{:?}", s);
+            assert_eq!(list.to_string(), formatted);
+        }
+        for s in ["3-1", "1-2-3", "a", "1,,2"] {
+            assert!(s.parse::<CpuList>().is_err(), "{:?}", s);
+        }
+        assert_eq!(CpuList::from(vec![7, 1, 2, 3, 5, 2]).to_string(), "1-3,5,7");
+        assert!(CpuList::from(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_cpu_list_operations() {
+        use cpuset::CpuList;
+
+        let list = |s: &str| s.parse::<CpuList>().unwrap();
+        assert!(list("0-3,6").contains(2));
+        assert!(list("0-3,6").contains(6));
+        assert!(!list("0-3,6").contains(4));
+        assert_eq!(list("0-2,5").iter().collect::<Vec<_>>(), vec![0, 1, 2, 5]);
+
+        assert_eq!(list("0-7").intersection(&list("2-3,6-9")), list("2-3,6-7"));
+        assert_eq!(list("0-3").intersection(&list("4-7")), list(""));
+
+        assert_eq!(list("0-3").difference(&list("0-3")), list(""));
+        assert_eq!(list("0-7").difference(&list("0-1,4")), list("2-3,5-7"));
+        assert_eq!(list("2,6-8").difference(&list("0-1")), list("2,6-8"));
+        assert_eq!(list("").difference(&list("0-3")), list(""));
+    }
+
     #[test]
     fn test_parse_range() {
         let test_cases = vec![
@@ -618,4 +1176,39 @@ mod tests {
             assert_eq!(range.unwrap(), expecteds[i]);
         }
     }
+
+    #[test]
+    fn test_parse_partition() {
+        use cpuset::CpuSetPartition;
+
+        assert_eq!(cpuset::parse_partition("member\n"), CpuSetPartition::Member);
+        assert_eq!(cpuset::parse_partition("root\n"), CpuSetPartition::Root);
+        assert_eq!(
+            cpuset::parse_partition("isolated\n"),
+            CpuSetPartition::Isolated
+        );
+        assert_eq!(
+            cpuset::parse_partition("root invalid (Parent is not a partition root)\n"),
+            CpuSetPartition::Invalid("root invalid (Parent is not a partition root)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_schedstat_domain_levels() {
+        let content = "version 15
+timestamp 4295259418
+cpu0 0 0 0 0 0 0 1210434340 109342154 21452
+domain0 00000003 1 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+domain1 000000ff 2 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+cpu1 0 0 0 0 0 0 1044535939 69218420 12398
+domain0 00000003 1 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+domain1 000000ff 2 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+domain2 0000ffff 2 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+";
+        assert_eq!(cpuset::schedstat_domain_levels(content), 3);
+        assert_eq!(
+            cpuset::schedstat_domain_levels("version 15\ntimestamp 1\ncpu0 0 0 0\n"),
+            0
+        );
+    }
 }
@@ -7,8 +7,12 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/devices.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/devices.txt)
-use std::io::{Read, Write};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::Read;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
 use log::*;
 
@@ -151,10 +155,23 @@ impl DevicePermissions {
     }
 }
 
+// The control files used by the controller on cgroup v1, see `Controller::managed_files()`.
+const MANAGED_FILES_V1: &[&str] = &["devices.allow", "devices.deny", "devices.list"];
+
+// The control files used by the controller on cgroup v2.
+const MANAGED_FILES_V2: &[&str] = &[];
+
 impl ControllerInternal for DevicesController {
     fn control_type(&self) -> Controllers {
         Controllers::Devices
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        if self.is_v2() {
+            MANAGED_FILES_V2
+        } else {
+            MANAGED_FILES_V1
+        }
+    }
     fn get_path(&self) -> &PathBuf {
         &self.path
     }
@@ -165,6 +182,11 @@ impl ControllerInternal for DevicesController {
         &self.base
     }
 
+    // Every v2 control group has this file, while v2 has no devices controller.
+    fn is_v2(&self) -> bool {
+        self.path.join("cgroup.controllers").exists()
+    }
+
     fn apply(&self, res: &Resources) -> Result<()> {
         // get the resources that apply to this controller
         let res: &DeviceResources = &res.devices;
@@ -179,6 +201,24 @@ impl ControllerInternal for DevicesController {
 
         Ok(())
     }
+
+    fn reset(&self) -> Result<()> {
+        if self.is_v2() {
+            return Ok(());
+        }
+
+        // allowing all the devices drops the other rules
+        self.allow_device(DeviceType::All, -1, -1, &DevicePermissions::all())
+    }
+
+    fn read_resources(&self, res: &mut Resources) -> Result<()> {
+        if self.is_v2() {
+            return Ok(());
+        }
+
+        res.devices.devices = self.allowed_devices()?;
+        Ok(())
+    }
 }
 
 impl ControllIdentifier for DevicesController {
@@ -187,17 +227,15 @@ impl ControllIdentifier for DevicesController {
     }
 }
 
-impl<'a> From<&'a Subsystem> for &'a DevicesController {
-    fn from(sub: &'a Subsystem) -> &'a DevicesController {
-        unsafe {
-            match sub {
-                Subsystem::Devices(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a DevicesController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a DevicesController> {
+        match sub {
+            Subsystem::Devices(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                DevicesController::controller_type().to_string(),
+            ))),
         }
     }
 }
@@ -215,6 +253,9 @@ impl DevicesController {
     ///
     /// When `-1` is passed as `major` or `minor`, the kernel interprets that value as "any",
     /// meaning that it will match any device.
+    ///
+    /// At least one permission is needed, `MkNod` alone being valid: it allows creating device
+    /// nodes with `mknod(2)` but not accessing the device.
     pub fn allow_device(
         &self,
         devtype: DeviceType,
@@ -222,6 +263,10 @@ impl DevicesController {
         minor: i64,
         perm: &[DevicePermissions],
     ) -> Result<()> {
+        if self.is_v2() {
+            return Err(devices_not_supported("devices.allow"));
+        }
+        check_permissions(perm)?;
         let perms = perm
             .iter()
             .map(DevicePermissions::to_char)
@@ -237,17 +282,7 @@ impl DevicesController {
             format!("{}", major)
         };
         let final_str = format!("{} {}:{} {}", devtype.to_char(), major, minor, perms);
-        self.open_path("devices.allow", true).and_then(|mut file| {
-            file.write_all(final_str.as_ref()).map_err(|e| {
-                Error::with_cause(
-                    WriteFailed(
-                        self.get_path().join("devices.allow").display().to_string(),
-                        final_str,
-                    ),
-                    e,
-                )
-            })
-        })
+        self.write_file("devices.allow", &final_str)
     }
 
     /// Deny the control group's tasks access to the devices covered by `dev`.
@@ -261,6 +296,10 @@ impl DevicesController {
         minor: i64,
         perm: &[DevicePermissions],
     ) -> Result<()> {
+        if self.is_v2() {
+            return Err(devices_not_supported("devices.deny"));
+        }
+        check_permissions(perm)?;
         let perms = perm
             .iter()
             .map(DevicePermissions::to_char)
@@ -276,21 +315,19 @@ impl DevicesController {
             format!("{}", major)
         };
         let final_str = format!("{} {}:{} {}", devtype.to_char(), major, minor, perms);
-        self.open_path("devices.deny", true).and_then(|mut file| {
-            file.write_all(final_str.as_ref()).map_err(|e| {
-                Error::with_cause(
-                    WriteFailed(
-                        self.get_path().join("devices.deny").display().to_string(),
-                        final_str,
-                    ),
-                    e,
-                )
-            })
-        })
+        self.write_file("devices.deny", &final_str)
     }
 
     /// Get the current list of allowed devices.
+    ///
+    /// Fails with `NotSupported` on cgroup v2, which has no `devices.list`: the device access is
+    /// controlled by a BPF program attached to the control group instead, which can't be read
+    /// back as a list of rules. The same goes for `allow_device()` and `deny_device()`.
     pub fn allowed_devices(&self) -> Result<Vec<DeviceResource>> {
+        if self.is_v2() {
+            return Err(devices_not_supported("devices.list"));
+        }
+
         self.open_path("devices.list", false).and_then(|mut file| {
             let mut s = String::new();
             let res = file.read_to_string(&mut s);
@@ -305,6 +342,42 @@ impl DevicesController {
     }
 }
 
+/// The error returned when the device rules are used on cgroup v2, where the device access is
+/// controlled by a `BPF_PROG_TYPE_CGROUP_DEVICE` program rather than by a controller.
+pub(crate) fn devices_not_supported(operation: &str) -> Error {
+    Error::not_supported(
+        operation,
+        "cgroup v2 controls device access with a BPF program attached to the control group",
+    )
+}
+
+// The names of the block devices resolved so far, which don't change while they exist.
+static BLOCK_DEVICE_NAMES: Mutex<BTreeMap<(u64, u64), String>> = Mutex::new(BTreeMap::new());
+
+/// Returns the name of the block device `major`:`minor` in `/dev`, e.g. `sda` for `8:0`, found
+/// through `/sys/dev/block/<major>:<minor>`, or `None` if there is no such device.
+///
+/// The names found are cached. A cached name is dropped once `/sys/class/block/<name>/dev` no
+/// longer holds `major`:`minor`, i.e. when the device was removed and its number possibly given
+/// to another one.
+pub fn resolve_block_device(major: u64, minor: u64) -> Option<String> {
+    let device = format!("{}:{}", major, minor);
+    let mut names = BLOCK_DEVICE_NAMES.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(name) = names.get(&(major, minor)) {
+        let dev = fs::read_to_string(format!("/sys/class/block/{}/dev", name));
+        if matches!(dev, Ok(dev) if dev.trim() == device) {
+            return Some(name.clone());
+        }
+        names.remove(&(major, minor));
+    }
+
+    // The entry links to the device in the sysfs tree, whose directory is named after it.
+    let link = fs::read_link(format!("/sys/dev/block/{}", device)).ok()?;
+    let name = link.file_name()?.to_str()?.to_string();
+    names.insert((major, minor), name.clone());
+    Some(name)
+}
+
 fn parse_device_number(s: &str) -> Result<i64> {
     if s == "*" {
         Ok(-1)
@@ -313,6 +386,15 @@ fn parse_device_number(s: &str) -> Result<i64> {
     }
 }
 
+fn check_permissions(perm: &[DevicePermissions]) -> Result<()> {
+    if perm.is_empty() {
+        return Err(Error::new(InvalidArgument(
+            "a device rule needs at least one permission".to_string(),
+        )));
+    }
+    Ok(())
+}
+
 fn parse_device_line(line: &str, allow: bool) -> Result<DeviceResource> {
     let parts: Vec<&str> = line.split([' ', ':']).collect();
     if parts.len() != 4 {
@@ -7,6 +7,8 @@
 use std::error::Error as StdError;
 use std::fmt;
 
+use crate::CgroupPid;
+
 /// The different types of errors that can occur while manipulating control groups.
 #[derive(thiserror::Error, Debug, Eq, PartialEq)]
 pub enum ErrorKind {
@@ -48,6 +50,19 @@ pub enum ErrorKind {
     #[error("the given path is invalid")]
     InvalidPath,
 
+    /// A component of the name of the control group is rejected, e.g. `..` or a name starting
+    /// with `cgroup.` that would clash with the interface files, see `Cgroup::create()`.
+    #[error("invalid control group name: {0}")]
+    InvalidName(String),
+
+    /// A value given to a setter is out of range or malformed, e.g. a swappiness above 100.
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+
+    /// The control group doesn't exist, see `Cgroup::load_existing()`.
+    #[error("the control group {0} does not exist")]
+    NotFound(String),
+
     #[error("invalid bytes size")]
     InvalidBytesSize,
 
@@ -56,9 +71,82 @@ pub enum ErrorKind {
     SpecifiedControllers,
 
     /// Using method in wrong cgroup version.
+    ///
+    /// Operations now report `NotSupported` instead, which tells what is missing.
     #[error("using method in wrong cgroup version")]
     CgroupVersion,
 
+    /// The operation isn't available with this cgroup version or kernel.
+    #[error("{operation} is not supported: {reason}")]
+    NotSupported { operation: String, reason: String },
+
+    /// The control group file can't be configured on the root control group, e.g. `memory.max`.
+    #[error("{0} can not be configured on the root control group")]
+    RootCgroupNotConfigurable(String),
+
+    /// A controller can't be enabled in `cgroup.subtree_control` of a v2 control group that has
+    /// processes of its own (the "no internal process" rule).
+    #[error("unable to enable the {0} controller for the children of a control group with processes, move them to a child control group first")]
+    ControllerEnableBlockedByProcesses(String),
+
+    /// Processes can't be added to a v2 control group whose type is "domain invalid", it must be
+    /// turned threaded first, see `Cgroup::cgroup_type()`.
+    #[error("unable to add processes to a domain invalid control group, make it threaded first")]
+    DomainInvalid,
+
+    /// The kernel rejected changing the type of a v2 control group to the given one, see
+    /// `Cgroup::set_type()`.
+    #[error("unable to change the type of the control group to {0}, only domain control groups whose parent allows it can be made threaded")]
+    CgroupTypeRejected(String),
+
+    /// The owner of a control group file couldn't be changed, see `Cgroup::delegate_to()`. The
+    /// cause is `EPERM` when giving files away without `CAP_CHOWN`.
+    #[error("unable to change the owner of {0}")]
+    ChownFailed(String),
+
+    /// Applying resources didn't complete in time, see `Cgroup::apply_with_timeout()`. The
+    /// controllers already configured are listed in `applied`, and `pending` is the one whose
+    /// files were being written.
+    #[error("timed out applying the resources of the {pending} controller, applied: {applied:?}")]
+    ApplyTimedOut {
+        applied: Vec<String>,
+        pending: String,
+    },
+
+    /// Applying the resources of the `failed` controller failed, and the controllers configured
+    /// until then were restored, see `Cgroup::apply_transactional()`. `not_restored` lists the
+    /// settings whose previous values couldn't be restored.
+    #[error("failed to apply the resources of the {failed} controller, rolled back except: {not_restored:?}")]
+    ApplyRolledBack {
+        failed: String,
+        not_restored: Vec<String>,
+    },
+
+    /// Some processes were still in the control group when `Cgroup::kill_and_wait()` gave up,
+    /// e.g. because they were in uninterruptible sleep.
+    #[error("processes left in the control group after being killed: {0:?}")]
+    KillTimedOut(Vec<CgroupPid>),
+
+    /// The control group was still freezing when `FreezerController::freeze_and_wait()` gave up,
+    /// e.g. because some of its processes were in uninterruptible sleep.
+    #[error("timed out waiting for the control group to be frozen")]
+    FreezeTimedOut,
+
+    /// The controller isn't attached to the hierarchy of the control group, see
+    /// `Cgroup::try_controller_of()`.
+    #[error("the {0} controller is not available in the hierarchy")]
+    ControllerNotAvailable(String),
+
+    /// The subsystem isn't the requested controller, when converting a `Subsystem` into a
+    /// controller with `TryFrom`.
+    #[error("the subsystem is not the {0} controller")]
+    ControllerNotFound(String),
+
+    /// The controller is attached to the v2 hierarchy but not enabled in the control group, i.e.
+    /// in `cgroup.subtree_control` of its parent, see `Cgroup::try_controller_of()`.
+    #[error("the {0} controller is not enabled in the control group")]
+    ControllerNotEnabled(String),
+
     /// Using method in wrong cgroup mode.
     #[error("using method in wrong cgroup mode.")]
     CgroupMode,
@@ -109,6 +197,13 @@ impl Error {
         Self { kind, cause: None }
     }
 
+    pub(crate) fn not_supported(operation: &str, reason: &str) -> Self {
+        Self::new(ErrorKind::NotSupported {
+            operation: operation.to_string(),
+            reason: reason.to_string(),
+        })
+    }
+
     pub(crate) fn with_cause<E>(kind: ErrorKind, cause: E) -> Self
     where
         E: 'static + Send + Sync + StdError,
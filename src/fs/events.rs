@@ -4,12 +4,17 @@
 //
 
 use eventfd::{eventfd, EfdFlags};
+use nix::poll::{poll, PollFd, PollFlags};
 use nix::sys::eventfd;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
 use std::fs::{self, File};
 use std::io::Read;
+use std::ops::Deref;
 use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
 use std::thread;
 
 use crate::fs::error::ErrorKind::*;
@@ -30,10 +35,10 @@ pub fn notify_on_oom_v1(key: &str, dir: &Path) -> Result<Receiver<String>> {
 // level is one of "low", "medium", or "critical"
 pub fn notify_memory_pressure(key: &str, dir: &Path, level: &str) -> Result<Receiver<String>> {
     if level != "low" && level != "medium" && level != "critical" {
-        return Err(Error::from_string(format!(
+        return Err(Error::new(InvalidArgument(format!(
             "invalid pressure level {}",
             level
-        )));
+        ))));
     }
 
     register_memory_event(key, dir, "memory.pressure_level", level)
@@ -90,3 +95,128 @@ fn register_memory_event(
 
     Ok(receiver)
 }
+
+// How often, in milliseconds, watched files are read again when the kernel doesn't notify their
+// changes.
+const WATCH_INTERVAL_MS: i32 = 1000;
+
+/// The receiving end of a watch on a control file, e.g. from `Cgroup::watch_events()`, used as
+/// the `Receiver` it dereferences to.
+///
+/// The file is watched by a thread of its own, which stops and closes its inotify instance
+/// once the `Watch` is dropped, within a second, or once the control group is removed.
+#[derive(Debug)]
+pub struct Watch<T> {
+    receiver: Receiver<T>,
+    stop: Arc<AtomicBool>,
+}
+
+impl<T> Deref for Watch<T> {
+    type Target = Receiver<T>;
+
+    fn deref(&self) -> &Receiver<T> {
+        &self.receiver
+    }
+}
+
+impl<T> Drop for Watch<T> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// watch_changes returns a channel on which the value returned by `read` is sent every time it
+// changes. Not every control file notifies its changes through inotify, so the file is also read
+// again every WATCH_INTERVAL_MS, which is also how soon the thread notices the Watch got
+// dropped. The channel is closed once the file can't be read anymore, e.g. when the cgroup is
+// removed.
+pub(crate) fn watch_changes<T, F>(path: &Path, read: F) -> Result<Watch<T>>
+where
+    T: Clone + PartialEq + Send + 'static,
+    F: Fn(&Path) -> Result<T> + Send + 'static,
+{
+    let inotify = Inotify::init(InitFlags::IN_CLOEXEC | InitFlags::IN_NONBLOCK)
+        .map_err(|e| Error::with_cause(ReadFailed("inotify".to_string()), e))?;
+    // Inotify doesn't close its fd when dropped, take ownership of it.
+    let inotify_file = unsafe { File::from_raw_fd(inotify.as_raw_fd()) };
+    inotify
+        .add_watch(
+            path,
+            AddWatchFlags::IN_MODIFY | AddWatchFlags::IN_DELETE_SELF,
+        )
+        .map_err(|e| Error::with_cause(ReadFailed(path.display().to_string()), e))?;
+
+    let mut last = read(path)?;
+    let path = path.to_path_buf();
+    let (sender, receiver) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+
+    thread::spawn(move || {
+        let _inotify_file = inotify_file;
+        loop {
+            let mut fds = [PollFd::new(inotify.as_raw_fd(), PollFlags::POLLIN)];
+            if poll(&mut fds, WATCH_INTERVAL_MS).is_err() || stopped.load(Ordering::Relaxed) {
+                return;
+            }
+            if let Ok(events) = inotify.read_events() {
+                let removed = AddWatchFlags::IN_DELETE_SELF | AddWatchFlags::IN_IGNORED;
+                if events.iter().any(|e| e.mask.intersects(removed)) {
+                    return;
+                }
+            }
+
+            let current = match read(&path) {
+                Ok(current) => current,
+                Err(_) => return,
+            };
+            if current != last {
+                last = current.clone();
+                if sender.send(current).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    Ok(Watch { receiver, stop })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::events::{watch_changes, WATCH_INTERVAL_MS};
+    use std::fs;
+    use std::thread;
+    use std::time::Duration;
+
+    fn inotify_fds() -> usize {
+        fs::read_dir("/proc/self/fd")
+            .unwrap()
+            .flatten()
+            .filter(|fd| {
+                fs::read_link(fd.path())
+                    .map(|target| target.to_string_lossy() == "anon_inode:inotify")
+                    .unwrap_or(false)
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_watch_changes_drop() {
+        let path = std::env::temp_dir().join(format!("cgroups-rs-watch-{}", std::process::id()));
+        fs::write(&path, "1").unwrap();
+        let before = inotify_fds();
+
+        let watch = watch_changes(&path, |p| Ok(fs::read_to_string(p).unwrap())).unwrap();
+        fs::write(&path, "2").unwrap();
+        assert_eq!(watch.recv_timeout(Duration::from_secs(5)).unwrap(), "2");
+        assert_eq!(inotify_fds(), before + 1);
+
+        // the thread notices the drop at its next wake-up and closes the inotify instance
+        drop(watch);
+        thread::sleep(Duration::from_millis(2 * WATCH_INTERVAL_MS as u64));
+        assert_eq!(inotify_fds(), before);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
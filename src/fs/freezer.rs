@@ -8,12 +8,18 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/freezer-subsystem.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/freezer-subsystem.txt)
-use std::io::{Read, Write};
+use std::convert::TryFrom;
+use std::io::Read;
 use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::fs::error::ErrorKind::*;
 use crate::fs::error::*;
-use crate::fs::{ControllIdentifier, ControllerInternal, Controllers, Resources, Subsystem};
+use crate::fs::{
+    read_string_from, read_u64_from, ControllIdentifier, ControllerInternal, Controllers,
+    Resources, Subsystem,
+};
 use crate::FreezerState;
 
 /// A controller that allows controlling the `freezer` subsystem of a Cgroup.
@@ -31,10 +37,27 @@ pub struct FreezerController {
     v2: bool,
 }
 
+// The control files used by the controller on cgroup v1, see `Controller::managed_files()`.
+const MANAGED_FILES_V1: &[&str] = &[
+    "freezer.state",
+    "freezer.self_freezing",
+    "freezer.parent_freezing",
+];
+
+// The control files used by the controller on cgroup v2.
+const MANAGED_FILES_V2: &[&str] = &["cgroup.freeze", "cgroup.events"];
+
 impl ControllerInternal for FreezerController {
     fn control_type(&self) -> Controllers {
         Controllers::Freezer
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        if self.is_v2() {
+            MANAGED_FILES_V2
+        } else {
+            MANAGED_FILES_V1
+        }
+    }
     fn get_path(&self) -> &PathBuf {
         &self.path
     }
@@ -48,6 +71,10 @@ impl ControllerInternal for FreezerController {
     fn apply(&self, _res: &Resources) -> Result<()> {
         Ok(())
     }
+
+    fn reset(&self) -> Result<()> {
+        self.thaw()
+    }
 }
 
 impl ControllIdentifier for FreezerController {
@@ -56,17 +83,15 @@ impl ControllIdentifier for FreezerController {
     }
 }
 
-impl<'a> From<&'a Subsystem> for &'a FreezerController {
-    fn from(sub: &'a Subsystem) -> &'a FreezerController {
-        unsafe {
-            match sub {
-                Subsystem::Freezer(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a FreezerController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a FreezerController> {
+        match sub {
+            Subsystem::Freezer(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                FreezerController::controller_type().to_string(),
+            ))),
         }
     }
 }
@@ -81,6 +106,9 @@ impl FreezerController {
         }
     }
     /// Freezes the processes in the control group.
+    ///
+    /// This only requests the freeze, `state()` reports `Freezing` until all the processes are
+    /// frozen; use `freeze_and_wait()` to wait for the `Frozen` state.
     pub fn freeze(&self) -> Result<()> {
         let mut file_name = "freezer.state";
         let mut content = "FROZEN".to_string();
@@ -89,10 +117,27 @@ impl FreezerController {
             content = "1".to_string();
         }
 
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(content.as_ref())
-                .map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), content), e))
-        })
+        self.write_file(file_name, &content)
+    }
+
+    /// Freezes the processes in the control group, then waits up to `timeout` for the state to
+    /// move from `Freezing` to `Frozen`, failing with `FreezeTimedOut` otherwise.
+    ///
+    /// Freezing is asynchronous on both versions: the processes are only frozen once they all
+    /// got out of the kernel, which processes in uninterruptible sleep may not do in time.
+    pub fn freeze_and_wait(&self, timeout: Duration) -> Result<()> {
+        self.freeze()?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.state()? == FreezerState::Frozen {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::new(FreezeTimedOut));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
     }
 
     /// Thaws, that is, unfreezes the processes in the control group.
@@ -103,18 +148,35 @@ impl FreezerController {
             file_name = "cgroup.freeze";
             content = "0".to_string();
         }
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(content.as_ref())
-                .map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), content), e))
-        })
+        self.write_file(file_name, &content)
     }
 
     /// Retrieve the state of processes in the control group.
+    ///
+    /// On v2, `cgroup.freeze` only holds the requested state, the processes are `Frozen` once
+    /// the `frozen` field of `cgroup.events` says so, which also covers a control group frozen
+    /// by one of its ancestors, and `Freezing` until then.
     pub fn state(&self) -> Result<FreezerState> {
-        let mut file_name = "freezer.state";
         if self.v2 {
-            file_name = "cgroup.freeze";
+            let frozen = self
+                .open_path("cgroup.events", false)
+                .and_then(read_string_from)?
+                .lines()
+                .any(|l| l.split_whitespace().eq(["frozen", "1"]));
+            if frozen {
+                return Ok(FreezerState::Frozen);
+            }
+            let requested = self
+                .open_path("cgroup.freeze", false)
+                .and_then(read_u64_from)?;
+            return Ok(if requested == 1 {
+                FreezerState::Freezing
+            } else {
+                FreezerState::Thawed
+            });
         }
+
+        let file_name = "freezer.state";
         self.open_path(file_name, false).and_then(|mut file| {
             let mut s = String::new();
             let res = file.read_to_string(&mut s);
@@ -122,8 +184,6 @@ impl FreezerController {
                 Ok(_) => match s.trim() {
                     "FROZEN" => Ok(FreezerState::Frozen),
                     "THAWED" => Ok(FreezerState::Thawed),
-                    "1" => Ok(FreezerState::Frozen),
-                    "0" => Ok(FreezerState::Thawed),
                     "FREEZING" => Ok(FreezerState::Freezing),
                     _ => Err(Error::new(ParseError)),
                 },
@@ -131,4 +191,30 @@ impl FreezerController {
             }
         })
     }
+
+    /// Whether the control group itself was asked to freeze, as found in
+    /// `freezer.self_freezing` (cgroup v1 only).
+    pub fn self_freezing(&self) -> Result<bool> {
+        self.read_freezing("freezer.self_freezing")
+    }
+
+    /// Whether the control group is frozen because one of its ancestors was asked to freeze, as
+    /// found in `freezer.parent_freezing` (cgroup v1 only). Thawing the control group itself
+    /// doesn't resume its processes then.
+    pub fn parent_freezing(&self) -> Result<bool> {
+        self.read_freezing("freezer.parent_freezing")
+    }
+
+    fn read_freezing(&self, file_name: &str) -> Result<bool> {
+        if self.v2 {
+            return Err(Error::not_supported(
+                file_name,
+                "only available on cgroup v1",
+            ));
+        }
+
+        self.open_path(file_name, false)
+            .and_then(read_u64_from)
+            .map(|v| v == 1)
+    }
 }
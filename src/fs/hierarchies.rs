@@ -6,6 +6,7 @@
 
 //! This module represents the various control group hierarchies the Linux kernel supports.
 
+use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -16,9 +17,11 @@ use crate::fs::cpu::CpuController;
 use crate::fs::cpuacct::CpuAcctController;
 use crate::fs::cpuset::CpuSetController;
 use crate::fs::devices::DevicesController;
+use crate::fs::error::{Error, Result};
 use crate::fs::freezer::FreezerController;
 use crate::fs::hugetlb::HugeTlbController;
 use crate::fs::memory::MemController;
+use crate::fs::misc::MiscController;
 use crate::fs::net_cls::NetClsController;
 use crate::fs::net_prio::NetPrioController;
 use crate::fs::perf_event::PerfEventController;
@@ -34,6 +37,13 @@ use crate::fs::cgroup::Cgroup;
 /// See `proc(5)` for format details.
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Mountinfo {
+    /// Unique ID of the mount.
+    pub mount_id: u32,
+    /// ID of the parent mount, or of the mount itself at the top of the mount tree.
+    pub parent_id: u32,
+    /// Device number of the file system, as `(major, minor)`, shared by the bind mounts of the
+    /// same file system.
+    pub st_dev: (u32, u32),
     /// Mount root directory of the file system.
     pub mount_root: PathBuf,
     /// Mount pathname relative to the process's root.
@@ -55,6 +65,10 @@ pub(crate) fn parse_mountinfo_for_line(line: &str) -> Option<Mountinfo> {
     if s0_values.len() < 6 || s1_values.len() < 3 {
         return None;
     }
+    let mount_id = s0_values[0].parse().ok()?;
+    let parent_id = s0_values[1].parse().ok()?;
+    let (major, minor) = s0_values[2].split_once(':')?;
+    let st_dev = (major.parse().ok()?, minor.parse().ok()?);
     let mount_point = PathBuf::from(s0_values[4]);
     let mount_root = PathBuf::from(s0_values[3]);
     let fs_type_values: Vec<_> = s1_values[0].trim().split('.').collect();
@@ -69,6 +83,9 @@ pub(crate) fn parse_mountinfo_for_line(line: &str) -> Option<Mountinfo> {
 
     let super_opts: Vec<String> = s1_values[2].trim().split(',').map(String::from).collect();
     Some(Mountinfo {
+        mount_id,
+        parent_id,
+        st_dev,
         mount_root,
         mount_point,
         fs_type,
@@ -76,14 +93,14 @@ pub(crate) fn parse_mountinfo_for_line(line: &str) -> Option<Mountinfo> {
     })
 }
 
-/// Parses the provided mountinfo file.
+/// Parses the provided mountinfo file, keeping both the `cgroup` and `cgroup2` mounts.
 fn mountinfo_file(file: &mut File) -> Vec<Mountinfo> {
     let mut r = Vec::new();
     for line in BufReader::new(file).lines() {
         match line {
             Ok(line) => {
                 if let Some(mi) = parse_mountinfo_for_line(&line) {
-                    if mi.fs_type.0 == "cgroup" {
+                    if mi.fs_type.0 == "cgroup" || mi.fs_type.0 == "cgroup2" {
                         r.push(mi);
                     }
                 }
@@ -94,7 +111,7 @@ fn mountinfo_file(file: &mut File) -> Vec<Mountinfo> {
     r
 }
 
-/// Returns mounts information for the current process.
+/// Returns the control group mounts information for the current process.
 pub fn mountinfo_self() -> Vec<Mountinfo> {
     match File::open("/proc/self/mountinfo") {
         Ok(mut file) => mountinfo_file(&mut file),
@@ -177,26 +194,33 @@ impl Hierarchy for V1 {
     }
 
     fn root_control_group(&self) -> Cgroup {
-        Cgroup::load(auto(), "")
+        Cgroup::load(Box::new(self.clone()), "")
     }
 
     fn parent_control_group(&self, path: &str) -> Cgroup {
-        let path = Path::new(path);
-        let parent_path = path.parent().unwrap().to_string_lossy().to_string();
-        Cgroup::load(auto(), parent_path)
+        Cgroup::load(Box::new(self.clone()), parent_path(path))
     }
 
+    /// Returns the directory the v1 hierarchies are mounted in, or `UNIFIED_MOUNTPOINT` if there
+    /// is no cgroup mount, in which case the operations on the control groups fail.
     fn root(&self) -> PathBuf {
+        self.try_root()
+            .unwrap_or_else(|_| PathBuf::from(UNIFIED_MOUNTPOINT))
+    }
+
+    /// Returns the directory the v1 hierarchies are mounted in, failing if there is no cgroup
+    /// mount, or only one at `/`.
+    fn try_root(&self) -> Result<PathBuf> {
         self.mountinfo
             .iter()
             .find_map(|m| {
                 if m.fs_type.0 == "cgroup" {
-                    return Some(m.mount_point.parent().unwrap());
+                    return m.mount_point.parent();
                 }
                 None
             })
-            .unwrap()
-            .to_path_buf()
+            .map(|p| p.to_path_buf())
+            .ok_or_else(|| Error::from_string("no cgroup v1 hierarchy is mounted".to_string()))
     }
 }
 
@@ -206,7 +230,7 @@ impl Hierarchy for V2 {
     }
 
     fn subsystems(&self) -> Vec<Subsystem> {
-        let p = format!("{}/{}", UNIFIED_MOUNTPOINT, "cgroup.controllers");
+        let p = format!("{}/{}", self.root, "cgroup.controllers");
         let ret = fs::read_to_string(p.as_str());
         if ret.is_err() {
             return vec![];
@@ -273,6 +297,12 @@ impl Hierarchy for V2 {
                         true,
                     )));
                 }
+                "misc" => {
+                    subs.push(Subsystem::Misc(MiscController::new(
+                        self.root(),
+                        PathBuf::from(""),
+                    )));
+                }
                 _ => {}
             }
         }
@@ -281,13 +311,11 @@ impl Hierarchy for V2 {
     }
 
     fn root_control_group(&self) -> Cgroup {
-        Cgroup::load(auto(), "")
+        Cgroup::load(Box::new(self.clone()), "")
     }
 
     fn parent_control_group(&self, path: &str) -> Cgroup {
-        let path = Path::new(path);
-        let parent_path = path.parent().unwrap().to_string_lossy().to_string();
-        Cgroup::load(auto(), parent_path)
+        Cgroup::load(Box::new(self.clone()), parent_path(path))
     }
 
     fn root(&self) -> PathBuf {
@@ -299,11 +327,20 @@ impl V1 {
     /// Finds where control groups are mounted to and returns a hierarchy in which control groups
     /// can be created.
     pub fn new() -> V1 {
-        V1 {
-            mountinfo: mountinfo_self(),
-        }
+        V1::with_mountinfo(mountinfo_self())
+    }
+
+    /// Returns a hierarchy made of the control group mounts in `mountinfo` instead of the ones of
+    /// the current process, e.g. to manage control groups mounted in another mount namespace.
+    pub fn with_mountinfo(mountinfo: Vec<Mountinfo>) -> V1 {
+        V1 { mountinfo }
     }
 
+    /// Returns the mount point and mount root of the hierarchy the controller is attached to.
+    ///
+    /// The superblock options list every controller co-mounted in a hierarchy, so e.g. both `cpu`
+    /// and `cpuacct` resolve to the same mount point when they are mounted as `cpu,cpuacct`, and
+    /// to their own mount points otherwise.
     pub fn get_mount_point(&self, controller: Controllers) -> Option<(PathBuf, PathBuf)> {
         self.mountinfo.iter().find_map(|m| {
             if m.fs_type.0 == "cgroup" && m.super_opts.contains(&controller.to_string()) {
@@ -312,6 +349,21 @@ impl V1 {
             None
         })
     }
+
+    /// Returns the mount point of every controller attached to a hierarchy, found in a single
+    /// pass over the mounts, same as calling `get_mount_point()` for each of them.
+    ///
+    /// Co-mounted controllers, e.g. `cpu,cpuacct`, share the same mount point.
+    pub fn mount_map(&self) -> HashMap<Controllers, PathBuf> {
+        let mut map = HashMap::new();
+        for m in self.mountinfo.iter().filter(|m| m.fs_type.0 == "cgroup") {
+            for controller in m.super_opts.iter().filter_map(|o| o.parse().ok()) {
+                map.entry(controller)
+                    .or_insert_with(|| m.mount_point.to_owned());
+            }
+        }
+        map
+    }
 }
 
 impl Default for V1 {
@@ -324,9 +376,34 @@ impl V2 {
     /// Finds where control groups are mounted to and returns a hierarchy in which control groups
     /// can be created.
     pub fn new() -> V2 {
-        V2 {
-            root: String::from(UNIFIED_MOUNTPOINT),
-        }
+        V2::with_root(UNIFIED_MOUNTPOINT)
+    }
+
+    /// Returns a hierarchy rooted at `root` instead of `/sys/fs/cgroup`, e.g. for a cgroup2
+    /// file system mounted elsewhere or a delegated subtree.
+    pub fn with_root(root: impl Into<String>) -> V2 {
+        V2 { root: root.into() }
+    }
+
+    /// Returns a hierarchy rooted at the mount point of the cgroup2 file system of the current
+    /// process, as found in `/proc/self/mountinfo`, or `None` if it isn't mounted.
+    ///
+    /// Unlike `new()`, this doesn't assume the unified hierarchy is mounted at `/sys/fs/cgroup`,
+    /// which containers often don't do.
+    pub fn from_mountinfo() -> Option<V2> {
+        unified_mount_point(&mountinfo_self())
+            .map(|point| V2::with_root(point.to_string_lossy().to_string()))
+    }
+
+    /// Returns the root of the hierarchy for every controller available in it, as listed in its
+    /// `cgroup.controllers`, for parity with `V1::mount_map()`.
+    pub fn mount_map(&self) -> HashMap<Controllers, PathBuf> {
+        fs::read_to_string(self.root().join("cgroup.controllers"))
+            .unwrap_or_default()
+            .split_whitespace()
+            .filter_map(|c| c.parse().ok())
+            .map(|c| (c, self.root()))
+            .collect()
     }
 }
 
@@ -336,6 +413,35 @@ impl Default for V2 {
     }
 }
 
+/// Returns the name of the parent of the control group `path`, the root control group (the empty
+/// name) being its own parent.
+fn parent_path(path: &str) -> String {
+    let path = Path::new(path);
+    path.parent().unwrap_or(path).to_string_lossy().to_string()
+}
+
+/// Returns the mount point of the first `cgroup2` mount in `mountinfo`.
+fn unified_mount_point(mountinfo: &[Mountinfo]) -> Option<&Path> {
+    mountinfo
+        .iter()
+        .find(|m| m.fs_type.0 == "cgroup2")
+        .map(|m| m.mount_point.as_path())
+}
+
+/// Returns the mount point of the `cgroup2` file system that `path` is in, the deepest one if
+/// several are nested.
+pub(crate) fn unified_mount_point_of(path: &Path) -> Option<PathBuf> {
+    mount_point_containing(&mountinfo_self(), path).map(Path::to_path_buf)
+}
+
+fn mount_point_containing<'a>(mountinfo: &'a [Mountinfo], path: &Path) -> Option<&'a Path> {
+    mountinfo
+        .iter()
+        .filter(|m| m.fs_type.0 == "cgroup2" && path.starts_with(&m.mount_point))
+        .map(|m| m.mount_point.as_path())
+        .max_by_key(|point| point.components().count())
+}
+
 pub const UNIFIED_MOUNTPOINT: &str = "/sys/fs/cgroup";
 
 pub fn is_cgroup2_unified_mode() -> bool {
@@ -358,27 +464,97 @@ pub fn auto() -> Box<dyn Hierarchy> {
     }
 }
 
+/// How the control group hierarchies are mounted, see `hierarchy_mode()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HierarchyMode {
+    /// Only v1 hierarchies are mounted.
+    Legacy,
+    /// v1 hierarchies are mounted, and the v2 one next to them, usually at
+    /// `/sys/fs/cgroup/unified`, with the controllers that aren't bound to a v1 hierarchy.
+    Hybrid,
+    /// Only the v2 hierarchy is mounted.
+    Unified,
+}
+
+/// Returns how the control group hierarchies of the current process are mounted, as found in
+/// `/proc/self/mountinfo`.
+///
+/// In hybrid mode `auto()` returns the v1 hierarchy, lacking the controllers only available in
+/// the v2 one, see `auto_for()`.
+pub fn hierarchy_mode() -> HierarchyMode {
+    hierarchy_mode_of(&mountinfo_self())
+}
+
+fn hierarchy_mode_of(mountinfo: &[Mountinfo]) -> HierarchyMode {
+    let v1 = mountinfo.iter().any(|m| m.fs_type.0 == "cgroup");
+    match (v1, unified_mount_point(mountinfo).is_some()) {
+        (true, true) => HierarchyMode::Hybrid,
+        (false, true) => HierarchyMode::Unified,
+        _ => HierarchyMode::Legacy,
+    }
+}
+
+/// Same as `auto()`, but in hybrid mode returns the v2 hierarchy if `controller` is only
+/// available there, e.g. the misc controller which has no v1 version.
+pub fn auto_for(controller: Controllers) -> Box<dyn Hierarchy> {
+    let mountinfo = mountinfo_self();
+    if hierarchy_mode_of(&mountinfo) != HierarchyMode::Hybrid {
+        return auto();
+    }
+
+    let v2 = unified_mount_point(&mountinfo)
+        .map(|point| V2::with_root(point.to_string_lossy().to_string()));
+    let v1 = V1::with_mountinfo(mountinfo);
+    match v2 {
+        Some(v2)
+            if v1.get_mount_point(controller.clone()).is_none()
+                && v2.mount_map().contains_key(&controller) =>
+        {
+            Box::new(v2)
+        }
+        _ => Box::new(v1),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parent_path() {
+        assert_eq!(parent_path(""), "");
+        assert_eq!(parent_path("foo"), "");
+        assert_eq!(parent_path("a/b/c"), "a/b");
+    }
+
+    #[test]
+    fn test_v1_try_root() {
+        let v1 = V1::with_mountinfo(vec![]);
+        assert!(v1.try_root().is_err());
+        assert_eq!(v1.root(), PathBuf::from(UNIFIED_MOUNTPOINT));
+
+        let line = "29 26 0:26 / /sys/fs/cgroup/pids rw,nosuid,nodev,noexec,relatime shared:10 - cgroup cgroup rw,pids";
+        let v1 = V1::with_mountinfo(vec![parse_mountinfo_for_line(line).unwrap()]);
+        assert_eq!(v1.try_root().unwrap(), PathBuf::from("/sys/fs/cgroup"));
+    }
+
     #[test]
     fn test_parse_mount() {
         let mountinfo = vec![
             ("29 26 0:26 / /sys/fs/cgroup/cpuset,cpu,cpuacct rw,nosuid,nodev,noexec,relatime shared:10 - cgroup cgroup rw,cpuset,cpu,cpuacct",
-             Mountinfo{mount_root: PathBuf::from("/"), mount_point: PathBuf::from("/sys/fs/cgroup/cpuset,cpu,cpuacct"), fs_type: ("cgroup".to_string(), None), super_opts: vec![
+             Mountinfo{mount_id: 29, parent_id: 26, st_dev: (0, 26), mount_root: PathBuf::from("/"), mount_point: PathBuf::from("/sys/fs/cgroup/cpuset,cpu,cpuacct"), fs_type: ("cgroup".to_string(), None), super_opts: vec![
                 "rw".to_string(),
                 "cpuset".to_string(),
                 "cpu".to_string(),
                 "cpuacct".to_string(),
              ]}),
             ("121 1731 0:42 / /shm rw,nosuid,nodev,noexec,relatime shared:68 master:66 - tmpfs shm rw,size=65536k",
-             Mountinfo{mount_root: PathBuf::from("/"), mount_point: PathBuf::from("/shm"), fs_type: ("tmpfs".to_string(), None), super_opts: vec![
+             Mountinfo{mount_id: 121, parent_id: 1731, st_dev: (0, 42), mount_root: PathBuf::from("/"), mount_point: PathBuf::from("/shm"), fs_type: ("tmpfs".to_string(), None), super_opts: vec![
                 "rw".to_string(),
                 "size=65536k".to_string(),
              ]}),
             ("121 1731 0:42 / /shm rw,nosuid,nodev,noexec,relatime shared:68 master:66 - tmpfs.123 shm rw,size=65536k",
-             Mountinfo{mount_root: PathBuf::from("/"), mount_point: PathBuf::from("/shm"), fs_type: ("tmpfs".to_string(), Some("123".to_string())), super_opts: vec![
+             Mountinfo{mount_id: 121, parent_id: 1731, st_dev: (0, 42), mount_root: PathBuf::from("/"), mount_point: PathBuf::from("/shm"), fs_type: ("tmpfs".to_string(), Some("123".to_string())), super_opts: vec![
                 "rw".to_string(),
                 "size=65536k".to_string(),
              ]}),
@@ -388,5 +564,160 @@ mod tests {
             let info = parse_mountinfo_for_line(mi.0).unwrap();
             assert_eq!(info, mi.1)
         }
+
+        // the IDs and the device number must be numbers
+        assert!(parse_mountinfo_for_line(
+            "x 26 0:26 / /sys/fs/cgroup rw,relatime - cgroup2 cgroup2 rw"
+        )
+        .is_none());
+        assert!(parse_mountinfo_for_line(
+            "29 26 026 / /sys/fs/cgroup rw,relatime - cgroup2 cgroup2 rw"
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_get_mount_point() {
+        let parse = |lines: &[&str]| V1 {
+            mountinfo: lines
+                .iter()
+                .map(|l| parse_mountinfo_for_line(l).unwrap())
+                .collect(),
+        };
+
+        let combined = parse(&[
+            "29 26 0:26 / /sys/fs/cgroup/cpuset rw,nosuid,nodev,noexec,relatime shared:10 - cgroup cgroup rw,cpuset",
+            "30 26 0:27 / /sys/fs/cgroup/cpu,cpuacct rw,nosuid,nodev,noexec,relatime shared:11 - cgroup cgroup rw,cpu,cpuacct",
+        ]);
+        for controller in [Controllers::Cpu, Controllers::CpuAcct] {
+            assert_eq!(
+                combined.get_mount_point(controller),
+                Some((
+                    PathBuf::from("/sys/fs/cgroup/cpu,cpuacct"),
+                    PathBuf::from("/")
+                ))
+            );
+        }
+        assert_eq!(
+            combined.get_mount_point(Controllers::CpuSet),
+            Some((PathBuf::from("/sys/fs/cgroup/cpuset"), PathBuf::from("/")))
+        );
+
+        let split = parse(&[
+            "33 32 0:29 / /sys/fs/cgroup/cpu rw,relatime - cgroup cgroup rw,cpu",
+            "34 32 0:30 / /sys/fs/cgroup/cpuacct rw,relatime - cgroup cgroup rw,cpuacct",
+        ]);
+        assert_eq!(
+            split.get_mount_point(Controllers::Cpu),
+            Some((PathBuf::from("/sys/fs/cgroup/cpu"), PathBuf::from("/")))
+        );
+        assert_eq!(
+            split.get_mount_point(Controllers::CpuAcct),
+            Some((PathBuf::from("/sys/fs/cgroup/cpuacct"), PathBuf::from("/")))
+        );
+        assert_eq!(split.get_mount_point(Controllers::CpuSet), None);
+    }
+
+    #[test]
+    fn test_mount_map() {
+        let v1 = V1::with_mountinfo(
+            [
+                "29 26 0:26 / /sys/fs/cgroup/cpuset rw,nosuid,nodev,noexec,relatime shared:10 - cgroup cgroup rw,cpuset",
+                "30 26 0:27 / /sys/fs/cgroup/cpu,cpuacct rw,nosuid,nodev,noexec,relatime shared:11 - cgroup cgroup rw,cpu,cpuacct",
+                "31 26 0:28 / /sys/fs/cgroup/systemd rw,nosuid,nodev,noexec,relatime shared:12 - cgroup cgroup rw,xattr,name=systemd",
+            ]
+            .iter()
+            .map(|l| parse_mountinfo_for_line(l).unwrap())
+            .collect(),
+        );
+
+        let map = v1.mount_map();
+        assert_eq!(map.len(), 4);
+        assert_eq!(
+            map[&Controllers::Cpu],
+            PathBuf::from("/sys/fs/cgroup/cpu,cpuacct")
+        );
+        assert_eq!(map[&Controllers::CpuAcct], map[&Controllers::Cpu]);
+        assert_eq!(
+            map[&Controllers::CpuSet],
+            PathBuf::from("/sys/fs/cgroup/cpuset")
+        );
+        assert_eq!(
+            map[&Controllers::Systemd],
+            PathBuf::from("/sys/fs/cgroup/systemd")
+        );
+        for (controller, point) in &map {
+            assert_eq!(v1.get_mount_point(controller.clone()).unwrap().0, *point);
+        }
+    }
+
+    #[test]
+    fn test_unified_mount_point() {
+        let mountinfo: Vec<_> = [
+            "29 26 0:26 / /sys/fs/cgroup/cpuset rw,nosuid,nodev,noexec,relatime shared:10 - cgroup cgroup rw,cpuset",
+            "35 24 0:30 / /run/container/cgroup rw,nosuid,nodev,noexec,relatime shared:9 - cgroup2 cgroup2 rw,nsdelegate,memory_recursiveprot",
+        ]
+        .iter()
+        .map(|l| parse_mountinfo_for_line(l).unwrap())
+        .collect();
+
+        assert_eq!(mountinfo[1].fs_type, ("cgroup2".to_string(), None));
+        assert_eq!(
+            unified_mount_point(&mountinfo),
+            Some(Path::new("/run/container/cgroup"))
+        );
+        assert_eq!(unified_mount_point(&mountinfo[..1]), None);
+
+        assert_eq!(
+            mount_point_containing(&mountinfo, Path::new("/run/container/cgroup/a/b")),
+            Some(Path::new("/run/container/cgroup"))
+        );
+        assert_eq!(
+            mount_point_containing(&mountinfo, Path::new("/sys/fs/cgroup/cpuset/a")),
+            None
+        );
+
+        // The v1 hierarchy ignores the cgroup2 mount.
+        let v1 = V1::with_mountinfo(mountinfo);
+        assert_eq!(v1.root(), PathBuf::from("/sys/fs/cgroup"));
+        assert_eq!(v1.mount_map().len(), 1);
+    }
+
+    #[test]
+    fn test_hierarchy_mode() {
+        let mountinfo: Vec<_> = [
+            "24 21 0:22 / /sys/fs/cgroup ro,nosuid,nodev,noexec shared:9 - tmpfs tmpfs ro,mode=755",
+            "25 24 0:23 / /sys/fs/cgroup/unified rw,nosuid,nodev,noexec,relatime shared:10 - cgroup2 cgroup2 rw,nsdelegate",
+            "26 24 0:24 / /sys/fs/cgroup/systemd rw,nosuid,nodev,noexec,relatime shared:11 - cgroup cgroup rw,xattr,name=systemd",
+            "29 24 0:27 / /sys/fs/cgroup/memory rw,nosuid,nodev,noexec,relatime shared:14 - cgroup cgroup rw,memory",
+        ]
+        .iter()
+        .filter_map(|l| parse_mountinfo_for_line(l))
+        .filter(|m| m.fs_type.0 == "cgroup" || m.fs_type.0 == "cgroup2")
+        .collect();
+
+        assert_eq!(hierarchy_mode_of(&mountinfo), HierarchyMode::Hybrid);
+        assert_eq!(hierarchy_mode_of(&mountinfo[1..]), HierarchyMode::Legacy);
+        assert_eq!(hierarchy_mode_of(&mountinfo[..1]), HierarchyMode::Unified);
+        assert_eq!(hierarchy_mode_of(&[]), HierarchyMode::Legacy);
+        assert_eq!(
+            unified_mount_point(&mountinfo),
+            Some(Path::new("/sys/fs/cgroup/unified"))
+        );
+    }
+
+    #[test]
+    fn test_v1_without_cgroup_mounts() {
+        let v1 = V1::with_mountinfo(vec![]);
+        assert_eq!(v1.root(), PathBuf::from(UNIFIED_MOUNTPOINT));
+        assert!(v1.subsystems().is_empty());
+
+        let root = v1.root_control_group();
+        assert!(root.is_root());
+        assert!(root.procs().is_empty());
+        assert!(v1.parent_control_group("").is_root());
+
+        let err = Cgroup::new(Box::new(v1), "test_v1_without_cgroup_mounts").unwrap_err();
+        assert_eq!(err.kind(), &crate::fs::error::ErrorKind::SubsystemsEmpty);
     }
 }
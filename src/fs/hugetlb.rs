@@ -9,6 +9,7 @@
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/hugetlb.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/hugetlb.txt)
 use log::warn;
+use std::convert::TryFrom;
 use std::io::Write;
 use std::path::PathBuf;
 
@@ -17,7 +18,8 @@ use crate::fs::error::*;
 use crate::fs::{flat_keyed_to_vec, read_u64_from};
 
 use crate::fs::{
-    ControllIdentifier, ControllerInternal, Controllers, HugePageResources, Resources, Subsystem,
+    ControllIdentifier, ControllerInternal, Controllers, HugePageResource, HugePageResources,
+    MaxValue, Resources, Subsystem,
 };
 
 /// A controller that allows controlling the `hugetlb` subsystem of a Cgroup.
@@ -32,10 +34,32 @@ pub struct HugeTlbController {
     v2: bool,
 }
 
+// The control files used by the controller on cgroup v1, see `Controller::managed_files()`.
+const MANAGED_FILES_V1: &[&str] = &[
+    "hugetlb.<size>.limit_in_bytes",
+    "hugetlb.<size>.usage_in_bytes",
+    "hugetlb.<size>.max_usage_in_bytes",
+    "hugetlb.<size>.failcnt",
+];
+
+// The control files used by the controller on cgroup v2.
+const MANAGED_FILES_V2: &[&str] = &[
+    "hugetlb.<size>.max",
+    "hugetlb.<size>.current",
+    "hugetlb.<size>.events",
+];
+
 impl ControllerInternal for HugeTlbController {
     fn control_type(&self) -> Controllers {
         Controllers::HugeTlb
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        if self.is_v2() {
+            MANAGED_FILES_V2
+        } else {
+            MANAGED_FILES_V1
+        }
+    }
     fn get_path(&self) -> &PathBuf {
         &self.path
     }
@@ -63,6 +87,43 @@ impl ControllerInternal for HugeTlbController {
 
         Ok(())
     }
+
+    fn reset(&self) -> Result<()> {
+        for size in &self.sizes {
+            let (file_name, unlimited) = if self.v2 {
+                (format!("hugetlb.{}.max", size), "max")
+            } else {
+                (format!("hugetlb.{}.limit_in_bytes", size), "-1")
+            };
+            self.open_path(&file_name, true).and_then(|mut file| {
+                file.write_all(unlimited.as_ref()).map_err(|e| {
+                    Error::with_cause(WriteFailed(file_name.clone(), unlimited.to_string()), e)
+                })
+            })?;
+        }
+        Ok(())
+    }
+
+    fn read_resources(&self, res: &mut Resources) -> Result<()> {
+        for size in &self.sizes {
+            let limit = if self.v2 {
+                // an unlimited size is left out, as it can't be expressed in bytes
+                match self.max_value_if_exists(&format!("hugetlb.{}.max", size))? {
+                    Some(MaxValue::Value(limit)) => Some(limit as u64),
+                    _ => None,
+                }
+            } else {
+                self.read_if_exists(&format!("hugetlb.{}.limit_in_bytes", size))?
+            };
+            if let Some(limit) = limit {
+                res.hugepages.limits.push(HugePageResource {
+                    size: size.clone(),
+                    limit,
+                });
+            }
+        }
+        Ok(())
+    }
 }
 
 impl ControllIdentifier for HugeTlbController {
@@ -71,17 +132,15 @@ impl ControllIdentifier for HugeTlbController {
     }
 }
 
-impl<'a> From<&'a Subsystem> for &'a HugeTlbController {
-    fn from(sub: &'a Subsystem) -> &'a HugeTlbController {
-        unsafe {
-            match sub {
-                Subsystem::HugeTlb(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a HugeTlbController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a HugeTlbController> {
+        match sub {
+            Subsystem::HugeTlb(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                HugeTlbController::controller_type().to_string(),
+            ))),
         }
     }
 }
@@ -112,6 +171,36 @@ impl HugeTlbController {
         self.sizes.clone()
     }
 
+    /// Returns the hugepage sizes the control group has a limit file for, from the smallest to
+    /// the largest, e.g. `2MB` for `hugetlb.2MB.max` on v2 or `hugetlb.2MB.limit_in_bytes` on
+    /// v1.
+    ///
+    /// Unlike `get_sizes()`, which lists the sizes of the host, this scans the directory of the
+    /// control group, which only has the files of the sizes the kernel accounts.
+    pub fn sizes(&self) -> Result<Vec<String>> {
+        let suffix = if self.v2 { ".max" } else { ".limit_in_bytes" };
+        let entries = fs::read_dir(&self.path)
+            .map_err(|e| Error::with_cause(ReadFailed(self.path.display().to_string()), e))?;
+
+        let mut sizes = vec![];
+        for entry in entries {
+            let entry = entry
+                .map_err(|e| Error::with_cause(ReadFailed(self.path.display().to_string()), e))?;
+            let name = entry.file_name();
+            let size = name
+                .to_str()
+                .and_then(|n| n.strip_prefix("hugetlb."))
+                .and_then(|n| n.strip_suffix(suffix));
+            // the reservation files, e.g. `hugetlb.2MB.rsvd.max`, are left out
+            if let Some(size) = size.filter(|s| !s.contains('.')) {
+                sizes.push(size.to_string());
+            }
+        }
+        sizes.sort_by_key(|s| parse_size(&s.to_lowercase(), &get_binary_size_map()).ok());
+
+        Ok(sizes)
+    }
+
     fn failcnt_v2(&self, hugetlb_size: &str) -> Result<u64> {
         self.open_path(&format!("hugetlb.{}.events", hugetlb_size), false)
             .and_then(flat_keyed_to_vec)
@@ -137,12 +226,19 @@ impl HugeTlbController {
 
     /// Get the limit (in bytes) of how much memory can be backed by hugepages of a certain size
     /// (`hugetlb_size`).
+    ///
+    /// On v2 an unlimited size (`max`) is returned as `u64::MAX`.
     pub fn limit_in_bytes(&self, hugetlb_size: &str) -> Result<u64> {
-        let mut file_name = format!("hugetlb.{}.limit_in_bytes", hugetlb_size);
         if self.v2 {
-            file_name = format!("hugetlb.{}.max", hugetlb_size);
+            return self
+                .get_max_value(&format!("hugetlb.{}.max", hugetlb_size))
+                .map(|max| match max {
+                    MaxValue::Value(limit) => limit as u64,
+                    MaxValue::Max => u64::MAX,
+                });
         }
-        self.open_path(&file_name, false).and_then(read_u64_from)
+        self.open_path(&format!("hugetlb.{}.limit_in_bytes", hugetlb_size), false)
+            .and_then(read_u64_from)
     }
 
     /// Get the current usage of memory that is backed by hugepages of a certain size
@@ -156,8 +252,14 @@ impl HugeTlbController {
     }
 
     /// Get the maximum observed usage of memory that is backed by hugepages of a certain size
-    /// (`hugetlb_size`).
+    /// (`hugetlb_size`). Only available on cgroup v1.
     pub fn max_usage_in_bytes(&self, hugetlb_size: &str) -> Result<u64> {
+        if self.v2 {
+            return Err(Error::not_supported(
+                &format!("hugetlb.{}.max_usage_in_bytes", hugetlb_size),
+                "only available on cgroup v1",
+            ));
+        }
         self.open_path(
             &format!("hugetlb.{}.max_usage_in_bytes", hugetlb_size),
             false,
@@ -172,11 +274,7 @@ impl HugeTlbController {
         if self.v2 {
             file_name = format!("hugetlb.{}.max", hugetlb_size);
         }
-        self.open_path(&file_name, true).and_then(|mut file| {
-            file.write_all(limit.to_string().as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed(file_name.to_string(), limit.to_string()), e)
-            })
-        })
+        self.write_file(&file_name, &limit.to_string())
     }
 }
 
@@ -194,21 +292,50 @@ fn get_hugepage_sizes() -> Vec<String> {
         .filter_map(|e| {
             let entry = e.map_err(|e| warn!("readdir error: {:?}", e)).ok()?;
             let name = entry.file_name().into_string().unwrap();
-            let parts: Vec<&str> = name.split('-').collect();
-            if parts.len() != 2 {
-                return None;
-            }
-            let bmap = get_binary_size_map();
-            let size = parse_size(parts[1], &bmap)
-                .map_err(|e| warn!("parse_size error: {:?}", e))
-                .ok()?;
-            let dabbrs = get_decimal_abbrs();
-
-            Some(custom_size(size as f64, 1024.0, &dabbrs))
+            hugepage_dir_size(&name).map(format_page_size)
         })
         .collect()
 }
 
+/// Returns the hugepage sizes supported by the system, from the smallest to the largest, as
+/// found in `/sys/kernel/mm/hugepages`.
+///
+/// The sizes are formatted the way the `hugetlb` control group files name them, e.g. `2MB` and
+/// `1GB` for the `hugepages-2048kB` and `hugepages-1048576kB` directories, or `64KB` and `16GB`
+/// on some architectures. This is the format `HugePageResource::size` expects.
+pub fn supported_page_sizes() -> Result<Vec<String>> {
+    let entries = fs::read_dir(HUGEPAGESIZE_DIR)
+        .map_err(|e| Error::with_cause(ReadFailed(HUGEPAGESIZE_DIR.to_string()), e))?;
+
+    let mut sizes = vec![];
+    for entry in entries {
+        let entry =
+            entry.map_err(|e| Error::with_cause(ReadFailed(HUGEPAGESIZE_DIR.to_string()), e))?;
+        if let Some(size) = entry.file_name().to_str().and_then(hugepage_dir_size) {
+            sizes.push(size);
+        }
+    }
+    sizes.sort_unstable();
+
+    Ok(sizes.into_iter().map(format_page_size).collect())
+}
+
+// Parses the size of the hugepages of a `hugepages-<size>kB` directory.
+fn hugepage_dir_size(name: &str) -> Option<u128> {
+    let parts: Vec<&str> = name.split('-').collect();
+    if parts.len() != 2 {
+        return None;
+    }
+    let bmap = get_binary_size_map();
+    parse_size(parts[1], &bmap)
+        .map_err(|e| warn!("parse_size error: {:?}", e))
+        .ok()
+}
+
+fn format_page_size(size: u128) -> String {
+    custom_size(size as f64, 1024.0, &get_decimal_abbrs())
+}
+
 pub const KB: u128 = 1000;
 pub const MB: u128 = 1000 * KB;
 pub const GB: u128 = 1000 * MB;
@@ -318,6 +445,18 @@ fn custom_size(mut size: f64, base: f64, m: &[String]) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_hugepage_dir_size() {
+        let size = |name| hugepage_dir_size(name).map(format_page_size);
+        assert_eq!(size("hugepages-2048kB"), Some("2MB".to_string()));
+        assert_eq!(size("hugepages-1048576kB"), Some("1GB".to_string()));
+        assert_eq!(size("hugepages-64kB"), Some("64KB".to_string()));
+        assert_eq!(size("hugepages-16384kB"), Some("16MB".to_string()));
+        assert_eq!(size("hugepages-524288kB"), Some("512MB".to_string()));
+        assert_eq!(size("hugepages-16777216kB"), Some("16GB".to_string()));
+        assert_eq!(size("hugepages"), None);
+    }
+
     #[test]
     fn test_binary_size_valid() {
         let m = get_binary_size_map();
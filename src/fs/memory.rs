@@ -10,20 +10,22 @@
 //!  [Documentation/cgroup-v1/memory.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/memory.txt)
 use log::warn;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::io::Write;
 use std::path::PathBuf;
 use std::sync::mpsc::Receiver;
+use std::time::Duration;
 
 use crate::fs::error::ErrorKind::*;
 use crate::fs::error::*;
 use crate::fs::events;
-use crate::fs::{read_i64_from, read_string_from, read_u64_from};
+use crate::fs::{parse_pressure, read_i64_from, read_string_from, read_u64_from};
 
 use crate::fs::flat_keyed_to_hashmap;
 
 use crate::fs::{
     ControllIdentifier, ControllerInternal, Controllers, CustomizedAttribute, MaxValue,
-    MemoryResources, Resources, Subsystem,
+    MemoryResources, Pressure, Resources, Subsystem,
 };
 
 /// A controller that allows controlling the `memory` subsystem of a Cgroup.
@@ -127,6 +129,29 @@ pub struct NumaStat {
     pub hierarchical_unevictable_pages_per_node: Vec<u64>,
 }
 
+fn limit_from_max_value(v: MaxValue) -> Option<u64> {
+    match v {
+        MaxValue::Max => None,
+        MaxValue::Value(v) if v < 0 => None,
+        MaxValue::Value(v) => Some(v as u64),
+    }
+}
+
+// cgroup v1 reports an unlimited limit as the largest page-aligned value
+// fitting in an i64 (PAGE_COUNTER_MAX * PAGE_SIZE).
+fn limit_from_v1(v: u64) -> Option<u64> {
+    let page_size = match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        n if n > 0 => n as u64,
+        _ => 4096,
+    };
+    let unlimited = (i64::MAX as u64 / page_size) * page_size;
+    if v >= unlimited {
+        None
+    } else {
+        Some(v)
+    }
+}
+
 #[allow(clippy::unnecessary_wraps)]
 fn parse_numa_stat(s: String) -> Result<NumaStat> {
     // Parse the number of nodes
@@ -289,6 +314,10 @@ fn parse_numa_stat(s: String) -> Result<NumaStat> {
     })
 }
 
+/// The statistics found in `memory.stat`.
+///
+/// The typed fields are the ones of cgroup v1 and are left to 0 on v2, whose counters differ,
+/// e.g. `anon` and `file` instead of `rss` and `cache`.
 #[derive(Default, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemoryStat {
@@ -328,9 +357,138 @@ pub struct MemoryStat {
     pub total_inactive_file: u64,
     pub total_active_file: u64,
     pub total_unevictable: u64,
+    /// Every counter of the file by name, on both versions, including the ones without a
+    /// typed field, e.g. `workingset_refault_file` or `kernel_stack`. Lines that aren't a
+    /// name and a number are skipped.
     pub raw: HashMap<String, u64>,
 }
 
+/// The kernel memory charged to the control group, broken down by use, as found in `memory.stat`
+/// on cgroup v2. All the values are in bytes.
+///
+/// A field is `None` if the running kernel doesn't report it.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KernelMemoryStat {
+    /// Memory used for in-kernel data structures (slab allocations).
+    pub slab: Option<u64>,
+    /// Part of `slab` that might be reclaimed, such as dentries and inodes.
+    pub slab_reclaimable: Option<u64>,
+    /// Part of `slab` that cannot be reclaimed under memory pressure.
+    pub slab_unreclaimable: Option<u64>,
+    /// Memory used in network transmission buffers.
+    pub sock: Option<u64>,
+    /// Memory allocated to kernel stacks.
+    pub kernel_stack: Option<u64>,
+    /// Memory used for storing per-cpu kernel data structures.
+    pub percpu: Option<u64>,
+    /// Memory allocated for page tables.
+    pub pagetables: Option<u64>,
+}
+
+fn kernel_memory_stat(raw: &HashMap<String, u64>) -> KernelMemoryStat {
+    let get = |key: &str| raw.get(key).copied();
+    KernelMemoryStat {
+        slab: get("slab"),
+        slab_reclaimable: get("slab_reclaimable"),
+        slab_unreclaimable: get("slab_unreclaimable"),
+        sock: get("sock"),
+        kernel_stack: get("kernel_stack"),
+        percpu: get("percpu"),
+        pagetables: get("pagetables"),
+    }
+}
+
+/// The memory of the control group and its descendants broken down by kind, from `memory.stat`,
+/// with the same meaning on both cgroup versions. All the values are in bytes.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryBreakdown {
+    /// Anonymous memory, which can only be reclaimed by swapping it out (`anon` on v2,
+    /// `total_rss` on v1).
+    pub anon: u64,
+    /// Page cache, shared memory included, which is mostly reclaimable (`file` on v2,
+    /// `total_cache` on v1).
+    pub file: u64,
+    /// Shared memory, part of `file` but only reclaimable by swapping it out (`shmem` on v2,
+    /// `total_shmem` on v1).
+    pub shmem: u64,
+}
+
+fn memory_breakdown(raw: &HashMap<String, u64>, v2: bool) -> MemoryBreakdown {
+    let get = |key: &str| raw.get(key).copied().unwrap_or(0);
+    if v2 {
+        return MemoryBreakdown {
+            anon: get("anon"),
+            file: get("file"),
+            shmem: get("shmem"),
+        };
+    }
+
+    // The `total_` keys account the descendants as well, like the keys of v2 do.
+    MemoryBreakdown {
+        anon: get("total_rss"),
+        file: get("total_cache"),
+        shmem: get("total_shmem"),
+    }
+}
+
+/// The number of memory events that occurred in the control group and its descendants, as found
+/// in `memory.events` on cgroup v2, see `MemController::memory_events()` for cgroup v1.
+///
+/// Events the running kernel doesn't report are counted as 0.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryEvents {
+    /// Times the memory usage went below `memory.low` while reclaiming despite the protection.
+    pub low: u64,
+    /// Times the memory usage went over `memory.high` and the tasks got throttled.
+    pub high: u64,
+    /// Times the memory usage was about to go over `memory.max`.
+    pub max: u64,
+    /// Times the memory usage hit `memory.max` and allocations failed, invoking the OOM killer.
+    pub oom: u64,
+    /// Number of tasks killed by the OOM killer.
+    pub oom_kill: u64,
+    /// Times the whole control group was killed by the OOM killer, because of `memory.oom.group`.
+    pub oom_group_kill: u64,
+}
+
+/// Counts of the swap events of the control group (cgroup v2 only), found in
+/// `memory.swap.events`.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SwapEvents {
+    /// Times the swap usage went over `memory.swap.high` and the tasks got throttled.
+    pub high: u64,
+    /// Times the swap usage was about to go over `memory.swap.max`.
+    pub max: u64,
+    /// Times a swap allocation failed, because the system ran out of swap or `memory.swap.max`
+    /// was hit.
+    pub fail: u64,
+}
+
+fn swap_events(raw: &HashMap<String, i64>) -> SwapEvents {
+    let get = |key: &str| raw.get(key).map_or(0, |v| *v as u64);
+    SwapEvents {
+        high: get("high"),
+        max: get("max"),
+        fail: get("fail"),
+    }
+}
+
+fn memory_events(raw: &HashMap<String, i64>) -> MemoryEvents {
+    let get = |key: &str| raw.get(key).map_or(0, |v| *v as u64);
+    MemoryEvents {
+        low: get("low"),
+        high: get("high"),
+        max: get("max"),
+        oom: get("oom"),
+        oom_kill: get("oom_kill"),
+        oom_group_kill: get("oom_group_kill"),
+    }
+}
+
 #[allow(clippy::unnecessary_wraps)]
 fn parse_memory_stat(s: String) -> Result<MemoryStat> {
     let mut raw = HashMap::new();
@@ -485,10 +643,80 @@ pub struct Kmem {
     pub slabinfo: String,
 }
 
+/// Current memory usage of the control group set against its configured limits.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemUtilization {
+    /// The current usage of memory by the control group's tasks, in bytes.
+    pub current: u64,
+    /// The throttling limit (`memory.high`) in bytes, `None` if unlimited. Always `None` on
+    /// cgroup v1, which has no equivalent.
+    pub high: Option<u64>,
+    /// The hard limit in bytes, `None` if unlimited.
+    pub max: Option<u64>,
+    /// `current` as a percentage of `max`, `None` if `max` is unlimited.
+    pub percent_of_max: Option<f64>,
+}
+
+// The control files used by the controller on cgroup v1, see `Controller::managed_files()`.
+const MANAGED_FILES_V1: &[&str] = &[
+    "memory.limit_in_bytes",
+    "memory.soft_limit_in_bytes",
+    "memory.memsw.limit_in_bytes",
+    "memory.kmem.limit_in_bytes",
+    "memory.kmem.tcp.limit_in_bytes",
+    "memory.swappiness",
+    "memory.oom_control",
+    "memory.move_charge_at_immigrate",
+    "memory.use_hierarchy",
+    "memory.usage_in_bytes",
+    "memory.max_usage_in_bytes",
+    "memory.failcnt",
+    "memory.memsw.usage_in_bytes",
+    "memory.memsw.max_usage_in_bytes",
+    "memory.memsw.failcnt",
+    "memory.kmem.usage_in_bytes",
+    "memory.kmem.max_usage_in_bytes",
+    "memory.kmem.failcnt",
+    "memory.kmem.slabinfo",
+    "memory.kmem.tcp.usage_in_bytes",
+    "memory.kmem.tcp.max_usage_in_bytes",
+    "memory.kmem.tcp.failcnt",
+    "memory.stat",
+    "memory.numa_stat",
+];
+
+// The control files used by the controller on cgroup v2.
+const MANAGED_FILES_V2: &[&str] = &[
+    "memory.max",
+    "memory.high",
+    "memory.low",
+    "memory.min",
+    "memory.swap.max",
+    "memory.swap.high",
+    "memory.oom.group",
+    "memory.current",
+    "memory.peak",
+    "memory.swap.current",
+    "memory.swap.peak",
+    "memory.events",
+    "memory.swap.events",
+    "memory.stat",
+    "memory.numa_stat",
+    "memory.pressure",
+];
+
 impl ControllerInternal for MemController {
     fn control_type(&self) -> Controllers {
         Controllers::Mem
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        if self.is_v2() {
+            MANAGED_FILES_V2
+        } else {
+            MANAGED_FILES_V1
+        }
+    }
     fn get_path(&self) -> &PathBuf {
         &self.path
     }
@@ -510,9 +738,28 @@ impl ControllerInternal for MemController {
         update!(self, set_limit, memres.memory_hard_limit);
         update!(self, set_soft_limit, memres.memory_soft_limit);
         update!(self, set_kmem_limit, memres.kernel_memory_limit);
-        update!(self, set_memswap_limit, memres.memory_swap_limit);
+        if self.v2 {
+            // `memory.swap.max` only limits the swap, see `MemoryResources::memory_swap_limit`
+            if let Some(memswap) = memres.memory_swap_limit {
+                let memory = match memres.memory_hard_limit {
+                    Some(memory) => Ok(memory),
+                    None => self.get_max_value("memory.max").map(|v| v.to_i64()),
+                };
+                if let Ok(Some(swap)) = memory.map(|memory| memswap_to_swap(memswap, memory)) {
+                    let _ = self.set_memswap_limit(swap);
+                }
+            }
+            update!(self, set_swap_max, memres.swap_max.map(|v| v.to_i64()));
+            update!(self, set_mem_high, memres.memory_high);
+        } else {
+            update!(self, set_memswap_limit, memres.memory_swap_limit);
+        }
         update!(self, set_tcp_limit, memres.kernel_tcp_memory_limit);
-        update!(self, set_swappiness, memres.swappiness);
+        // only cgroup v1 has a per-cgroup swappiness
+        if !self.v2 {
+            let swappiness = memres.swappiness.and_then(|v| u8::try_from(v).ok());
+            update!(self, set_swappiness, swappiness);
+        }
 
         memres.attrs.iter().for_each(|(k, v)| {
             let _ = self.set(k, v);
@@ -520,6 +767,53 @@ impl ControllerInternal for MemController {
 
         Ok(())
     }
+
+    fn reset(&self) -> Result<()> {
+        // the swap limit can't be lower than the memory limit on v1, so it's raised first
+        if self.get_path().join("memory.memsw.limit_in_bytes").exists()
+            || self.get_path().join("memory.swap.max").exists()
+        {
+            self.set_memswap_limit(-1)?;
+        }
+
+        if self.v2 {
+            return self.set_mem(SetMemory {
+                low: Some(MaxValue::Value(0)),
+                high: Some(MaxValue::Max),
+                min: Some(MaxValue::Value(0)),
+                max: Some(MaxValue::Max),
+            });
+        }
+
+        self.set_limit(-1)?;
+        self.set_soft_limit(-1)
+    }
+
+    fn read_resources(&self, res: &mut Resources) -> Result<()> {
+        let memres = &mut res.memory;
+        if self.v2 {
+            let max = |f: &str| -> Result<Option<i64>> {
+                Ok(self.max_value_if_exists(f)?.map(|v| v.to_i64()))
+            };
+            memres.memory_hard_limit = max("memory.max")?;
+            memres.memory_soft_limit = max("memory.low")?;
+            memres.swap_max = self.max_value_if_exists("memory.swap.max")?;
+            memres.memory_high = self.max_value_if_exists("memory.high")?;
+            if let (Some(swap), Some(memory)) = (max("memory.swap.max")?, memres.memory_hard_limit)
+            {
+                memres.memory_swap_limit = Some(swap_to_memswap(swap, memory));
+            }
+            return Ok(());
+        }
+
+        memres.memory_hard_limit = self.read_if_exists("memory.limit_in_bytes")?;
+        memres.memory_soft_limit = self.read_if_exists("memory.soft_limit_in_bytes")?;
+        memres.memory_swap_limit = self.read_if_exists("memory.memsw.limit_in_bytes")?;
+        memres.kernel_memory_limit = self.read_if_exists("memory.kmem.limit_in_bytes")?;
+        memres.kernel_tcp_memory_limit = self.read_if_exists("memory.kmem.tcp.limit_in_bytes")?;
+        memres.swappiness = self.read_if_exists("memory.swappiness")?;
+        Ok(())
+    }
 }
 
 impl MemController {
@@ -545,16 +839,101 @@ impl MemController {
             let f = value.1;
             if let Some(v) = v {
                 let v = v.to_string();
-                self.open_path(f, true).and_then(|mut file| {
-                    file.write_all(v.as_ref()).map_err(|e| {
-                        Error::with_cause(WriteFailed(f.to_string(), format!("{:?}", v)), e)
-                    })
-                })?;
+                self.write_file(f, &v)?;
             }
         }
         Ok(())
     }
 
+    /// Sets the throttling (`memory.high`) and hard (`memory.max`) memory limits together, in
+    /// bytes, -1 meaning unlimited.
+    ///
+    /// When the hard limit is lowered, `memory.high` is set first so that the kernel reclaims
+    /// memory gracefully instead of invoking the OOM killer, otherwise `memory.max` is raised
+    /// first. `high` must not be above `max`, or above the current hard limit if `max` isn't
+    /// given.
+    ///
+    /// On cgroup v1 `max` sets the hard limit and `high` is not supported.
+    ///
+    /// Returns the limits in effect afterwards, as rounded by the kernel.
+    pub fn set_limits(&self, high: Option<i64>, max: Option<i64>) -> Result<SetMemory> {
+        if !self.v2 {
+            if high.is_some() {
+                return Err(Error::not_supported(
+                    "memory.high",
+                    "only available on cgroup v2",
+                ));
+            }
+            if let Some(max) = max {
+                self.set_limit(max)?;
+            }
+            let max = self
+                .open_path("memory.limit_in_bytes", false)
+                .and_then(read_u64_from)
+                .map(limit_from_v1)?;
+            return Ok(SetMemory {
+                max: Some(max.map_or(MaxValue::Max, |v| MaxValue::Value(v as i64))),
+                ..Default::default()
+            });
+        }
+
+        let to_max_value = |v: i64| {
+            if v < 0 {
+                MaxValue::Max
+            } else {
+                MaxValue::Value(v)
+            }
+        };
+        let high = high.map(to_max_value);
+        let max = max.map(to_max_value);
+
+        let current_max = self.get_max_value("memory.max")?;
+        let new_max = max.unwrap_or(current_max);
+        if let (Some(h), Some(m)) = (
+            high.and_then(limit_from_max_value),
+            limit_from_max_value(new_max),
+        ) {
+            if h > m {
+                return Err(Error::from_string(format!(
+                    "memory.high {} is above memory.max {}",
+                    h, m
+                )));
+            }
+        }
+
+        let lowering = match (
+            limit_from_max_value(new_max),
+            limit_from_max_value(current_max),
+        ) {
+            (Some(new), Some(current)) => new < current,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        let (high, max) = (
+            SetMemory {
+                high,
+                ..Default::default()
+            },
+            SetMemory {
+                max,
+                ..Default::default()
+            },
+        );
+        if lowering {
+            self.set_mem(high)?;
+            self.set_mem(max)?;
+        } else {
+            self.set_mem(max)?;
+            self.set_mem(high)?;
+        }
+
+        Ok(SetMemory {
+            high: Some(self.get_max_value("memory.high")?),
+            max: Some(self.get_max_value("memory.max")?),
+            ..Default::default()
+        })
+    }
+
     // for v2
     pub fn get_mem(&self) -> Result<SetMemory> {
         let m = SetMemory {
@@ -674,6 +1053,103 @@ impl MemController {
         }
     }
 
+    /// Reports the current memory usage of the control group together with its `high` and `max`
+    /// limits, so that the remaining headroom can be computed in a single call.
+    pub fn utilization(&self) -> Result<MemUtilization> {
+        let (current, high, max) = if self.v2 {
+            let current = self
+                .open_path("memory.current", false)
+                .and_then(read_u64_from)?;
+            let high = self
+                .get_max_value("memory.high")
+                .map(limit_from_max_value)?;
+            let max = self.get_max_value("memory.max").map(limit_from_max_value)?;
+            (current, high, max)
+        } else {
+            let current = self
+                .open_path("memory.usage_in_bytes", false)
+                .and_then(read_u64_from)?;
+            let max = self
+                .open_path("memory.limit_in_bytes", false)
+                .and_then(read_u64_from)
+                .map(limit_from_v1)?;
+            (current, None, max)
+        };
+
+        Ok(MemUtilization {
+            current,
+            high,
+            max,
+            percent_of_max: max.map(|max| {
+                if max == 0 {
+                    100.0
+                } else {
+                    current as f64 * 100.0 / max as f64
+                }
+            }),
+        })
+    }
+
+    /// Returns the number of page faults incurred by the tasks in the control group.
+    ///
+    /// This is the monotonic `pgfault` counter of `memory.stat`.
+    pub fn pgfault(&self) -> Result<u64> {
+        self.open_path("memory.stat", false)
+            .and_then(read_string_from)
+            .and_then(parse_memory_stat)
+            .map(|stat| stat.pgfault)
+    }
+
+    /// Returns the number of major page faults incurred by the tasks in the control group.
+    ///
+    /// This is the monotonic `pgmajfault` counter of `memory.stat`.
+    pub fn pgmajfault(&self) -> Result<u64> {
+        self.open_path("memory.stat", false)
+            .and_then(read_string_from)
+            .and_then(parse_memory_stat)
+            .map(|stat| stat.pgmajfault)
+    }
+
+    /// Returns the kernel memory charged to the control group, broken down by use (cgroup v2
+    /// only, use `kmem_stat()` on v1).
+    pub fn kernel_memory_stat(&self) -> Result<KernelMemoryStat> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "memory.stat kernel memory breakdown",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.open_path("memory.stat", false)
+            .and_then(read_string_from)
+            .and_then(parse_memory_stat)
+            .map(|stat| kernel_memory_stat(&stat.raw))
+    }
+
+    /// Returns the anonymous, file and shared memory of the control group, e.g. to tell how much
+    /// of its memory can be reclaimed.
+    pub fn memory_breakdown(&self) -> Result<MemoryBreakdown> {
+        self.open_path("memory.stat", false)
+            .and_then(read_string_from)
+            .and_then(parse_memory_stat)
+            .map(|stat| memory_breakdown(&stat.raw, self.v2))
+    }
+
+    /// Returns the anonymous memory of the control group, see `memory_breakdown()`.
+    pub fn anon(&self) -> Result<u64> {
+        self.memory_breakdown().map(|b| b.anon)
+    }
+
+    /// Returns the page cache of the control group, see `memory_breakdown()`.
+    pub fn file(&self) -> Result<u64> {
+        self.memory_breakdown().map(|b| b.file)
+    }
+
+    /// Returns the shared memory of the control group, see `memory_breakdown()`.
+    pub fn shmem(&self) -> Result<u64> {
+        self.memory_breakdown().map(|b| b.shmem)
+    }
+
     /// Gathers information about the kernel memory usage of the control group's tasks.
     pub fn kmem_stat(&self) -> Kmem {
         Kmem {
@@ -700,6 +1176,45 @@ impl MemController {
         }
     }
 
+    /// Returns the kernel memory usage of the control group's tasks in bytes (cgroup v1 only).
+    ///
+    /// On cgroup v2 kernel memory is accounted together with the rest of the memory.
+    pub fn kmem_usage(&self) -> Result<u64> {
+        if self.v2 {
+            return Err(Error::not_supported(
+                "memory.kmem.usage_in_bytes",
+                "only available on cgroup v1",
+            ));
+        }
+        self.open_path("memory.kmem.usage_in_bytes", false)
+            .and_then(read_u64_from)
+    }
+
+    /// Returns the maximum kernel memory usage recorded for the control group's tasks in bytes
+    /// (cgroup v1 only).
+    pub fn kmem_max_usage(&self) -> Result<u64> {
+        if self.v2 {
+            return Err(Error::not_supported(
+                "memory.kmem.max_usage_in_bytes",
+                "only available on cgroup v1",
+            ));
+        }
+        self.open_path("memory.kmem.max_usage_in_bytes", false)
+            .and_then(read_u64_from)
+    }
+
+    /// Returns the kernel memory limit of the control group in bytes (cgroup v1 only).
+    pub fn kmem_limit(&self) -> Result<i64> {
+        if self.v2 {
+            return Err(Error::not_supported(
+                "memory.kmem.limit_in_bytes",
+                "only available on cgroup v1",
+            ));
+        }
+        self.open_path("memory.kmem.limit_in_bytes", false)
+            .and_then(read_i64_from)
+    }
+
     /// Gathers information about the control group's kernel memory usage where said memory is
     /// TCP-related.
     pub fn kmem_tcp_stat(&self) -> Tcp {
@@ -731,8 +1246,8 @@ impl MemController {
                 .map(|x| *x.get("fail").unwrap_or(&0) as u64)
                 .unwrap_or(0),
             limit_in_bytes: self
-                .open_path("memory.swap.max", false)
-                .and_then(read_i64_from)
+                .get_max_value("memory.swap.max")
+                .map(|v| v.to_i64())
                 .unwrap_or(0),
             usage_in_bytes: self
                 .open_path("memory.swap.current", false)
@@ -774,14 +1289,7 @@ impl MemController {
 
     /// Reset the fail counter
     pub fn reset_fail_count(&self) -> Result<()> {
-        self.open_path("memory.failcnt", true).and_then(|mut file| {
-            file.write_all("0".to_string().as_ref()).map_err(|e| {
-                Error::with_cause(
-                    WriteFailed("memory.failcnt".to_string(), "0".to_string()),
-                    e,
-                )
-            })
-        })
+        self.write_file("memory.failcnt", "0")
     }
 
     /// Reset the kernel memory fail counter
@@ -791,15 +1299,7 @@ impl MemController {
             return Ok(());
         }
 
-        self.open_path("memory.kmem.failcnt", true)
-            .and_then(|mut file| {
-                file.write_all("0".to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("memory.kmem.failcnt".to_string(), "0".to_string()),
-                        e,
-                    )
-                })
-            })
+        self.write_file("memory.kmem.failcnt", "0")
     }
 
     /// Reset the TCP related fail counter
@@ -809,41 +1309,17 @@ impl MemController {
             return Ok(());
         }
 
-        self.open_path("memory.kmem.tcp.failcnt", true)
-            .and_then(|mut file| {
-                file.write_all("0".to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("memory.kmem.tcp.failcnt".to_string(), "0".to_string()),
-                        e,
-                    )
-                })
-            })
+        self.write_file("memory.kmem.tcp.failcnt", "0")
     }
 
     /// Reset the memory+swap fail counter
     pub fn reset_memswap_fail_count(&self) -> Result<()> {
-        self.open_path("memory.memsw.failcnt", true)
-            .and_then(|mut file| {
-                file.write_all("0".to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("memory.memsw.failcnt".to_string(), "0".to_string()),
-                        e,
-                    )
-                })
-            })
+        self.write_file("memory.memsw.failcnt", "0")
     }
 
     /// Reset the max memory usage recorded
     pub fn reset_max_usage(&self) -> Result<()> {
-        self.open_path("memory.max_usage_in_bytes", true)
-            .and_then(|mut file| {
-                file.write_all("0".to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("memory.max_usage_in_bytes".to_string(), "0".to_string()),
-                        e,
-                    )
-                })
-            })
+        self.write_file("memory.max_usage_in_bytes", "0")
     }
 
     /// Set the memory usage limit of the control group, in bytes.
@@ -856,10 +1332,7 @@ impl MemController {
                 limit_str = "max".to_string();
             }
         }
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(limit_str.as_ref())
-                .map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), limit_str), e))
-        })
+        self.write_file(file_name, &limit_str)
     }
 
     /// Set the kernel memory limit of the control group, in bytes.
@@ -886,7 +1359,27 @@ impl MemController {
             })
     }
 
+    /// Retrieve the memory+swap limit of the control group in bytes, `-1` if unlimited.
+    ///
+    /// On cgroup v2, where swap is limited apart from memory, this is computed from
+    /// `memory.max` and `memory.swap.max` the way `MemoryResources::memory_swap_limit` is
+    /// applied.
+    pub fn memswap_limit(&self) -> Result<i64> {
+        if !self.v2 {
+            return self
+                .open_path("memory.memsw.limit_in_bytes", false)
+                .and_then(read_i64_from);
+        }
+
+        let memory = self.get_max_value("memory.max")?.to_i64();
+        let swap = self.get_max_value("memory.swap.max")?.to_i64();
+        Ok(swap_to_memswap(swap, memory))
+    }
+
     /// Set the memory+swap limit of the control group, in bytes.
+    ///
+    /// On cgroup v2 this writes `memory.swap.max`, which limits the swap usage alone, see
+    /// `set_swap_max()`.
     pub fn set_memswap_limit(&self, limit: i64) -> Result<()> {
         let mut file_name = "memory.memsw.limit_in_bytes";
         let mut limit_str = limit.to_string();
@@ -896,10 +1389,122 @@ impl MemController {
                 limit_str = "max".to_string();
             }
         }
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(limit_str.as_ref())
-                .map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), limit_str), e))
-        })
+        self.write_file(file_name, &limit_str)
+    }
+
+    /// Set the swap limit (`memory.swap.max`) of the control group, in bytes, `-1` removing it.
+    /// Only available on cgroup v2, where swap is limited apart from memory.
+    ///
+    /// `0` prevents the tasks of the control group from using swap at all.
+    pub fn set_swap_max(&self, limit: i64) -> Result<()> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "memory.swap.max",
+                "only available on cgroup v2, see set_memswap_limit()",
+            ));
+        }
+
+        self.set_memswap_limit(limit)
+    }
+
+    /// Retrieve the swap limit (`memory.swap.max`) of the control group in bytes, `-1` if
+    /// unlimited. Only available on cgroup v2.
+    pub fn swap_max(&self) -> Result<i64> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "memory.swap.max",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.get_max_value("memory.swap.max").map(|v| v.to_i64())
+    }
+
+    /// Returns the swap usage of the control group and its descendants, in bytes, found in
+    /// `memory.swap.current`. Only available on cgroup v2.
+    pub fn memory_swap_current(&self) -> Result<u64> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "memory.swap.current",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.open_path("memory.swap.current", false)
+            .and_then(read_u64_from)
+    }
+
+    /// Returns the swap events that occurred in the control group, found in
+    /// `memory.swap.events`. Only available on cgroup v2.
+    pub fn swap_events(&self) -> Result<SwapEvents> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "memory.swap.events",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.open_path("memory.swap.events", false)
+            .and_then(flat_keyed_to_hashmap)
+            .map(|raw| swap_events(&raw))
+    }
+
+    /// Set the swap throttling limit (`memory.swap.high`) of the control group, in bytes, `-1`
+    /// removing it.
+    ///
+    /// Above this limit the allocations of the control group are throttled, which discourages
+    /// swap use before the hard limit set with `set_memswap_limit()` is reached. Only available
+    /// on cgroup v2.
+    pub fn set_swap_high(&self, limit: i64) -> Result<()> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "memory.swap.high",
+                "only available on cgroup v2",
+            ));
+        }
+
+        let limit_str = if limit == -1 {
+            "max".to_string()
+        } else {
+            limit.to_string()
+        };
+        self.write_file("memory.swap.high", &limit_str)
+    }
+
+    /// Retrieve the swap throttling limit (`memory.swap.high`) of the control group in bytes, `-1`
+    /// if unlimited. Only available on cgroup v2.
+    pub fn swap_high(&self) -> Result<i64> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "memory.swap.high",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.get_max_value("memory.swap.high").map(|v| v.to_i64())
+    }
+
+    /// Set the throttling limit (`memory.high`) of the control group, in bytes.
+    ///
+    /// Above this limit the tasks are throttled and put under heavy reclaim pressure, but never
+    /// OOM killed, unlike at the hard limit set with `set_limit()`. Only available on cgroup v2.
+    ///
+    /// This is `set_limits()` leaving `memory.max` untouched.
+    pub fn set_mem_high(&self, limit: MaxValue) -> Result<()> {
+        self.set_limits(Some(limit.to_i64()), None).map(|_| ())
+    }
+
+    /// Retrieve the throttling limit (`memory.high`) of the control group. Only available on
+    /// cgroup v2.
+    pub fn mem_high(&self) -> Result<MaxValue> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "memory.high",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.get_max_value("memory.high")
     }
 
     /// Set how much kernel memory can be used for TCP-related buffers by the control group.
@@ -909,18 +1514,7 @@ impl MemController {
             return Ok(());
         }
 
-        self.open_path("memory.kmem.tcp.limit_in_bytes", true)
-            .and_then(|mut file| {
-                file.write_all(limit.to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed(
-                            "memory.kmem.tcp.limit_in_bytes".to_string(),
-                            limit.to_string(),
-                        ),
-                        e,
-                    )
-                })
-            })
+        self.write_file("memory.kmem.tcp.limit_in_bytes", &limit.to_string())
     }
 
     /// Set the soft limit of the control group, in bytes.
@@ -932,40 +1526,157 @@ impl MemController {
         if self.v2 {
             file_name = "memory.low"
         }
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(limit.to_string().as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed(file_name.to_string(), limit.to_string()), e)
-            })
-        })
+        self.write_file(file_name, &limit.to_string())
     }
 
     /// Set how likely the kernel is to swap out parts of the address space used by the control
     /// group.
     ///
-    /// Note that a value of zero does not imply that the process will not be swapped out.
-    pub fn set_swappiness(&self, swp: u64) -> Result<()> {
-        let mut file_name = "memory.swappiness";
+    /// The value must be in the range 0-100. Note that a value of zero does not imply that the
+    /// process will not be swapped out. Only cgroup v1 has a per-cgroup swappiness.
+    pub fn set_swappiness(&self, swp: u8) -> Result<()> {
         if self.v2 {
-            file_name = "memory.swap.max"
+            return Err(Error::not_supported(
+                "memory.swappiness",
+                "only available on cgroup v1",
+            ));
+        }
+        if swp > 100 {
+            return Err(Error::new(InvalidArgument(format!(
+                "invalid swappiness {}, must be in 0-100",
+                swp
+            ))));
         }
 
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(swp.to_string().as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed(file_name.to_string(), swp.to_string()), e)
-            })
-        })
+        self.write_file("memory.swappiness", &swp.to_string())
+    }
+
+    /// Retrieve how likely the kernel is to swap out parts of the address space used by the
+    /// control group. Only cgroup v1 has a per-cgroup swappiness.
+    pub fn swappiness(&self) -> Result<u8> {
+        if self.v2 {
+            return Err(Error::not_supported(
+                "memory.swappiness",
+                "only available on cgroup v1",
+            ));
+        }
+
+        self.open_path("memory.swappiness", false)
+            .and_then(read_u64_from)
+            .and_then(|v| u8::try_from(v).map_err(|e| Error::with_cause(ParseError, e)))
     }
 
     pub fn disable_oom_killer(&self) -> Result<()> {
-        self.open_path("memory.oom_control", true)
-            .and_then(|mut file| {
-                file.write_all("1".to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("memory.oom_control".to_string(), "1".to_string()),
-                        e,
-                    )
-                })
-            })
+        self.write_file("memory.oom_control", "1")
+    }
+
+    /// Returns the memory events that occurred in the control group, found in `memory.events` on
+    /// cgroup v2.
+    ///
+    /// cgroup v1 has no such file, only two of the counters are available there: `oom_kill`
+    /// from `memory.oom_control`, and `max` from `memory.failcnt`, which counts the times the
+    /// usage hit the limit. The other counters are 0.
+    pub fn memory_events(&self) -> Result<MemoryEvents> {
+        if !self.v2 {
+            let oom = self
+                .open_path("memory.oom_control", false)
+                .and_then(read_string_from)
+                .and_then(parse_oom_control)?;
+            let fail_cnt = self
+                .open_path("memory.failcnt", false)
+                .and_then(read_u64_from)?;
+            return Ok(MemoryEvents {
+                max: fail_cnt,
+                oom_kill: oom.oom_kill,
+                ..Default::default()
+            });
+        }
+
+        self.open_path("memory.events", false)
+            .and_then(flat_keyed_to_hashmap)
+            .map(|raw| memory_events(&raw))
+    }
+
+    /// Returns how many times the whole control group was killed by the OOM killer, see
+    /// `set_oom_group()` (cgroup v2 only).
+    pub fn group_oom_count(&self) -> Result<u64> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "memory.oom.group",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.memory_events().map(|events| events.oom_group_kill)
+    }
+
+    /// Returns how many tasks of the control group the OOM killer killed so far, from the
+    /// `oom_kill` counter of `memory.events` on v2 and of `memory.oom_control` on v1.
+    ///
+    /// A new kill shows up as an increment between two calls. The counter doesn't tell which
+    /// process was killed: correlating it with a PID requires the kernel log (`/dev/kmsg` or
+    /// `dmesg`), where the OOM killer reports `Killed process <pid>` for each kill.
+    pub fn last_oom_kill_count(&self) -> Result<u64> {
+        self.memory_events().map(|events| events.oom_kill)
+    }
+
+    /// Returns the pressure stall information of the control group on memory, from
+    /// `memory.pressure` (cgroup v2 only).
+    ///
+    /// The file is missing when the kernel doesn't account pressure, see
+    /// `Cgroup::set_pressure_accounting()`.
+    pub fn pressure(&self) -> Result<Pressure> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "memory.pressure",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.open_path("memory.pressure", false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_pressure(&s))
+    }
+
+    /// Returns the time during which all the tasks of the control group were stalled on memory
+    /// at once, from the `full` line of `memory.pressure` (PSI), e.g. to approximate the time
+    /// spent throttled above `memory.high` (cgroup v2 only).
+    ///
+    /// The stalls include reclaim and swap-ins, not only the throttling, and the file is missing
+    /// when the kernel doesn't account pressure, see `Cgroup::set_pressure_accounting()`.
+    pub fn throttle_time(&self) -> Result<Duration> {
+        self.pressure()
+            .and_then(|p| p.full.ok_or_else(|| Error::new(ParseError)))
+            .map(|full| Duration::from_micros(full.total))
+    }
+
+    /// Sets whether the OOM killer kills all the tasks of the control group together rather than
+    /// a single one, through `memory.oom.group` (cgroup v2 only).
+    pub fn set_oom_group(&self, enable: bool) -> Result<()> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "memory.oom.group",
+                "only available on cgroup v2",
+            ));
+        }
+
+        let value = (enable as u8).to_string();
+        self.write_file("memory.oom.group", &value)
+    }
+
+    /// Returns whether the OOM killer kills all the tasks of the control group together (cgroup
+    /// v2 only).
+    pub fn oom_group(&self) -> Result<bool> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "memory.oom.group",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.open_path("memory.oom.group", false)
+            .and_then(read_u64_from)
+            .map(|v| v == 1)
     }
 
     pub fn register_oom_event(&self, key: &str) -> Result<Receiver<String>> {
@@ -975,6 +1686,31 @@ impl MemController {
             events::notify_on_oom_v1(key, self.get_path())
         }
     }
+
+    /// Watches `memory.events`, sending the new counters on the returned channel every time one
+    /// of them grows (cgroup v2 only).
+    ///
+    /// This tells right away when a task of the subtree gets OOM-killed (`oom_kill`) or when the
+    /// tasks get throttled by `memory.high` (`high`), without polling `memory_events()`. The
+    /// kernel notifies the changes of `memory.events`, whose counters include the events of the
+    /// descendants.
+    pub fn watch_memory_events(&self) -> Result<events::Watch<MemoryEvents>> {
+        if !self.v2 {
+            return Err(Error::not_supported(
+                "memory.events",
+                "only available on cgroup v2",
+            ));
+        }
+
+        self.verify_path()?;
+        let path = self.get_path().join("memory.events");
+        events::watch_changes(&path, |p| {
+            std::fs::File::open(p)
+                .map_err(|e| Error::with_cause(ReadFailed(p.display().to_string()), e))
+                .and_then(flat_keyed_to_hashmap)
+                .map(|raw| memory_events(&raw))
+        })
+    }
 }
 
 impl ControllIdentifier for MemController {
@@ -985,27 +1721,58 @@ impl ControllIdentifier for MemController {
 
 impl CustomizedAttribute for MemController {}
 
-impl<'a> From<&'a Subsystem> for &'a MemController {
-    fn from(sub: &'a Subsystem) -> &'a MemController {
-        unsafe {
-            match sub {
-                Subsystem::Mem(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a MemController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a MemController> {
+        match sub {
+            Subsystem::Mem(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                MemController::controller_type().to_string(),
+            ))),
         }
     }
 }
 
+// Converts a v1 memory+swap limit to the v2 swap limit given the memory limit, `-1` meaning
+// unlimited. Returns `None` when the memory+swap limit is below the memory limit.
+fn memswap_to_swap(memswap: i64, memory: i64) -> Option<i64> {
+    if memswap == -1 || memory == -1 {
+        return Some(memswap);
+    }
+    memswap.checked_sub(memory).filter(|swap| *swap >= 0)
+}
+
+// The inverse of `memswap_to_swap()`.
+fn swap_to_memswap(swap: i64, memory: i64) -> i64 {
+    if swap == -1 || memory == -1 {
+        return swap;
+    }
+    swap + memory
+}
+
 #[cfg(test)]
 mod tests {
     use crate::fs::memory::{
-        parse_memory_stat, parse_numa_stat, parse_oom_control, MemoryStat, NumaStat, OomControl,
+        kernel_memory_stat, memory_breakdown, memory_events, memswap_to_swap, parse_memory_stat,
+        parse_numa_stat, parse_oom_control, swap_events, swap_to_memswap, KernelMemoryStat,
+        MemoryBreakdown, MemoryEvents, MemoryStat, NumaStat, OomControl, SwapEvents,
     };
 
+    #[test]
+    fn test_memswap_swap_conversion() {
+        let mib = 1024 * 1024;
+        assert_eq!(memswap_to_swap(768 * mib, 512 * mib), Some(256 * mib));
+        assert_eq!(memswap_to_swap(512 * mib, 512 * mib), Some(0));
+        assert_eq!(memswap_to_swap(-1, 512 * mib), Some(-1));
+        assert_eq!(memswap_to_swap(256 * mib, -1), Some(256 * mib));
+        assert_eq!(memswap_to_swap(256 * mib, 512 * mib), None);
+
+        assert_eq!(swap_to_memswap(256 * mib, 512 * mib), 768 * mib);
+        assert_eq!(swap_to_memswap(-1, 512 * mib), -1);
+        assert_eq!(swap_to_memswap(256 * mib, -1), 256 * mib);
+    }
+
     static GOOD_VALUE: &str = "\
 total=51189 N0=51189 N1=123
 file=50175 N0=50175 N1=123
@@ -1216,4 +1983,111 @@ total_unevictable 81920
             }
         );
     }
+
+    #[test]
+    fn test_parse_memory_stat_raw() {
+        let stat = parse_memory_stat(
+            "anon 4096\nfile 8192\nkernel_stack 16384\npgmajfault 7\n\
+             workingset_refault_anon 3\nworkingset_refault_file 11\n\
+             some_future_counter 1\nnot a counter\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(stat.raw.len(), 7);
+        assert_eq!(stat.raw["workingset_refault_file"], 11);
+        assert_eq!(stat.raw["some_future_counter"], 1);
+        assert_eq!(stat.pgmajfault, 7);
+        assert_eq!(stat.cache, 0);
+    }
+
+    #[test]
+    fn test_kernel_memory_stat() {
+        let stat = parse_memory_stat(
+            "anon 1000\nslab 300\nslab_reclaimable 200\nslab_unreclaimable 100\n\
+             sock 40\nkernel_stack 50\npagetables 60\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            kernel_memory_stat(&stat.raw),
+            KernelMemoryStat {
+                slab: Some(300),
+                slab_reclaimable: Some(200),
+                slab_unreclaimable: Some(100),
+                sock: Some(40),
+                kernel_stack: Some(50),
+                // Only reported since Linux 5.9.
+                percpu: None,
+                pagetables: Some(60),
+            }
+        );
+    }
+
+    #[test]
+    fn test_memory_breakdown() {
+        let v2 = parse_memory_stat("anon 4096\nfile 8192\nshmem 1024\n".to_string()).unwrap();
+        let v1 = parse_memory_stat(
+            "cache 100\nrss 200\nshmem 10\ntotal_cache 8192\ntotal_rss 4096\n\
+             total_shmem 1024\n"
+                .to_string(),
+        )
+        .unwrap();
+        let expected = MemoryBreakdown {
+            anon: 4096,
+            file: 8192,
+            shmem: 1024,
+        };
+        assert_eq!(memory_breakdown(&v2.raw, true), expected);
+        assert_eq!(memory_breakdown(&v1.raw, false), expected);
+    }
+
+    #[test]
+    fn test_memory_events() {
+        let raw = [
+            ("low", 0),
+            ("high", 12),
+            ("max", 3),
+            ("oom", 2),
+            ("oom_kill", 5),
+            ("oom_group_kill", 1),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), *v))
+        .collect();
+        assert_eq!(
+            memory_events(&raw),
+            MemoryEvents {
+                low: 0,
+                high: 12,
+                max: 3,
+                oom: 2,
+                oom_kill: 5,
+                oom_group_kill: 1,
+            }
+        );
+
+        // `oom_group_kill` is only reported since Linux 5.18.
+        let raw = [("oom", 2), ("oom_kill", 5)]
+            .iter()
+            .map(|(k, v)| (k.to_string(), *v))
+            .collect();
+        assert_eq!(memory_events(&raw).oom_group_kill, 0);
+    }
+
+    #[test]
+    fn test_swap_events() {
+        let raw = [("high", 4), ("max", 2), ("fail", 1)]
+            .iter()
+            .map(|(k, v)| (k.to_string(), *v))
+            .collect();
+        assert_eq!(
+            swap_events(&raw),
+            SwapEvents {
+                high: 4,
+                max: 2,
+                fail: 1,
+            }
+        );
+        assert_eq!(swap_events(&Default::default()), SwapEvents::default());
+    }
 }
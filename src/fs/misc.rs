@@ -0,0 +1,216 @@
+// Copyright (c) 2020 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0 or MIT
+//
+
+//! This module contains the implementation of the `misc` cgroup subsystem, available on cgroup v2
+//! and, since Linux 5.13, on cgroup v1 with the same interface files.
+//!
+//! See the Kernel's documentation for more information about this subsystem, found at:
+//!  [Documentation/admin-guide/cgroup-v2.rst](https://www.kernel.org/doc/Documentation/admin-guide/cgroup-v2.rst)
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::fs::error::ErrorKind::*;
+use crate::fs::error::*;
+
+use crate::fs::{
+    parse_max_value, read_string_from, ControllIdentifier, ControllerInternal, Controllers,
+    MaxValue, MiscResource, MiscResources, Resources, Subsystem,
+};
+
+/// A controller that allows controlling the `misc` subsystem of a Cgroup.
+///
+/// In essence, using this controller one can limit the scalar resources the kernel doesn't have
+/// a dedicated controller for, e.g. the AMD SEV ASIDs, that the tasks in the control group can
+/// use.
+#[derive(Debug, Clone)]
+pub struct MiscController {
+    base: PathBuf,
+    path: PathBuf,
+}
+
+// The control files used by the controller, see `Controller::managed_files()`.
+const MANAGED_FILES: &[&str] = &["misc.max", "misc.capacity", "misc.current", "misc.events"];
+
+impl ControllerInternal for MiscController {
+    fn control_type(&self) -> Controllers {
+        Controllers::Misc
+    }
+    fn managed_files(&self) -> &'static [&'static str] {
+        MANAGED_FILES
+    }
+    fn get_path(&self) -> &PathBuf {
+        &self.path
+    }
+    fn get_path_mut(&mut self) -> &mut PathBuf {
+        &mut self.path
+    }
+    fn get_base(&self) -> &PathBuf {
+        &self.base
+    }
+
+    fn is_v2(&self) -> bool {
+        true
+    }
+
+    fn apply(&self, res: &Resources) -> Result<()> {
+        // get the resources that apply to this controller
+        let res: &MiscResources = &res.misc;
+
+        for limit in &res.limits {
+            self.set_max(&limit.key, limit.max)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_resources(&self, res: &mut Resources) -> Result<()> {
+        if !self.path.join("misc.max").exists() {
+            return Ok(());
+        }
+        res.misc.limits = self
+            .max()?
+            .into_iter()
+            .map(|(key, max)| MiscResource { key, max })
+            .collect();
+        Ok(())
+    }
+}
+
+impl ControllIdentifier for MiscController {
+    fn controller_type() -> Controllers {
+        Controllers::Misc
+    }
+}
+
+impl<'a> TryFrom<&'a Subsystem> for &'a MiscController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a MiscController> {
+        match sub {
+            Subsystem::Misc(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                MiscController::controller_type().to_string(),
+            ))),
+        }
+    }
+}
+
+impl MiscController {
+    /// Constructs a new `MiscController` with `root` serving as the root of the control group.
+    pub fn new(point: PathBuf, root: PathBuf) -> Self {
+        Self {
+            base: root,
+            path: point,
+        }
+    }
+
+    /// Set the limit of the misc resource `key` in `misc.max`, `MaxValue::Max` removing it.
+    ///
+    /// The kernel only accepts the resources listed by `capacity()`, an error naming `key` is
+    /// returned for the other ones.
+    pub fn set_max(&self, key: &str, limit: MaxValue) -> Result<()> {
+        if !self.capacity()?.contains_key(key) {
+            return Err(Error::new(InvalidArgument(format!(
+                "unsupported misc resource: {}",
+                key
+            ))));
+        }
+
+        let line = format!("{} {}", key, limit);
+        self.write_file("misc.max", &line)
+    }
+
+    /// Returns the limit of every misc resource, as found in `misc.max`.
+    pub fn max(&self) -> Result<Vec<(String, MaxValue)>> {
+        let content = self
+            .open_path("misc.max", false)
+            .and_then(read_string_from)?;
+        content
+            .lines()
+            .filter_map(|l| l.split_once(' '))
+            .map(|(key, value)| Ok((key.to_string(), parse_max_value(value)?)))
+            .collect()
+    }
+
+    /// Removes the limit of the misc resource `key`, same as `set_max(key, MaxValue::Max)`.
+    pub fn clear(&self, key: &str) -> Result<()> {
+        self.set_max(key, MaxValue::Max)
+    }
+
+    /// Returns the misc resources available on the host and their total amount, e.g. the
+    /// number of SEV ASIDs, as found in `misc.capacity`.
+    ///
+    /// This file only exists in the root control group and is read from there.
+    pub fn capacity(&self) -> Result<HashMap<String, u64>> {
+        let path = self
+            .path
+            .ancestors()
+            .map(|dir| dir.join("misc.capacity"))
+            .find(|p| p.exists())
+            .unwrap_or_else(|| self.path.join("misc.capacity"));
+        fs::read_to_string(&path)
+            .map_err(|e| Error::with_cause(ReadFailed(path.display().to_string()), e))
+            .map(|s| parse_misc_values(&s))
+    }
+
+    /// Returns the current usage of every misc resource by the control group and its
+    /// descendants, as found in `misc.current`.
+    pub fn current(&self) -> Result<HashMap<String, u64>> {
+        self.open_path("misc.current", false)
+            .and_then(read_string_from)
+            .map(|s| parse_misc_values(&s))
+    }
+
+    /// Returns the number of times the usage of every misc resource was about to go over its
+    /// limit, as found in `misc.events`.
+    pub fn events(&self) -> Result<HashMap<String, u64>> {
+        self.open_path("misc.events", false)
+            .and_then(read_string_from)
+            .map(|s| parse_misc_events(&s))
+    }
+}
+
+// Parses the `<resource> <value>` lines of `misc.capacity` and `misc.current`.
+fn parse_misc_values(s: &str) -> HashMap<String, u64> {
+    s.lines()
+        .filter_map(|l| {
+            let (key, value) = l.split_once(' ')?;
+            Some((key.to_string(), value.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+// Parses the `<resource>.max <count>` lines of `misc.events`.
+fn parse_misc_events(s: &str) -> HashMap<String, u64> {
+    parse_misc_values(s)
+        .into_iter()
+        .filter_map(|(key, count)| Some((key.strip_suffix(".max")?.to_string(), count)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::misc::{parse_misc_events, parse_misc_values};
+
+    #[test]
+    fn test_parse_misc_values() {
+        let capacity = parse_misc_values("sev 509\nsev_es 0\n");
+        assert_eq!(capacity.len(), 2);
+        assert_eq!(capacity["sev"], 509);
+        assert_eq!(capacity["sev_es"], 0);
+
+        assert!(parse_misc_values("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_misc_events() {
+        let events = parse_misc_events("sev.max 3\nsev_es.max 0\n");
+        assert_eq!(events.len(), 2);
+        assert_eq!(events["sev"], 3);
+        assert_eq!(events["sev_es"], 0);
+    }
+}
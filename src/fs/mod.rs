@@ -7,12 +7,15 @@
 #![allow(clippy::unnecessary_unwrap)]
 use log::*;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::fmt;
 use std::fs::{self, File};
 use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 macro_rules! update_and_test {
     ($self: ident, $set_func:ident, $value:expr, $get_func:ident) => {
@@ -46,6 +49,7 @@ pub mod freezer;
 pub mod hierarchies;
 pub mod hugetlb;
 pub mod memory;
+pub mod misc;
 pub mod net_cls;
 pub mod net_prio;
 pub mod perf_event;
@@ -63,11 +67,12 @@ use crate::fs::error::*;
 use crate::fs::freezer::FreezerController;
 use crate::fs::hugetlb::HugeTlbController;
 use crate::fs::memory::MemController;
+use crate::fs::misc::MiscController;
 use crate::fs::net_cls::NetClsController;
 use crate::fs::net_prio::NetPrioController;
 use crate::fs::perf_event::PerfEventController;
 use crate::fs::pid::PidController;
-use crate::fs::rdma::RdmaController;
+use crate::fs::rdma::{RdmaController, RdmaLimit};
 use crate::fs::systemd::SystemdController;
 
 #[doc(inline)]
@@ -102,12 +107,14 @@ pub enum Subsystem {
     HugeTlb(HugeTlbController),
     /// Controller for the `Rdma` subsystem, see `RdmaController` for more information.
     Rdma(RdmaController),
+    /// Controller for the `Misc` subsystem, see `MiscController` for more information.
+    Misc(MiscController),
     /// Controller for the `Systemd` subsystem, see `SystemdController` for more information.
     Systemd(SystemdController),
 }
 
 #[doc(hidden)]
-#[derive(Eq, PartialEq, Debug, Clone)]
+#[derive(Eq, PartialEq, Hash, Debug, Clone)]
 pub enum Controllers {
     Pids,
     Mem,
@@ -122,6 +129,7 @@ pub enum Controllers {
     NetPrio,
     HugeTlb,
     Rdma,
+    Misc,
     Systemd,
 }
 
@@ -141,11 +149,105 @@ impl fmt::Display for Controllers {
             Controllers::NetPrio => write!(f, "net_prio"),
             Controllers::HugeTlb => write!(f, "hugetlb"),
             Controllers::Rdma => write!(f, "rdma"),
+            Controllers::Misc => write!(f, "misc"),
             Controllers::Systemd => write!(f, "name=systemd"),
         }
     }
 }
 
+impl FromStr for Controllers {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pids" => Ok(Controllers::Pids),
+            "memory" => Ok(Controllers::Mem),
+            "cpuset" => Ok(Controllers::CpuSet),
+            "cpuacct" => Ok(Controllers::CpuAcct),
+            "cpu" => Ok(Controllers::Cpu),
+            "devices" => Ok(Controllers::Devices),
+            "freezer" => Ok(Controllers::Freezer),
+            "net_cls" => Ok(Controllers::NetCls),
+            // the blkio controller is named io on cgroup v2
+            "blkio" | "io" => Ok(Controllers::BlkIo),
+            "perf_event" => Ok(Controllers::PerfEvent),
+            "net_prio" => Ok(Controllers::NetPrio),
+            "hugetlb" => Ok(Controllers::HugeTlb),
+            "rdma" => Ok(Controllers::Rdma),
+            "misc" => Ok(Controllers::Misc),
+            "name=systemd" => Ok(Controllers::Systemd),
+            _ => Err(Error::from_string(format!("unknown controller: {}", s))),
+        }
+    }
+}
+
+/// Interface files that exist on the root control group of a v1 hierarchy but are rejected by
+/// the kernel when written to.
+const ROOT_UNCONFIGURABLE_FILES: &[&str] = &[
+    "memory.limit_in_bytes",
+    "memory.memsw.limit_in_bytes",
+    "memory.kmem.limit_in_bytes",
+    "memory.kmem.tcp.limit_in_bytes",
+    "memory.oom_control",
+    "cpu.shares",
+    "cpu.cfs_quota_us",
+    "cpu.cfs_period_us",
+    "cpuset.cpus",
+    "cpuset.mems",
+];
+
+/// Whether `path` is the root control group of a hierarchy.
+pub(crate) fn is_root_path(path: &Path) -> bool {
+    // `release_agent` only exists in the root of a v1 hierarchy, and `cgroup.events` in every v2
+    // control group but the root.
+    path.join("release_agent").exists()
+        || (path.join("cgroup.controllers").exists() && !path.join("cgroup.events").exists())
+}
+
+// The write handles of the control files of the directories registered by
+// `Cgroup::with_cached_handles()`, by directory and then by file name.
+static CACHED_HANDLES: Mutex<BTreeMap<PathBuf, HashMap<String, Arc<File>>>> =
+    Mutex::new(BTreeMap::new());
+
+// A panic while holding the lock can't leave the cache inconsistent, at worst a handle is
+// missing and gets opened again.
+fn lock_handles() -> MutexGuard<'static, BTreeMap<PathBuf, HashMap<String, Arc<File>>>> {
+    CACHED_HANDLES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Starts or stops caching the write handles of the control files of `dirs`, see
+/// `Cgroup::with_cached_handles()`. Stopping closes the cached handles of the directories and
+/// of their descendants.
+pub(crate) fn cache_handles<'a>(dirs: impl Iterator<Item = &'a Path>, enable: bool) {
+    let mut cache = lock_handles();
+    for dir in dirs {
+        if enable {
+            cache.entry(dir.to_path_buf()).or_default();
+        } else {
+            cache.retain(|d, _| !d.starts_with(dir));
+        }
+    }
+}
+
+// Returns the cached write handle of `dir/file_name`, if any.
+fn cached_handle(dir: &Path, file_name: &str) -> Option<Arc<File>> {
+    lock_handles().get(dir)?.get(file_name).cloned()
+}
+
+// Caches the write handle `file` of `dir/file_name`, if `dir` is registered.
+fn store_handle(dir: &Path, file_name: &str, file: File) {
+    if let Some(files) = lock_handles().get_mut(dir) {
+        files.insert(file_name.to_string(), Arc::new(file));
+    }
+}
+
+// Drops the cached write handle of `dir/file_name`, e.g. once it went stale.
+fn evict_handle(dir: &Path, file_name: &str) {
+    if let Some(files) = lock_handles().get_mut(dir) {
+        files.remove(file_name);
+    }
+}
+
 mod sealed {
     use super::*;
 
@@ -161,10 +263,30 @@ mod sealed {
         /// Hooks running after controller crated, if have
         fn post_create(&self) {}
 
+        /// The control files the controller knows about, see `Controller::managed_files()`.
+        fn managed_files(&self) -> &'static [&'static str];
+
+        /// Resets the settings of the controller to their defaults, see `Controller::reset()`.
+        fn reset(&self) -> Result<()> {
+            Ok(())
+        }
+
+        /// Fills the section of `res` of the controller with its current settings, see
+        /// `Controller::read_resources()`.
+        fn read_resources(&self, _res: &mut Resources) -> Result<()> {
+            Ok(())
+        }
+
         fn is_v2(&self) -> bool {
             false
         }
 
+        /// Whether the controller points at the root control group, on which most interface
+        /// files can't be configured.
+        fn is_root(&self) -> bool {
+            is_root_path(self.get_path())
+        }
+
         fn verify_path(&self) -> Result<()> {
             if self.get_path().starts_with(self.get_base()) {
                 Ok(())
@@ -179,8 +301,34 @@ mod sealed {
 
             self.verify_path()?;
 
+            // The std library already opens files with O_CLOEXEC, ask for it explicitly
+            // anyway so that control files never leak into spawned processes.
             if w {
-                match File::create(&path) {
+                if ROOT_UNCONFIGURABLE_FILES.contains(&p) && self.is_root() {
+                    return Err(Error::new(ErrorKind::RootCgroupNotConfigurable(
+                        p.to_string(),
+                    )));
+                }
+                match fs::OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .custom_flags(libc::O_CLOEXEC)
+                    .open(&path)
+                {
+                    // the interface files of the controller missing from the root control group
+                    // can't be created, cgroupfs refusing it with EACCES
+                    Err(e)
+                        if e.raw_os_error() == Some(libc::EACCES)
+                            && self.managed_files().contains(&p)
+                            && !path.exists()
+                            && self.is_root() =>
+                    {
+                        Err(Error::with_cause(
+                            ErrorKind::RootCgroupNotConfigurable(p.to_string()),
+                            e,
+                        ))
+                    }
                     Err(e) => Err(Error::with_cause(
                         ErrorKind::WriteFailed(
                             path.display().to_string(),
@@ -191,7 +339,11 @@ mod sealed {
                     Ok(file) => Ok(file),
                 }
             } else {
-                match File::open(&path) {
+                match fs::OpenOptions::new()
+                    .read(true)
+                    .custom_flags(libc::O_CLOEXEC)
+                    .open(&path)
+                {
                     Err(e) => Err(Error::with_cause(
                         ErrorKind::ReadFailed(path.display().to_string()),
                         e,
@@ -201,6 +353,30 @@ mod sealed {
             }
         }
 
+        /// Writes `value` to the file `p`, through the cached handle if the control group caches
+        /// them, see `Cgroup::with_cached_handles()`.
+        fn write_file(&self, p: &str, value: &str) -> Result<()> {
+            let write_failed =
+                |e| Error::with_cause(WriteFailed(p.to_string(), value.to_string()), e);
+
+            if let Some(file) = cached_handle(self.get_path(), p) {
+                match (&*file).write_all(value.as_ref()) {
+                    Ok(()) => return Ok(()),
+                    // the control group was removed, and maybe created again, since the file was
+                    // opened: the handle is stale, open the file anew
+                    Err(e) if matches!(e.raw_os_error(), Some(libc::ESTALE | libc::ENODEV)) => {
+                        evict_handle(self.get_path(), p)
+                    }
+                    Err(e) => return Err(write_failed(e)),
+                }
+            }
+
+            let mut file = self.open_path(p, true)?;
+            file.write_all(value.as_ref()).map_err(write_failed)?;
+            store_handle(self.get_path(), p, file);
+            Ok(())
+        }
+
         fn get_max_value(&self, f: &str) -> Result<MaxValue> {
             self.open_path(f, false).and_then(|mut file| {
                 let mut string = String::new();
@@ -212,6 +388,46 @@ mod sealed {
             })
         }
 
+        /// Reads and parses the file `f`, or returns `None` if the controller doesn't have it.
+        fn read_if_exists<T>(&self, f: &str) -> Result<Option<T>>
+        where
+            Self: Sized,
+            T: FromStr,
+            <T as FromStr>::Err: 'static + Send + Sync + std::error::Error,
+        {
+            if !self.get_path().join(f).exists() {
+                return Ok(None);
+            }
+            self.open_path(f, false).and_then(read_from).map(Some)
+        }
+
+        /// Same as `get_max_value()`, but returns `None` if the controller doesn't have `f`.
+        fn max_value_if_exists(&self, f: &str) -> Result<Option<MaxValue>> {
+            if !self.get_path().join(f).exists() {
+                return Ok(None);
+            }
+            self.get_max_value(f).map(Some)
+        }
+
+        /// Writes `value` to the file `p`, then reads it back and returns what the kernel
+        /// actually stored, which may differ if the value got clamped or rounded.
+        ///
+        /// Returns `None` for write-only files, which can't be read back.
+        fn write_and_verify(&self, p: &str, value: &str) -> Result<Option<String>> {
+            self.write_file(p, value)?;
+
+            let readable = fs::metadata(self.get_path().join(p))
+                .map(|m| m.permissions().mode() & 0o444 != 0)
+                .unwrap_or(false);
+            if !readable {
+                return Ok(None);
+            }
+
+            self.open_path(p, false)
+                .and_then(read_string_from)
+                .map(Some)
+        }
+
         #[doc(hidden)]
         fn path_exists(&self, p: &str) -> bool {
             if self.verify_path().is_err() {
@@ -224,11 +440,7 @@ mod sealed {
 
     pub trait CustomizedAttribute: ControllerInternal {
         fn set(&self, key: &str, value: &str) -> Result<()> {
-            self.open_path(key, true).and_then(|mut file| {
-                file.write_all(value.as_ref()).map_err(|e| {
-                    Error::with_cause(WriteFailed(key.to_string(), value.to_string()), e)
-                })
-            })
+            self.write_file(key, value)
         }
 
         #[allow(dead_code)]
@@ -264,6 +476,45 @@ pub trait Controller {
     /// kernel the information.
     fn apply(&self, res: &Resources) -> Result<()>;
 
+    /// Reset the settings of the controller to their defaults, e.g. to reuse the control group
+    /// without recreating it.
+    ///
+    /// The files written are:
+    /// - memory: `memory.memsw.limit_in_bytes` (if present), `memory.limit_in_bytes` and
+    ///   `memory.soft_limit_in_bytes` to -1 on v1; `memory.swap.max` (if present),
+    ///   `memory.high` and `memory.max` to `max`, `memory.low` and `memory.min` to 0 on v2.
+    /// - cpu: `cpu.shares` to 1024, `cpu.cfs_quota_us` to -1 and `cpu.cfs_period_us` to 100000
+    ///   on v1; `cpu.weight` to 100 and `cpu.max` to `max 100000` on v2.
+    /// - cpuset: `cpuset.cpus` and `cpuset.mems` to the values of the parent on v1, and emptied
+    ///   to use the ones of the parent on v2.
+    /// - pids: `pids.max` to `max`.
+    /// - blkio: the rules of `blkio.throttle.{read,write}_{bps,iops}_device` are removed on v1,
+    ///   and the ones of `io.max` set to `max` on v2. Weights are left untouched, their default
+    ///   depends on the I/O scheduler.
+    /// - devices: `a *:* rwm` is written to `devices.allow`, which drops the other rules.
+    /// - hugetlb: `hugetlb.<size>.limit_in_bytes` to -1 on v1, `hugetlb.<size>.max` to `max` on
+    ///   v2, for every hugepage size.
+    /// - net_cls: `net_cls.classid` to 0.
+    /// - net_prio: the priority of every interface in `net_prio.ifpriomap` to 0.
+    /// - rdma: the limits of every device in `rdma.max` to `max`.
+    /// - freezer: the control group is thawed.
+    ///
+    /// Nothing is done for the other controllers, which have no settings.
+    fn reset(&self) -> Result<()>;
+
+    /// Fills the section of `res` of the controller with its current settings, the counterpart
+    /// of `apply()`. Settings whose file doesn't exist, e.g. on the root control group, are left
+    /// as `None`.
+    fn read_resources(&self, res: &mut Resources) -> Result<()>;
+
+    /// The control files the controller knows about, the settings first and then the
+    /// statistics, depending on the cgroup version. They may not all exist, e.g. the kernel
+    /// doesn't provide the kernel memory files of cgroup v1 anymore.
+    ///
+    /// The hugetlb files are given once with a `<size>` placeholder, to be replaced with each
+    /// size returned by `HugeTlbController::get_sizes()`.
+    fn managed_files(&self) -> &'static [&'static str];
+
     /// Create this controller
     fn create(&self);
 
@@ -285,21 +536,75 @@ pub trait Controller {
     /// Attach a task to this controller.
     fn add_task_by_tgid(&self, pid: &CgroupPid) -> Result<()>;
 
+    /// Attach processes to this controller by thread group id, opening `cgroup.procs` once for
+    /// all of them. The processes that couldn't be attached are returned with the error.
+    fn add_tasks_by_tgid(&self, pids: &[CgroupPid]) -> Result<Vec<(CgroupPid, Error)>>;
+
     /// set cgroup type.
     fn set_cgroup_type(&self, cgroup_type: &str) -> Result<()>;
 
     /// get cgroup type.
     fn get_cgroup_type(&self) -> Result<String>;
 
+    /// Write a value to a file of this controller, then read back what the kernel stored.
+    ///
+    /// The kernel silently clamps or rounds some values (e.g. memory limits are rounded to the
+    /// page size), the returned value lets the caller detect it. `None` is returned for
+    /// write-only files.
+    fn write_and_verify(&self, file: &str, value: &str) -> Result<Option<String>>;
+
     /// Get the list of tasks that this controller has.
     fn tasks(&self) -> Vec<CgroupPid>;
 
     /// Get the list of procs that this controller has.
     fn procs(&self) -> Vec<CgroupPid>;
 
+    /// Same as `tasks()`, but reads the list lazily.
+    fn tasks_iter(&self) -> PidIter;
+
+    /// Same as `procs()`, but reads the list lazily.
+    fn procs_iter(&self) -> PidIter;
+
     fn v2(&self) -> bool;
 }
 
+/// An iterator over the pids listed in a `cgroup.procs`, `cgroup.threads` or `tasks` file,
+/// reading the file as it goes rather than all at once.
+///
+/// The iteration stops at the first read error, and lines that aren't a pid are skipped.
+#[derive(Debug)]
+pub struct PidIter {
+    lines: Option<std::io::Lines<BufReader<File>>>,
+}
+
+impl PidIter {
+    fn new(file: Option<File>) -> Self {
+        PidIter {
+            lines: file.map(|f| BufReader::new(f).lines()),
+        }
+    }
+}
+
+impl Iterator for PidIter {
+    type Item = CgroupPid;
+
+    fn next(&mut self) -> Option<CgroupPid> {
+        loop {
+            match self.lines.as_mut()?.next() {
+                Some(Ok(line)) => {
+                    if let Ok(pid) = line.trim().parse::<u64>() {
+                        return Some(CgroupPid::from(pid));
+                    }
+                }
+                _ => {
+                    self.lines = None;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
 impl<T> Controller for T
 where
     T: ControllerInternal,
@@ -322,6 +627,21 @@ where
         ControllerInternal::apply(self, res)
     }
 
+    /// The control files the controller knows about.
+    fn managed_files(&self) -> &'static [&'static str] {
+        ControllerInternal::managed_files(self)
+    }
+
+    /// Reset the settings of the controller to their defaults.
+    fn reset(&self) -> Result<()> {
+        ControllerInternal::reset(self)
+    }
+
+    /// Read back the settings of the controller into `res`.
+    fn read_resources(&self, res: &mut Resources) -> Result<()> {
+        ControllerInternal::read_resources(self, res)
+    }
+
     /// Create this controller
     fn create(&self) {
         self.verify_path()
@@ -336,7 +656,10 @@ where
     /// Set notify_on_release
     fn set_notify_on_release(&self, enable: bool) -> Result<()> {
         if self.is_v2() {
-            return Err(Error::new(ErrorKind::CgroupVersion));
+            return Err(Error::not_supported(
+                "notify_on_release",
+                "only available on cgroup v1",
+            ));
         }
         self.open_path("notify_on_release", true)
             .and_then(|mut file| {
@@ -352,7 +675,10 @@ where
     /// Set release_agent
     fn set_release_agent(&self, path: &str) -> Result<()> {
         if self.is_v2() {
-            return Err(Error::new(ErrorKind::CgroupVersion));
+            return Err(Error::not_supported(
+                "release_agent",
+                "only available on cgroup v1",
+            ));
         }
         self.open_path("release_agent", true).and_then(|mut file| {
             file.write_all(path.as_bytes()).map_err(|e| {
@@ -370,6 +696,7 @@ where
 
     /// Delete the controller.
     fn delete(&self) -> Result<()> {
+        cache_handles(std::iter::once(self.get_path().as_path()), false);
         if !self.get_path().exists() {
             return Ok(());
         }
@@ -399,78 +726,70 @@ where
         if self.is_v2() {
             file_name = "cgroup.threads";
         }
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(pid.pid.to_string().as_ref()).map_err(|e| {
-                Error::with_cause(
-                    ErrorKind::WriteFailed(file_name.to_string(), pid.pid.to_string()),
-                    e,
-                )
-            })
-        })
+        self.write_file(file_name, &pid.pid.to_string())
     }
 
     /// Attach a task to this controller by thread group id.
     fn add_task_by_tgid(&self, pid: &CgroupPid) -> Result<()> {
         let file_name = "cgroup.procs";
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(pid.pid.to_string().as_ref()).map_err(|e| {
-                Error::with_cause(
-                    ErrorKind::WriteFailed(file_name.to_string(), pid.pid.to_string()),
-                    e,
-                )
+        self.write_file(file_name, &pid.pid.to_string())
+    }
+
+    /// Attach processes to this controller by thread group id.
+    fn add_tasks_by_tgid(&self, pids: &[CgroupPid]) -> Result<Vec<(CgroupPid, Error)>> {
+        let file_name = "cgroup.procs";
+        let mut file = self.open_path(file_name, true)?;
+        // the kernel takes one pid per write, so each one succeeds or fails on its own
+        Ok(pids
+            .iter()
+            .filter_map(|pid| {
+                file.write_all(pid.pid.to_string().as_ref())
+                    .map_err(|e| {
+                        let kind =
+                            ErrorKind::WriteFailed(file_name.to_string(), pid.pid.to_string());
+                        (*pid, Error::with_cause(kind, e))
+                    })
+                    .err()
             })
-        })
+            .collect())
+    }
+
+    /// Write a value to a file of this controller, then read back what the kernel stored.
+    fn write_and_verify(&self, file: &str, value: &str) -> Result<Option<String>> {
+        ControllerInternal::write_and_verify(self, file, value)
     }
 
     /// Get the list of procs that this controller has.
     fn procs(&self) -> Vec<CgroupPid> {
-        let file_name = "cgroup.procs";
-        self.open_path(file_name, false)
-            .map(|file| {
-                let bf = BufReader::new(file);
-                let mut v = Vec::new();
-                for line in bf.lines() {
-                    match line {
-                        Ok(line) => {
-                            let n = line.trim().parse().unwrap_or(0u64);
-                            v.push(n);
-                        }
-                        Err(_) => break,
-                    }
-                }
-                v.into_iter().map(CgroupPid::from).collect()
-            })
-            .unwrap_or_default()
+        self.procs_iter().collect()
     }
 
     /// Get the list of tasks that this controller has.
     fn tasks(&self) -> Vec<CgroupPid> {
+        self.tasks_iter().collect()
+    }
+
+    /// Same as `procs()`, but reads the list lazily.
+    fn procs_iter(&self) -> PidIter {
+        PidIter::new(self.open_path("cgroup.procs", false).ok())
+    }
+
+    /// Same as `tasks()`, but reads the list lazily.
+    fn tasks_iter(&self) -> PidIter {
         let mut file_name = "tasks";
         if self.is_v2() {
             file_name = "cgroup.threads";
         }
-        self.open_path(file_name, false)
-            .map(|file| {
-                let bf = BufReader::new(file);
-                let mut v = Vec::new();
-                for line in bf.lines() {
-                    match line {
-                        Ok(line) => {
-                            let n = line.trim().parse().unwrap_or(0u64);
-                            v.push(n);
-                        }
-                        Err(_) => break,
-                    }
-                }
-                v.into_iter().map(CgroupPid::from).collect()
-            })
-            .unwrap_or_default()
+        PidIter::new(self.open_path(file_name, false).ok())
     }
 
     /// set cgroup.type
     fn set_cgroup_type(&self, cgroup_type: &str) -> Result<()> {
         if !self.is_v2() {
-            return Err(Error::new(ErrorKind::CgroupVersion));
+            return Err(Error::not_supported(
+                "cgroup.type",
+                "only available on cgroup v2",
+            ));
         }
         let file_name = "cgroup.type";
         self.open_path(file_name, true).and_then(|mut file| {
@@ -486,7 +805,10 @@ where
     /// get cgroup.type
     fn get_cgroup_type(&self) -> Result<String> {
         if !self.is_v2() {
-            return Err(Error::new(ErrorKind::CgroupVersion));
+            return Err(Error::not_supported(
+                "cgroup.type",
+                "only available on cgroup v2",
+            ));
         }
         let file_name = "cgroup.type";
         self.open_path(file_name, false).and_then(|mut file: File| {
@@ -545,6 +867,12 @@ pub trait Hierarchy: std::fmt::Debug + Send + Sync {
     /// Returns the root directory of the hierarchy.
     fn root(&self) -> PathBuf;
 
+    /// Same as `root()`, but fails if the hierarchy isn't mounted instead of returning a default
+    /// directory in which the operations on the control groups would fail later.
+    fn try_root(&self) -> Result<PathBuf> {
+        Ok(self.root())
+    }
+
     /// Return a handle to the root control group in the hierarchy.
     fn root_control_group(&self) -> Cgroup;
 
@@ -568,7 +896,17 @@ pub struct MemoryResources {
     /// How much of the kernel's memory (in bytes) can be used for TCP-related buffers.
     pub kernel_tcp_memory_limit: Option<i64>,
     /// How much memory and swap together can the tasks in the control group use.
+    ///
+    /// On cgroup v2, where swap is limited apart from memory, `memory.swap.max` is set to this
+    /// limit minus `memory_hard_limit` (or the current `memory.max`), and nothing is set if that
+    /// is negative. `-1` leaves the swap unlimited.
     pub memory_swap_limit: Option<i64>,
+    /// How much swap alone can the tasks in the control group use, written to `memory.swap.max`
+    /// on cgroup v2 after `memory_swap_limit`, and ignored on v1.
+    pub swap_max: Option<MaxValue>,
+    /// The throttling limit of the tasks in the control group, written to `memory.high` on
+    /// cgroup v2 and ignored on v1.
+    pub memory_high: Option<MaxValue>,
     /// Controls the tendency of the kernel to swap out parts of the address space of the tasks to
     /// disk. Lower value implies less likely.
     ///
@@ -612,8 +950,14 @@ pub struct CpuResources {
     // cpu
     /// Weight of how much of the total CPU time should this control group get. Note that this is
     /// hierarchical, so this is weighted against the siblings of this control group.
+    ///
+    /// These are v1 shares, in `[2, 262144]`. On cgroup v2 they are converted to a `cpu.weight`
+    /// with `cpu::shares_to_weight()`, and converted back with `cpu::weight_to_shares()` when
+    /// read.
     pub shares: Option<u64>,
-    /// In one `period`, how much can the tasks run in microseconds.
+    /// In one `period`, how much can the tasks run in microseconds, `-1` for no limit.
+    ///
+    /// Written to `cpu.max` along with the period on cgroup v2.
     pub quota: Option<i64>,
     /// Period of time in microseconds.
     pub period: Option<u64>,
@@ -647,6 +991,35 @@ pub struct DeviceResource {
     pub access: Vec<crate::fs::devices::DevicePermissions>,
 }
 
+impl DeviceResource {
+    /// A rule allowing only to create device nodes of the device with `mknod(2)`, without
+    /// reading from or writing to the device.
+    pub fn mknod_only(devtype: crate::fs::devices::DeviceType, major: i64, minor: i64) -> Self {
+        Self {
+            allow: true,
+            devtype,
+            major,
+            minor,
+            access: vec![crate::fs::devices::DevicePermissions::MkNod],
+        }
+    }
+
+    /// A rule allowing to read from and write to the device. Creating device nodes additionally
+    /// needs the `MkNod` permission, see `mknod_only()`.
+    pub fn rw(devtype: crate::fs::devices::DeviceType, major: i64, minor: i64) -> Self {
+        Self {
+            allow: true,
+            devtype,
+            major,
+            minor,
+            access: vec![
+                crate::fs::devices::DevicePermissions::Read,
+                crate::fs::devices::DevicePermissions::Write,
+            ],
+        }
+    }
+}
+
 /// Limit the usage of devices for the control group's tasks.
 #[derive(Debug, Clone, Eq, PartialEq, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -697,6 +1070,42 @@ pub struct HugePageResources {
     pub limits: Vec<HugePageResource>,
 }
 
+/// A limit of a misc resource, e.g. the AMD SEV ASIDs, for the control group.
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MiscResource {
+    /// The name of the resource, as listed in `misc.capacity`, e.g. `sev`.
+    pub key: String,
+    /// The maximum amount of the resource the tasks of the control group can use.
+    pub max: MaxValue,
+}
+
+/// Provides the ability to limit the misc resources (cgroup v2 only).
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MiscResources {
+    /// Set a limit for each misc resource.
+    pub limits: Vec<MiscResource>,
+}
+
+/// The RDMA/IB resources limits of a device for the control group.
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RdmaResource {
+    /// The name of the device, as listed in `rdma.max`, e.g. `mlx4_0`.
+    pub device: String,
+    /// The maximum amount of the RDMA/IB resources the tasks of the control group can use.
+    pub limit: RdmaLimit,
+}
+
+/// Provides the ability to limit the RDMA/IB resources of each device.
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RdmaResources {
+    /// Set a limit for each device.
+    pub limits: Vec<RdmaResource>,
+}
+
 /// Weight for a particular block device.
 #[derive(Debug, Clone, Eq, PartialEq, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -770,6 +1179,133 @@ pub struct Resources {
     pub hugepages: HugePageResources,
     /// Block device I/O related limits.
     pub blkio: BlkIoResources,
+    /// Misc resources related limits.
+    pub misc: MiscResources,
+    /// RDMA/IB resources related limits.
+    pub rdma: RdmaResources,
+}
+
+impl Resources {
+    /// Layers `other` on top of these resources.
+    ///
+    /// Limits set in `other` (`Some`) override the ones in `self`, and customized attributes are
+    /// overridden key by key. Device rules of `other` are appended after the ones of `self`,
+    /// except those already there, which keep their position so that the order of the allow and
+    /// deny rules doesn't change. Other lists hold one entry per hugepage size, network
+    /// interface, block device, misc resource or RDMA device, those of `other` replacing the ones
+    /// of `self` in place for the same key and being appended otherwise.
+    pub fn merge(&mut self, other: &Resources) {
+        let memory = &other.memory;
+        merge_option(
+            &mut self.memory.kernel_memory_limit,
+            &memory.kernel_memory_limit,
+        );
+        merge_option(
+            &mut self.memory.memory_hard_limit,
+            &memory.memory_hard_limit,
+        );
+        merge_option(
+            &mut self.memory.memory_soft_limit,
+            &memory.memory_soft_limit,
+        );
+        merge_option(
+            &mut self.memory.kernel_tcp_memory_limit,
+            &memory.kernel_tcp_memory_limit,
+        );
+        merge_option(
+            &mut self.memory.memory_swap_limit,
+            &memory.memory_swap_limit,
+        );
+        merge_option(&mut self.memory.swap_max, &memory.swap_max);
+        merge_option(&mut self.memory.memory_high, &memory.memory_high);
+        merge_option(&mut self.memory.swappiness, &memory.swappiness);
+        merge_attrs(&mut self.memory.attrs, &memory.attrs);
+
+        merge_option(
+            &mut self.pid.maximum_number_of_processes,
+            &other.pid.maximum_number_of_processes,
+        );
+
+        let cpu = &other.cpu;
+        merge_option(&mut self.cpu.cpus, &cpu.cpus);
+        merge_option(&mut self.cpu.mems, &cpu.mems);
+        merge_option(&mut self.cpu.shares, &cpu.shares);
+        merge_option(&mut self.cpu.quota, &cpu.quota);
+        merge_option(&mut self.cpu.period, &cpu.period);
+        merge_option(&mut self.cpu.realtime_runtime, &cpu.realtime_runtime);
+        merge_option(&mut self.cpu.realtime_period, &cpu.realtime_period);
+        merge_attrs(&mut self.cpu.attrs, &cpu.attrs);
+
+        merge_keyed(&mut self.devices.devices, &other.devices.devices, |d| {
+            d.clone()
+        });
+
+        merge_option(&mut self.network.class_id, &other.network.class_id);
+        merge_keyed(
+            &mut self.network.priorities,
+            &other.network.priorities,
+            |p| p.name.clone(),
+        );
+
+        merge_keyed(&mut self.hugepages.limits, &other.hugepages.limits, |l| {
+            l.size.clone()
+        });
+
+        let blkio = &other.blkio;
+        merge_option(&mut self.blkio.weight, &blkio.weight);
+        merge_option(&mut self.blkio.leaf_weight, &blkio.leaf_weight);
+        merge_keyed(&mut self.blkio.weight_device, &blkio.weight_device, |d| {
+            (d.major, d.minor)
+        });
+        merge_keyed(
+            &mut self.blkio.throttle_read_bps_device,
+            &blkio.throttle_read_bps_device,
+            |d| (d.major, d.minor),
+        );
+        merge_keyed(
+            &mut self.blkio.throttle_read_iops_device,
+            &blkio.throttle_read_iops_device,
+            |d| (d.major, d.minor),
+        );
+        merge_keyed(
+            &mut self.blkio.throttle_write_bps_device,
+            &blkio.throttle_write_bps_device,
+            |d| (d.major, d.minor),
+        );
+        merge_keyed(
+            &mut self.blkio.throttle_write_iops_device,
+            &blkio.throttle_write_iops_device,
+            |d| (d.major, d.minor),
+        );
+        merge_attrs(&mut self.blkio.attrs, &blkio.attrs);
+
+        merge_keyed(&mut self.misc.limits, &other.misc.limits, |l| l.key.clone());
+
+        merge_keyed(&mut self.rdma.limits, &other.rdma.limits, |l| {
+            l.device.clone()
+        });
+    }
+}
+
+fn merge_option<T: Clone>(dst: &mut Option<T>, src: &Option<T>) {
+    if src.is_some() {
+        dst.clone_from(src);
+    }
+}
+
+fn merge_attrs(dst: &mut HashMap<String, String>, src: &HashMap<String, String>) {
+    dst.extend(src.iter().map(|(k, v)| (k.clone(), v.clone())));
+}
+
+// Appends the entries of `src` to `dst`, removing the entries of `dst` with the same key first.
+fn merge_keyed<T: Clone, K: PartialEq>(dst: &mut Vec<T>, src: &[T], key: impl Fn(&T) -> K) {
+    for entry in src {
+        let k = key(entry);
+        match dst.iter_mut().find(|e| key(e) == k) {
+            Some(e) => *e = entry.clone(),
+            None => dst.push(entry.clone()),
+        }
+    }
 }
 
 impl Subsystem {
@@ -827,6 +1363,10 @@ impl Subsystem {
                 cont.get_path_mut().push(path);
                 cont
             }),
+            Subsystem::Misc(mut cont) => Subsystem::Misc({
+                cont.get_path_mut().push(path);
+                cont
+            }),
             Subsystem::Systemd(mut cont) => Subsystem::Systemd({
                 cont.get_path_mut().push(path);
                 cont
@@ -849,6 +1389,7 @@ impl Subsystem {
             Subsystem::NetPrio(cont) => cont,
             Subsystem::HugeTlb(cont) => cont,
             Subsystem::Rdma(cont) => cont,
+            Subsystem::Misc(cont) => cont,
             Subsystem::Systemd(cont) => cont,
         }
     }
@@ -894,6 +1435,55 @@ impl fmt::Display for MaxValue {
     }
 }
 
+/// Parses a human readable size such as `"512M"` or `"2GB"` into a number of bytes.
+///
+/// The number must be a non-negative integer, directly followed by an optional, case sensitive,
+/// unit:
+///
+/// * none or `B`: bytes.
+/// * `K`, `M`, `G`, `T`, `P` (or their lowercase) and `KiB`, `MiB`, `GiB`, `TiB`, `PiB`: binary
+///   multiples, powers of 1024. A single letter is binary, as understood by the kernel.
+/// * `KB` (or `kB`), `MB`, `GB`, `TB`, `PB`: SI multiples, powers of 1000.
+///
+/// Any other input, including sizes that don't fit in a `u64`, fails with `InvalidBytesSize`.
+pub fn parse_size(s: &str) -> Result<u64> {
+    use crate::fs::hugetlb::{GiB, KiB, MiB, PiB, TiB, GB, KB, MB, PB, TB};
+
+    let s = s.trim();
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let multiplier = match unit {
+        "" | "B" => 1,
+        "K" | "k" | "KiB" => KiB,
+        "M" | "m" | "MiB" => MiB,
+        "G" | "g" | "GiB" => GiB,
+        "T" | "t" | "TiB" => TiB,
+        "P" | "p" | "PiB" => PiB,
+        "KB" | "kB" => KB,
+        "MB" => MB,
+        "GB" => GB,
+        "TB" => TB,
+        "PB" => PB,
+        _ => return Err(Error::new(InvalidBytesSize)),
+    };
+
+    number
+        .parse::<u128>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .and_then(|n| u64::try_from(n).ok())
+        .ok_or_else(|| Error::new(InvalidBytesSize))
+}
+
+/// Parses a limit given as a human readable size (see `parse_size()`), or `"max"` for no limit
+/// which is returned as -1.
+pub(crate) fn parse_limit(s: &str) -> Result<i64> {
+    if s.trim() == "max" {
+        return Ok(-1);
+    }
+
+    parse_size(s).and_then(|n| i64::try_from(n).map_err(|_| Error::new(InvalidBytesSize)))
+}
+
 pub fn parse_max_value(s: &str) -> Result<MaxValue> {
     if s.trim() == "max" {
         return Ok(MaxValue::Max);
@@ -904,6 +1494,67 @@ pub fn parse_max_value(s: &str) -> Result<MaxValue> {
     }
 }
 
+/// The pressure stall information of one line of a `*.pressure` file (cgroup v2 only).
+#[derive(PartialEq, Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PressureData {
+    /// The share of time stalled, in percent, averaged over the last 10 seconds.
+    pub avg10: f64,
+    /// Same as `avg10`, over the last 60 seconds.
+    pub avg60: f64,
+    /// Same as `avg10`, over the last 300 seconds.
+    pub avg300: f64,
+    /// The total time stalled, in microseconds.
+    pub total: u64,
+}
+
+/// The pressure stall information of a resource, as found in `cpu.pressure`, `memory.pressure`
+/// and `io.pressure` (cgroup v2 only).
+#[derive(PartialEq, Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pressure {
+    /// The time at least one task was stalled on the resource.
+    pub some: PressureData,
+    /// The time all the non-idle tasks were stalled at once, `None` if the file has no `full`
+    /// line, e.g. `cpu.pressure` on kernels older than 5.13.
+    pub full: Option<PressureData>,
+}
+
+/// Parses the content of a `*.pressure` file, which must have a `some` line.
+pub(crate) fn parse_pressure(s: &str) -> Result<Pressure> {
+    let mut some = None;
+    let mut full = None;
+    for line in s.lines() {
+        let mut fields = line.split_whitespace();
+        let slot = match fields.next() {
+            Some("some") => &mut some,
+            Some("full") => &mut full,
+            _ => continue,
+        };
+        let mut data = PressureData::default();
+        for (key, value) in fields.filter_map(|f| f.split_once('=')) {
+            let parse_err = |e| Error::with_cause(ParseError, e);
+            match key {
+                "avg10" => data.avg10 = value.parse().map_err(parse_err)?,
+                "avg60" => data.avg60 = value.parse().map_err(parse_err)?,
+                "avg300" => data.avg300 = value.parse().map_err(parse_err)?,
+                "total" => {
+                    data.total = value
+                        .parse()
+                        .map_err(|e| Error::with_cause(ParseError, e))?
+                }
+                _ => {}
+            }
+        }
+        *slot = Some(data);
+    }
+
+    Ok(Pressure {
+        some: some.ok_or_else(|| Error::new(ParseError))?,
+        full,
+    })
+}
+
 // Flat keyed
 //  KEY0 VAL0\n
 //  KEY1 VAL1\n
@@ -1011,3 +1662,176 @@ fn read_u64_from(file: File) -> Result<u64> {
 fn read_i64_from(file: File) -> Result<i64> {
     read_from::<i64>(file)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::devices::{DevicePermissions, DeviceType};
+    use crate::fs::pid::PidController;
+    use crate::fs::{
+        cache_handles, cached_handle, parse_limit, parse_pressure, parse_size, ControllerInternal,
+        DeviceResource, HugePageResource, MaxValue, PressureData, Resources,
+    };
+    use std::os::unix::io::AsRawFd;
+    use std::process::Command;
+
+    #[test]
+    fn test_open_path_cloexec() {
+        let dir = std::env::temp_dir().join(format!("cgroups-rs-cloexec-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let controller = PidController::new(dir.clone(), dir.clone(), false);
+        for w in [true, false] {
+            let file = controller.open_path("pids.max", w).unwrap();
+            let status = Command::new("sh")
+                .arg("-c")
+                .arg(format!("test -e /proc/self/fd/{}", file.as_raw_fd()))
+                .status()
+                .unwrap();
+            assert!(!status.success());
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cached_handles_eviction() {
+        let dir = std::env::temp_dir().join(format!("cgroups-rs-handles-{}", std::process::id()));
+        let child = dir.join("child");
+        std::fs::create_dir_all(&child).unwrap();
+
+        let controller = PidController::new(child.clone(), dir.clone(), false);
+        cache_handles(std::iter::once(child.as_path()), true);
+        controller.write_file("pids.max", "10").unwrap();
+        controller.write_file("pids.max", "20").unwrap();
+        assert!(cached_handle(&child, "pids.max").is_some());
+        assert_eq!(
+            std::fs::read_to_string(child.join("pids.max")).unwrap(),
+            "1020"
+        );
+
+        // releasing a control group closes the handles of its descendants too
+        cache_handles(std::iter::once(dir.as_path()), false);
+        assert!(cached_handle(&child, "pids.max").is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_pressure() {
+        let pressure = parse_pressure(
+            "some avg10=1.50 avg60=0.12 avg300=0.05 total=5678\n\
+             full avg10=0.00 avg60=0.03 avg300=0.01 total=1234\n",
+        )
+        .unwrap();
+        assert_eq!(
+            pressure.some,
+            PressureData {
+                avg10: 1.5,
+                avg60: 0.12,
+                avg300: 0.05,
+                total: 5678
+            }
+        );
+        assert_eq!(pressure.full.unwrap().total, 1234);
+
+        // cpu.pressure has no full line on older kernels
+        let pressure = parse_pressure("some avg10=0.00 avg60=0.00 avg300=0.00 total=42\n").unwrap();
+        assert_eq!(pressure.some.total, 42);
+        assert_eq!(pressure.full, None);
+
+        assert!(parse_pressure("").is_err());
+        assert!(parse_pressure("some avg10=x avg60=0.00 avg300=0.00 total=42\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("0").unwrap(), 0);
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("4096B").unwrap(), 4096);
+        assert_eq!(parse_size(" 512M\n").unwrap(), 512 << 20);
+        assert_eq!(parse_size("512m").unwrap(), 512 << 20);
+        assert_eq!(parse_size("512MiB").unwrap(), 512 << 20);
+        assert_eq!(parse_size("512MB").unwrap(), 512_000_000);
+        assert_eq!(parse_size("2G").unwrap(), 2 << 30);
+        assert_eq!(parse_size("2GB").unwrap(), 2_000_000_000);
+        assert_eq!(parse_size("1kB").unwrap(), 1000);
+        assert_eq!(parse_size("1K").unwrap(), 1024);
+
+        for garbage in [
+            "", "M", "-1M", "1.5G", "1 G", "1Mb", "1mb", "1MIB", "max", "12x",
+        ] {
+            assert!(
+                parse_size(garbage).is_err(),
+                "{:?} should be rejected",
+                garbage
+            );
+        }
+        assert!(parse_size("17179869184G").is_err());
+
+        assert_eq!(parse_limit("max").unwrap(), -1);
+        assert_eq!(parse_limit("1G").unwrap(), 1 << 30);
+        assert!(parse_limit("16383P").is_err());
+    }
+
+    #[test]
+    fn test_resources_merge_override() {
+        let mut base = Resources::default();
+        base.memory.memory_hard_limit = Some(1024);
+        base.memory.swappiness = Some(60);
+        base.cpu.shares = Some(100);
+        base.cpu.attrs.insert("cpu.a".to_string(), "1".to_string());
+        base.cpu.attrs.insert("cpu.b".to_string(), "1".to_string());
+
+        let mut overrides = Resources::default();
+        overrides.memory.memory_hard_limit = Some(2048);
+        overrides.pid.maximum_number_of_processes = Some(MaxValue::Value(10));
+        overrides
+            .cpu
+            .attrs
+            .insert("cpu.b".to_string(), "2".to_string());
+
+        base.merge(&overrides);
+        assert_eq!(base.memory.memory_hard_limit, Some(2048));
+        assert_eq!(base.memory.swappiness, Some(60));
+        assert_eq!(base.cpu.shares, Some(100));
+        assert_eq!(
+            base.pid.maximum_number_of_processes,
+            Some(MaxValue::Value(10))
+        );
+        assert_eq!(base.cpu.attrs["cpu.a"], "1");
+        assert_eq!(base.cpu.attrs["cpu.b"], "2");
+    }
+
+    #[test]
+    fn test_resources_merge_append() {
+        let device = |allow, major| DeviceResource {
+            allow,
+            devtype: DeviceType::Char,
+            major,
+            minor: 0,
+            access: vec![DevicePermissions::Read],
+        };
+        let hugepage = |size: &str, limit| HugePageResource {
+            size: size.to_string(),
+            limit,
+        };
+
+        let mut base = Resources::default();
+        base.devices.devices = vec![device(false, 1), device(true, 2)];
+        base.hugepages.limits = vec![hugepage("2MB", 1), hugepage("1GB", 1)];
+
+        let mut overrides = Resources::default();
+        overrides.devices.devices = vec![device(true, 3), device(false, 1)];
+        overrides.hugepages.limits = vec![hugepage("2MB", 2)];
+
+        base.merge(&overrides);
+        assert_eq!(
+            base.devices.devices,
+            vec![device(false, 1), device(true, 2), device(true, 3)]
+        );
+        assert_eq!(
+            base.hugepages.limits,
+            vec![hugepage("2MB", 2), hugepage("1GB", 1)]
+        );
+    }
+}
@@ -7,6 +7,7 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/net_cls.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/net_cls.txt)
+use std::convert::TryFrom;
 use std::io::Write;
 use std::path::PathBuf;
 
@@ -29,10 +30,23 @@ pub struct NetClsController {
     path: PathBuf,
 }
 
+// The control files used by the controller on cgroup v1, see `Controller::managed_files()`.
+const MANAGED_FILES_V1: &[&str] = &["net_cls.classid"];
+
+// The control files used by the controller on cgroup v2.
+const MANAGED_FILES_V2: &[&str] = &[];
+
 impl ControllerInternal for NetClsController {
     fn control_type(&self) -> Controllers {
         Controllers::NetCls
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        if self.is_v2() {
+            MANAGED_FILES_V2
+        } else {
+            MANAGED_FILES_V1
+        }
+    }
     fn get_path(&self) -> &PathBuf {
         &self.path
     }
@@ -51,6 +65,15 @@ impl ControllerInternal for NetClsController {
 
         Ok(())
     }
+
+    fn reset(&self) -> Result<()> {
+        self.set_class(0)
+    }
+
+    fn read_resources(&self, res: &mut Resources) -> Result<()> {
+        res.network.class_id = self.read_if_exists("net_cls.classid")?;
+        Ok(())
+    }
 }
 
 impl ControllIdentifier for NetClsController {
@@ -59,17 +82,15 @@ impl ControllIdentifier for NetClsController {
     }
 }
 
-impl<'a> From<&'a Subsystem> for &'a NetClsController {
-    fn from(sub: &'a Subsystem) -> &'a NetClsController {
-        unsafe {
-            match sub {
-                Subsystem::NetCls(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a NetClsController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a NetClsController> {
+        match sub {
+            Subsystem::NetCls(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                NetClsController::controller_type().to_string(),
+            ))),
         }
     }
 }
@@ -84,6 +105,10 @@ impl NetClsController {
     }
 
     /// Set the network class id of the outgoing packets of the control group's tasks.
+    ///
+    /// The class id packs a traffic control handle as `0xAAAABBBB`, `AAAA` being the major
+    /// number and `BBBB` the minor one, see `set_class_major_minor()`. Writing `0` detaches the
+    /// control group from any class.
     pub fn set_class(&self, class: u64) -> Result<()> {
         self.open_path("net_cls.classid", true)
             .and_then(|mut file| {
@@ -99,4 +124,39 @@ impl NetClsController {
         self.open_path("net_cls.classid", false)
             .and_then(read_u64_from)
     }
+
+    /// Set the network class id of the outgoing packets of the control group's tasks from the
+    /// `major:minor` traffic control handle, e.g. `10:1` for the class id `0x100001`.
+    pub fn set_class_major_minor(&self, major: u16, minor: u16) -> Result<()> {
+        self.set_class(pack_class(major, minor))
+    }
+
+    /// Get the network class id of the outgoing packets of the control group's tasks as its
+    /// `(major, minor)` traffic control handle, `(0, 0)` if the control group has no class.
+    pub fn get_class_major_minor(&self) -> Result<(u16, u16)> {
+        self.get_class().map(unpack_class)
+    }
+}
+
+fn pack_class(major: u16, minor: u16) -> u64 {
+    (u64::from(major) << 16) | u64::from(minor)
+}
+
+fn unpack_class(class: u64) -> (u16, u16) {
+    ((class >> 16) as u16, class as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_class_major_minor() {
+        assert_eq!(pack_class(0x10, 0x1), 0x100001);
+        assert_eq!(pack_class(0xffff, 0xffff), 0xffff_ffff);
+        assert_eq!(pack_class(0, 0), 0);
+        for (major, minor) in [(0, 0), (1, 0), (0, 1), (0x10, 0x1), (0xffff, 0xffff)] {
+            assert_eq!(unpack_class(pack_class(major, minor)), (major, minor));
+        }
+    }
 }
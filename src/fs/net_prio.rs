@@ -8,15 +8,16 @@
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/net_prio.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/net_prio.txt)
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Write};
+use std::convert::TryFrom;
 use std::path::PathBuf;
 
 use crate::fs::error::ErrorKind::*;
 use crate::fs::error::*;
 
-use crate::fs::read_u64_from;
+use crate::fs::{read_string_from, read_u64_from};
 use crate::fs::{
-    ControllIdentifier, ControllerInternal, Controllers, NetworkResources, Resources, Subsystem,
+    ControllIdentifier, ControllerInternal, Controllers, NetworkPriority, NetworkResources,
+    Resources, Subsystem,
 };
 
 /// A controller that allows controlling the `net_prio` subsystem of a Cgroup.
@@ -30,10 +31,23 @@ pub struct NetPrioController {
     path: PathBuf,
 }
 
+// The control files used by the controller on cgroup v1, see `Controller::managed_files()`.
+const MANAGED_FILES_V1: &[&str] = &["net_prio.ifpriomap", "net_prio.prioidx"];
+
+// The control files used by the controller on cgroup v2.
+const MANAGED_FILES_V2: &[&str] = &[];
+
 impl ControllerInternal for NetPrioController {
     fn control_type(&self) -> Controllers {
         Controllers::NetPrio
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        if self.is_v2() {
+            MANAGED_FILES_V2
+        } else {
+            MANAGED_FILES_V1
+        }
+    }
     fn get_path(&self) -> &PathBuf {
         &self.path
     }
@@ -54,6 +68,26 @@ impl ControllerInternal for NetPrioController {
 
         Ok(())
     }
+
+    fn reset(&self) -> Result<()> {
+        for (ifname, prio) in self.ifpriomap()? {
+            if prio != 0 {
+                self.set_if_prio(&ifname, 0)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_resources(&self, res: &mut Resources) -> Result<()> {
+        let mut priorities: Vec<NetworkPriority> = self
+            .ifpriomap()?
+            .into_iter()
+            .map(|(name, priority)| NetworkPriority { name, priority })
+            .collect();
+        priorities.sort_by(|a, b| a.name.cmp(&b.name));
+        res.network.priorities = priorities;
+        Ok(())
+    }
 }
 
 impl ControllIdentifier for NetPrioController {
@@ -62,17 +96,15 @@ impl ControllIdentifier for NetPrioController {
     }
 }
 
-impl<'a> From<&'a Subsystem> for &'a NetPrioController {
-    fn from(sub: &'a Subsystem) -> &'a NetPrioController {
-        unsafe {
-            match sub {
-                Subsystem::NetPrio(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a NetPrioController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a NetPrioController> {
+        match sub {
+            Subsystem::NetPrio(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                NetPrioController::controller_type().to_string(),
+            ))),
         }
     }
 }
@@ -95,42 +127,69 @@ impl NetPrioController {
 
     /// A map of priorities for each network interface.
     pub fn ifpriomap(&self) -> Result<HashMap<String, u64>> {
-        self.open_path("net_prio.ifpriomap", false)
-            .and_then(|file| {
-                let bf = BufReader::new(file);
-                bf.lines()
-                    .map(|line| {
-                        let line = line.map_err(|_| Error::new(ParseError))?;
-                        let mut parts = line.split_whitespace();
-
-                        let ifname = parts.next().ok_or(Error::new(ParseError))?;
-                        let ifprio_str = parts.next().ok_or(Error::new(ParseError))?;
+        self.if_prio_map().map(|map| map.into_iter().collect())
+    }
 
-                        let ifprio = ifprio_str
-                            .trim()
-                            .parse()
-                            .map_err(|e| Error::with_cause(ParseError, e))?;
+    /// Returns the priority of each network interface, in the order of `net_prio.ifpriomap`.
+    pub fn if_prio_map(&self) -> Result<Vec<(String, u64)>> {
+        self.open_path("net_prio.ifpriomap", false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_if_prio_map(&s))
+    }
 
-                        Ok((ifname.to_string(), ifprio))
-                    })
-                    .collect::<Result<HashMap<String, _>>>()
-            })
+    /// Set the priority of the network traffic on each interface of `map`, one interface at a
+    /// time, stopping at the first error.
+    pub fn set_if_prio_map(&self, map: &[(String, u64)]) -> Result<()> {
+        for (eif, prio) in map {
+            self.set_if_prio(eif, *prio)?;
+        }
+        Ok(())
     }
 
     /// Set the priority of the network traffic on `eif` to be `prio`.
     pub fn set_if_prio(&self, eif: &str, prio: u64) -> Result<()> {
-        self.open_path("net_prio.ifpriomap", true)
-            .and_then(|mut file| {
-                file.write_all(format!("{} {}", eif, prio).as_ref())
-                    .map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed(
-                                "net_prio.ifpriomap".to_string(),
-                                format!("{} {}", eif, prio),
-                            ),
-                            e,
-                        )
-                    })
-            })
+        self.write_file("net_prio.ifpriomap", &format!("{} {}", eif, prio))
+    }
+}
+
+// Parses the `<interface> <priority>` lines of `net_prio.ifpriomap`.
+fn parse_if_prio_map(s: &str) -> Result<Vec<(String, u64)>> {
+    s.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut parts = line.split_whitespace();
+
+            let ifname = parts.next().ok_or(Error::new(ParseError))?;
+            let ifprio_str = parts.next().ok_or(Error::new(ParseError))?;
+
+            let ifprio = ifprio_str
+                .trim()
+                .parse()
+                .map_err(|e| Error::with_cause(ParseError, e))?;
+
+            Ok((ifname.to_string(), ifprio))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::net_prio::parse_if_prio_map;
+
+    #[test]
+    fn test_parse_if_prio_map() {
+        let map = parse_if_prio_map("lo 0\neth0 5\nwl0 10\n").unwrap();
+        assert_eq!(
+            map,
+            vec![
+                ("lo".to_string(), 0),
+                ("eth0".to_string(), 5),
+                ("wl0".to_string(), 10)
+            ]
+        );
+
+        assert!(parse_if_prio_map("").unwrap().is_empty());
+        assert!(parse_if_prio_map("eth0").is_err());
+        assert!(parse_if_prio_map("eth0 high").is_err());
     }
 }
@@ -7,6 +7,7 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [tools/perf/Documentation/perf-record.txt](https://raw.githubusercontent.com/torvalds/linux/master/tools/perf/Documentation/perf-record.txt)
+use std::convert::TryFrom;
 use std::path::PathBuf;
 
 use crate::fs::error::*;
@@ -23,10 +24,16 @@ pub struct PerfEventController {
     path: PathBuf,
 }
 
+// The control files used by the controller, see `Controller::managed_files()`.
+const MANAGED_FILES: &[&str] = &[];
+
 impl ControllerInternal for PerfEventController {
     fn control_type(&self) -> Controllers {
         Controllers::PerfEvent
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        MANAGED_FILES
+    }
     fn get_path(&self) -> &PathBuf {
         &self.path
     }
@@ -48,17 +55,15 @@ impl ControllIdentifier for PerfEventController {
     }
 }
 
-impl<'a> From<&'a Subsystem> for &'a PerfEventController {
-    fn from(sub: &'a Subsystem) -> &'a PerfEventController {
-        unsafe {
-            match sub {
-                Subsystem::PerfEvent(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a PerfEventController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a PerfEventController> {
+        match sub {
+            Subsystem::PerfEvent(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                PerfEventController::controller_type().to_string(),
+            ))),
         }
     }
 }
@@ -8,8 +8,10 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroups-v1/pids.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/pids.txt)
+use std::convert::TryFrom;
 use std::io::{Read, Write};
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 use crate::fs::error::ErrorKind::*;
 use crate::fs::error::*;
@@ -28,10 +30,38 @@ pub struct PidController {
     v2: bool,
 }
 
+/// The number of processes in the control group at some point in time, see
+/// `PidController::sample()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PidsSample {
+    /// The value of `pids.current`.
+    pub current: u64,
+    /// When `pids.current` was read.
+    pub at: Instant,
+}
+
+impl PidsSample {
+    /// Returns how many processes were created per second between `earlier` and this sample,
+    /// approximated from the increase of `pids.current`: a decrease counts as no process created.
+    pub fn fork_rate_since(&self, earlier: &PidsSample) -> f64 {
+        let secs = self.at.saturating_duration_since(earlier.at).as_secs_f64();
+        if secs == 0.0 {
+            return 0.0;
+        }
+        self.current.saturating_sub(earlier.current) as f64 / secs
+    }
+}
+
+// The control files used by the controller, see `Controller::managed_files()`.
+const MANAGED_FILES: &[&str] = &["pids.max", "pids.current", "pids.events"];
+
 impl ControllerInternal for PidController {
     fn control_type(&self) -> Controllers {
         Controllers::Pids
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        MANAGED_FILES
+    }
     fn get_path(&self) -> &PathBuf {
         &self.path
     }
@@ -60,6 +90,15 @@ impl ControllerInternal for PidController {
 
         Ok(())
     }
+
+    fn reset(&self) -> Result<()> {
+        self.set_pid_max(MaxValue::Max)
+    }
+
+    fn read_resources(&self, res: &mut Resources) -> Result<()> {
+        res.pid.maximum_number_of_processes = self.max_value_if_exists("pids.max")?;
+        Ok(())
+    }
 }
 
 // impl<'a> ControllIdentifier for &'a PidController {
@@ -74,17 +113,15 @@ impl ControllIdentifier for PidController {
     }
 }
 
-impl<'a> From<&'a Subsystem> for &'a PidController {
-    fn from(sub: &'a Subsystem) -> &'a PidController {
-        unsafe {
-            match sub {
-                Subsystem::Pid(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a PidController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a PidController> {
+        match sub {
+            Subsystem::Pid(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                PidController::controller_type().to_string(),
+            ))),
         }
     }
 }
@@ -123,6 +160,16 @@ impl PidController {
             .and_then(read_u64_from)
     }
 
+    /// Reads `pids.current` along with the time it was read, to be compared with a later sample,
+    /// see `PidsSample::fork_rate_since()`.
+    pub fn sample(&self) -> Result<PidsSample> {
+        let current = self.get_pid_current()?;
+        Ok(PidsSample {
+            current,
+            at: Instant::now(),
+        })
+    }
+
     /// The maximum number of processes that can exist at one time in the control group.
     pub fn get_pid_max(&self) -> Result<MaxValue> {
         self.open_path("pids.max", false).and_then(|mut file| {
@@ -135,6 +182,61 @@ impl PidController {
         })
     }
 
+    /// The maximum number of processes that can effectively exist in the control group, which is
+    /// the lowest `pids.max` of the control group and its ancestors.
+    ///
+    /// Returns `MaxValue::Max` if none of them is limited.
+    pub fn effective_max(&self) -> Result<MaxValue> {
+        if self.is_root() {
+            return Ok(MaxValue::Max);
+        }
+
+        let mut max = self.get_pid_max()?;
+        // The walk ends at the root control group, or at the top of a hierarchy mounted from a
+        // non-root control group, neither of which has a `pids.max`.
+        for dir in self.get_path().ancestors().skip(1) {
+            let file = dir.join("pids.max");
+            if !file.exists() {
+                break;
+            }
+            let limit = std::fs::read_to_string(&file)
+                .map_err(|e| Error::with_cause(ReadFailed(file.display().to_string()), e))
+                .and_then(|s| parse_max_value(&s))?;
+            max = match (max, limit) {
+                (MaxValue::Value(a), MaxValue::Value(b)) => MaxValue::Value(a.min(b)),
+                (MaxValue::Max, v) | (v, MaxValue::Max) => v,
+            };
+        }
+
+        Ok(max)
+    }
+
+    /// Estimates how many processes are created per second in the control group, sampling
+    /// `pids.current` over `window`, e.g. to detect fork storms.
+    ///
+    /// The rate is approximated from the increases of `pids.current` between samples taken every
+    /// tenth of the window, so processes that are created and exit between two samples are not
+    /// counted and the result is a lower bound.
+    pub fn fork_pressure(&self, window: Duration) -> Result<f64> {
+        const SAMPLES: u32 = 10;
+
+        let first = self.sample()?;
+        let mut last = first;
+        let mut created = 0;
+        for _ in 0..SAMPLES {
+            std::thread::sleep(window / SAMPLES);
+            let sample = self.sample()?;
+            created += sample.current.saturating_sub(last.current);
+            last = sample;
+        }
+
+        let secs = last.at.duration_since(first.at).as_secs_f64();
+        if secs == 0.0 {
+            return Ok(0.0);
+        }
+        Ok(created as f64 / secs)
+    }
+
     /// Set the maximum number of processes that can exist in this control group.
     ///
     /// Note that if `get_pid_current()` returns a higher number than what you
@@ -7,14 +7,18 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/rdma.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/rdma.txt)
-use std::io::Write;
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::path::PathBuf;
 
 use crate::fs::error::ErrorKind::*;
 use crate::fs::error::*;
 
 use crate::fs::read_string_from;
-use crate::fs::{ControllIdentifier, ControllerInternal, Controllers, Resources, Subsystem};
+use crate::fs::{
+    ControllIdentifier, ControllerInternal, Controllers, RdmaResource, RdmaResources, Resources,
+    Subsystem,
+};
 
 /// A controller that allows controlling the `rdma` subsystem of a Cgroup.
 ///
@@ -26,10 +30,28 @@ pub struct RdmaController {
     path: PathBuf,
 }
 
+/// The RDMA/IB resources of a device, as found in `rdma.max` and `rdma.current`.
+///
+/// `None` means that the resource is unlimited (`max`).
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RdmaLimit {
+    /// Maximum number of HCA handles.
+    pub hca_handle: Option<u32>,
+    /// Maximum number of HCA objects.
+    pub hca_object: Option<u32>,
+}
+
+// The control files used by the controller, see `Controller::managed_files()`.
+const MANAGED_FILES: &[&str] = &["rdma.max", "rdma.current"];
+
 impl ControllerInternal for RdmaController {
     fn control_type(&self) -> Controllers {
         Controllers::Rdma
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        MANAGED_FILES
+    }
     fn get_path(&self) -> &PathBuf {
         &self.path
     }
@@ -40,7 +62,33 @@ impl ControllerInternal for RdmaController {
         &self.base
     }
 
-    fn apply(&self, _res: &Resources) -> Result<()> {
+    fn apply(&self, res: &Resources) -> Result<()> {
+        // get the resources that apply to this controller
+        let res: &RdmaResources = &res.rdma;
+
+        for limit in &res.limits {
+            self.set_max_limits(&limit.device, limit.limit)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_resources(&self, res: &mut Resources) -> Result<()> {
+        if !self.path.join("rdma.max").exists() {
+            return Ok(());
+        }
+        res.rdma.limits = self
+            .max_parsed()?
+            .into_iter()
+            .map(|(device, limit)| RdmaResource { device, limit })
+            .collect();
+        Ok(())
+    }
+
+    fn reset(&self) -> Result<()> {
+        for device in self.max_parsed()?.keys() {
+            self.set_unlimited(device)?;
+        }
         Ok(())
     }
 }
@@ -51,17 +99,15 @@ impl ControllIdentifier for RdmaController {
     }
 }
 
-impl<'a> From<&'a Subsystem> for &'a RdmaController {
-    fn from(sub: &'a Subsystem) -> &'a RdmaController {
-        unsafe {
-            match sub {
-                Subsystem::Rdma(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a RdmaController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a RdmaController> {
+        match sub {
+            Subsystem::Rdma(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                RdmaController::controller_type().to_string(),
+            ))),
         }
     }
 }
@@ -88,10 +134,97 @@ impl RdmaController {
 
     /// Set a maximum usage for each RDMA/IB resource.
     pub fn set_max(&self, max: &str) -> Result<()> {
-        self.open_path("rdma.max", true).and_then(|mut file| {
-            file.write_all(max.as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed("rdma.max".to_string(), max.to_string()), e)
-            })
-        })
+        self.write_file("rdma.max", max)
+    }
+
+    /// Returns the current usage of the RDMA/IB resources of each device, as found in
+    /// `rdma.current`.
+    pub fn current_parsed(&self) -> Result<HashMap<String, RdmaLimit>> {
+        self.current().and_then(|s| parse_rdma_limits(&s))
+    }
+
+    /// Returns the RDMA/IB resources limits of each device, as found in `rdma.max`.
+    pub fn max_parsed(&self) -> Result<HashMap<String, RdmaLimit>> {
+        self.max().and_then(|s| parse_rdma_limits(&s))
+    }
+
+    /// Set the RDMA/IB resources limits of a device, `None` fields meaning unlimited.
+    pub fn set_max_limits(&self, device: &str, limit: RdmaLimit) -> Result<()> {
+        let value = |v: Option<u32>| v.map_or_else(|| "max".to_string(), |v| v.to_string());
+        let line = format!(
+            "{} hca_handle={} hca_object={}",
+            device,
+            value(limit.hca_handle),
+            value(limit.hca_object)
+        );
+        self.set_max(&line)
+    }
+
+    /// Removes the RDMA/IB resources limits of a device.
+    pub fn set_unlimited(&self, device: &str) -> Result<()> {
+        self.set_max_limits(device, RdmaLimit::default())
+    }
+}
+
+fn parse_rdma_limits(s: &str) -> Result<HashMap<String, RdmaLimit>> {
+    let mut limits = HashMap::new();
+    for line in s.lines() {
+        let mut fields = line.split_whitespace();
+        let device = match fields.next() {
+            Some(device) => device,
+            None => continue,
+        };
+
+        let mut limit = RdmaLimit::default();
+        for field in fields {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| Error::from_string(format!("invalid rdma limit: {}", field)))?;
+            let value = match value {
+                "max" => None,
+                v => Some(
+                    v.parse::<u32>()
+                        .map_err(|e| Error::with_cause(ParseError, e))?,
+                ),
+            };
+            match key {
+                "hca_handle" => limit.hca_handle = value,
+                "hca_object" => limit.hca_object = value,
+                _ => {}
+            }
+        }
+        limits.insert(device.to_string(), limit);
+    }
+
+    Ok(limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::rdma::{parse_rdma_limits, RdmaLimit};
+
+    #[test]
+    fn test_parse_rdma_limits() {
+        let content = "mlx4_0 hca_handle=2 hca_object=2000\nocrdma1 hca_handle=3 hca_object=max\n";
+        let limits = parse_rdma_limits(content).unwrap();
+        assert_eq!(limits.len(), 2);
+        assert_eq!(
+            limits["mlx4_0"],
+            RdmaLimit {
+                hca_handle: Some(2),
+                hca_object: Some(2000),
+            }
+        );
+        assert_eq!(
+            limits["ocrdma1"],
+            RdmaLimit {
+                hca_handle: Some(3),
+                hca_object: None,
+            }
+        );
+
+        assert!(parse_rdma_limits("").unwrap().is_empty());
+        assert!(parse_rdma_limits("mlx4_0 hca_handle=abc").is_err());
+        assert!(parse_rdma_limits("mlx4_0 hca_handle").is_err());
     }
 }
@@ -5,11 +5,25 @@
 
 //! This module contains the implementation of the `systemd` cgroup subsystem.
 //!
+use std::convert::TryFrom;
 use std::path::PathBuf;
 
+#[cfg(feature = "systemd-dbus")]
+use zbus::zvariant::{OwnedObjectPath, Value};
+
+#[cfg(feature = "systemd-dbus")]
+use crate::fs::error::ErrorKind::*;
 use crate::fs::error::*;
 
 use crate::fs::{ControllIdentifier, ControllerInternal, Controllers, Resources, Subsystem};
+#[cfg(feature = "systemd-dbus")]
+use crate::systemd::dbus::proxy::{systemd_manager_proxy, SystemManager};
+#[cfg(feature = "systemd-dbus")]
+use crate::systemd::utils::{is_scope_unit, is_slice_unit};
+#[cfg(feature = "systemd-dbus")]
+use crate::systemd::{Property, PIDS, UNIT_MODE_REPLACE};
+#[cfg(feature = "systemd-dbus")]
+use crate::CgroupPid;
 
 /// A controller that allows controlling the `systemd` subsystem of a Cgroup.
 ///
@@ -20,10 +34,16 @@ pub struct SystemdController {
     _v2: bool,
 }
 
+// The control files used by the controller, see `Controller::managed_files()`.
+const MANAGED_FILES: &[&str] = &[];
+
 impl ControllerInternal for SystemdController {
     fn control_type(&self) -> Controllers {
         Controllers::Systemd
     }
+    fn managed_files(&self) -> &'static [&'static str] {
+        MANAGED_FILES
+    }
     fn get_path(&self) -> &PathBuf {
         &self.path
     }
@@ -45,17 +65,15 @@ impl ControllIdentifier for SystemdController {
     }
 }
 
-impl<'a> From<&'a Subsystem> for &'a SystemdController {
-    fn from(sub: &'a Subsystem) -> &'a SystemdController {
-        unsafe {
-            match sub {
-                Subsystem::Systemd(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+impl<'a> TryFrom<&'a Subsystem> for &'a SystemdController {
+    type Error = Error;
+
+    fn try_from(sub: &'a Subsystem) -> Result<&'a SystemdController> {
+        match sub {
+            Subsystem::Systemd(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::ControllerNotFound(
+                SystemdController::controller_type().to_string(),
+            ))),
         }
     }
 }
@@ -70,3 +88,246 @@ impl SystemdController {
         }
     }
 }
+
+#[cfg(feature = "systemd-dbus")]
+impl SystemdController {
+    /// Sets properties such as `MemoryMax` or `CPUQuota` of the systemd unit (slice or scope) of
+    /// the control group, the last component of its path, through the D-Bus method
+    /// `SetUnitProperties`.
+    ///
+    /// systemd owns the control groups of its units and may revert changes written directly to
+    /// their files, while properties set through systemd are kept until the unit is stopped.
+    pub fn set_unit_properties(&self, props: &[Property]) -> Result<()> {
+        let unit = self.unit()?;
+        systemd_manager_proxy()
+            .and_then(|proxy| set_unit_properties(&proxy, unit, props))
+            .map_err(|e| {
+                Error::with_cause(
+                    Common(format!("unable to set properties of systemd unit {}", unit)),
+                    e,
+                )
+            })
+    }
+
+    /// Creates the transient systemd unit `unit` through the D-Bus method `StartTransientUnit`,
+    /// with the properties `props`, e.g. `MemoryMax` or `CPUQuota`.
+    ///
+    /// The unit is a scope or a slice depending on the suffix of its name. A scope is created
+    /// with the processes `pids` in it and systemd refuses empty ones, while a slice can't hold
+    /// processes directly, so `pids` must be empty. Going through systemd, rather than creating
+    /// the directory, keeps it from removing the control group as one it doesn't know about.
+    pub fn start_transient_unit(
+        &self,
+        unit: &str,
+        pids: &[CgroupPid],
+        props: &[Property],
+    ) -> Result<()> {
+        if !is_scope_unit(unit) && !is_slice_unit(unit) {
+            return Err(Error::new(InvalidName(unit.to_string())));
+        }
+        if is_scope_unit(unit) == pids.is_empty() {
+            return Err(Error::new(InvalidOperation));
+        }
+
+        systemd_manager_proxy()
+            .and_then(|proxy| start_transient_unit(&proxy, unit, pids, props))
+            .map_err(|e| {
+                Error::with_cause(Common(format!("unable to start systemd unit {}", unit)), e)
+            })
+    }
+
+    /// Stops the systemd unit `unit`, which kills the processes of a scope and removes its
+    /// control group.
+    pub fn stop_unit(&self, unit: &str) -> Result<()> {
+        systemd_manager_proxy()
+            .and_then(|proxy| proxy.stop_unit(unit, UNIT_MODE_REPLACE))
+            .map(|_| ())
+            .map_err(|e| {
+                Error::with_cause(Common(format!("unable to stop systemd unit {}", unit)), e)
+            })
+    }
+
+    // The name of the unit of the control group, e.g. `foo.scope` for
+    // `/sys/fs/cgroup/systemd/system.slice/foo.scope`.
+    fn unit(&self) -> Result<&str> {
+        self.path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| is_scope_unit(name) || is_slice_unit(name))
+            .ok_or_else(|| Error::new(InvalidName(self.path.display().to_string())))
+    }
+}
+
+// The methods of the systemd manager used by the controller, which the tests mock.
+#[cfg(feature = "systemd-dbus")]
+trait UnitManager {
+    fn set_unit_properties(
+        &self,
+        unit: &str,
+        runtime: bool,
+        props: &[&(&str, &Value<'_>)],
+    ) -> zbus::Result<()>;
+
+    fn start_transient_unit(
+        &self,
+        unit: &str,
+        mode: &str,
+        props: &[&(&str, &Value<'_>)],
+    ) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[cfg(feature = "systemd-dbus")]
+impl UnitManager for SystemManager<'_> {
+    fn set_unit_properties(
+        &self,
+        unit: &str,
+        runtime: bool,
+        props: &[&(&str, &Value<'_>)],
+    ) -> zbus::Result<()> {
+        SystemManager::set_unit_properties(self, unit, runtime, props)
+    }
+
+    fn start_transient_unit(
+        &self,
+        unit: &str,
+        mode: &str,
+        props: &[&(&str, &Value<'_>)],
+    ) -> zbus::Result<OwnedObjectPath> {
+        SystemManager::start_transient_unit(self, unit, mode, props, &[])
+    }
+}
+
+#[cfg(feature = "systemd-dbus")]
+fn set_unit_properties(
+    manager: &impl UnitManager,
+    unit: &str,
+    props: &[Property],
+) -> zbus::Result<()> {
+    with_dbus_props(props, None, |props| {
+        manager.set_unit_properties(unit, true, props)
+    })
+}
+
+#[cfg(feature = "systemd-dbus")]
+fn start_transient_unit(
+    manager: &impl UnitManager,
+    unit: &str,
+    pids: &[CgroupPid],
+    props: &[Property],
+) -> zbus::Result<()> {
+    let pids = Value::Array(
+        pids.iter()
+            .map(|pid| pid.pid as u32)
+            .collect::<Vec<_>>()
+            .into(),
+    );
+    let pids = is_scope_unit(unit).then_some((PIDS, &pids));
+    with_dbus_props(props, pids, |props| {
+        manager.start_transient_unit(unit, UNIT_MODE_REPLACE, props)
+    })
+    .map(|_| ())
+}
+
+// Calls `call` with `props`, followed by `extra` if any, in the form the D-Bus proxy takes them.
+#[cfg(feature = "systemd-dbus")]
+fn with_dbus_props<T>(
+    props: &[Property],
+    extra: Option<(&str, &Value<'_>)>,
+    call: impl FnOnce(&[&(&str, &Value<'_>)]) -> T,
+) -> T {
+    let props: Vec<(&str, &Value)> = props.iter().map(|(k, v)| (*k, v)).chain(extra).collect();
+    call(&props.iter().collect::<Vec<_>>())
+}
+
+#[cfg(all(test, feature = "systemd-dbus"))]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    // Records the calls rather than sending them to systemd.
+    #[derive(Default)]
+    struct MockManager {
+        calls: RefCell<Vec<(String, Vec<String>)>>,
+    }
+
+    impl MockManager {
+        fn record(&self, unit: &str, props: &[&(&str, &Value<'_>)]) {
+            let names = props.iter().map(|(k, _)| k.to_string()).collect();
+            self.calls.borrow_mut().push((unit.to_string(), names));
+        }
+    }
+
+    impl UnitManager for MockManager {
+        fn set_unit_properties(
+            &self,
+            unit: &str,
+            runtime: bool,
+            props: &[&(&str, &Value<'_>)],
+        ) -> zbus::Result<()> {
+            assert!(runtime);
+            self.record(unit, props);
+            Ok(())
+        }
+
+        fn start_transient_unit(
+            &self,
+            unit: &str,
+            mode: &str,
+            props: &[&(&str, &Value<'_>)],
+        ) -> zbus::Result<OwnedObjectPath> {
+            assert_eq!(mode, UNIT_MODE_REPLACE);
+            self.record(unit, props);
+            Ok(OwnedObjectPath::try_from("/org/freedesktop/systemd1/job/1").unwrap())
+        }
+    }
+
+    #[test]
+    fn test_unit_calls() {
+        let manager = MockManager::default();
+        let props = [("MemoryMax", Value::U64(1 << 30))];
+
+        set_unit_properties(&manager, "foo.scope", &props).unwrap();
+        start_transient_unit(&manager, "foo.scope", &[CgroupPid::from(1)], &props).unwrap();
+        start_transient_unit(&manager, "foo.slice", &[], &props).unwrap();
+
+        let calls = |unit: &str, names: &[&str]| {
+            (
+                unit.to_string(),
+                names.iter().map(|n| n.to_string()).collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(
+            *manager.calls.borrow(),
+            vec![
+                calls("foo.scope", &["MemoryMax"]),
+                calls("foo.scope", &["MemoryMax", PIDS]),
+                calls("foo.slice", &["MemoryMax"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unit_of_control_group() {
+        let root = PathBuf::from("/sys/fs/cgroup/systemd");
+        let c = SystemdController::new(root.join("system.slice/foo.scope"), root.clone(), false);
+        assert_eq!(c.unit().unwrap(), "foo.scope");
+
+        let c = SystemdController::new(root.join("foo"), root, false);
+        assert!(matches!(c.unit().unwrap_err().kind(), InvalidName(_)));
+    }
+
+    #[test]
+    fn test_start_transient_unit_rejects_invalid_units() {
+        let c = SystemdController::new(PathBuf::new(), PathBuf::new(), false);
+        let pids = [CgroupPid::from(1)];
+
+        let err = c
+            .start_transient_unit("foo.service", &pids, &[])
+            .unwrap_err();
+        assert_eq!(err.kind(), &InvalidName("foo.service".to_string()));
+        let err = c.start_transient_unit("foo.scope", &[], &[]).unwrap_err();
+        assert_eq!(err.kind(), &InvalidOperation);
+        let err = c.start_transient_unit("foo.slice", &pids, &[]).unwrap_err();
+        assert_eq!(err.kind(), &InvalidOperation);
+    }
+}
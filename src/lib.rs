@@ -11,6 +11,7 @@ pub mod manager;
 pub use manager::{FsManager, Manager, SystemdManager};
 pub mod stats;
 pub use stats::CgroupStats;
+#[cfg(feature = "systemd-dbus")]
 pub mod systemd;
 
 /// The maximum value for CPU shares in cgroups v1
@@ -19,6 +20,7 @@ pub const CPU_SHARES_V1_MAX: u64 = 262144;
 pub const CPU_WEIGHT_V2_MAX: u64 = 10000;
 
 /// The current state of the control group
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FreezerState {
     /// The processes in the control group are _not_ frozen.
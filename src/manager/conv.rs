@@ -3,8 +3,8 @@
 // SPDX-License-Identifier: Apache-2.0 or MIT
 //
 
+use crate::fs::cpu::shares_to_weight;
 use crate::manager::error::{Error, Result};
-use crate::{CPU_SHARES_V1_MAX, CPU_WEIGHT_V2_MAX};
 
 // Converts CPU shares, used by cgroup v1, to CPU weight, used by cgroup
 // v2.
@@ -18,14 +18,8 @@ pub(crate) fn cpu_shares_to_cgroup_v2(shares: u64) -> u64 {
     if shares == 0 {
         return 0;
     }
-    if shares <= 2 {
-        return 1;
-    }
-    if shares >= CPU_SHARES_V1_MAX {
-        return CPU_WEIGHT_V2_MAX;
-    }
 
-    (((shares - 2) * 9999) / 262142) + 1
+    shares_to_weight(shares)
 }
 
 // ConvertMemorySwapToCgroupV2Value converts MemorySwap value from OCI spec
@@ -71,6 +65,7 @@ pub(crate) fn memory_swap_to_cgroup_v2(memswap_limit: i64, mem_limit: i64) -> Re
 #[cfg(test)]
 mod tests {
     use crate::manager::conv::*;
+    use crate::{CPU_SHARES_V1_MAX, CPU_WEIGHT_V2_MAX};
 
     #[test]
     fn test_cpu_shares_to_cgroup_v2() {
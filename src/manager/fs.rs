@@ -4,6 +4,7 @@
 //
 
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fs;
 use std::path::Path;
 use std::str::FromStr;
@@ -116,7 +117,7 @@ impl FsManager {
 
     fn controller<'a, T>(&'a self) -> FsResult<&'a T>
     where
-        &'a T: From<&'a Subsystem>,
+        &'a T: TryFrom<&'a Subsystem, Error = FsError>,
         T: Controller + ControllIdentifier,
     {
         let controller: &T = self
@@ -226,10 +227,9 @@ impl FsManager {
         }
 
         if let Some(swappiness) = linux_memory.swappiness() {
-            if swappiness <= 100 {
-                controller.set_swappiness(swappiness)?;
-            } else {
-                return Err(Error::InvalidLinuxResource);
+            match u8::try_from(swappiness) {
+                Ok(swappiness) if swappiness <= 100 => controller.set_swappiness(swappiness)?,
+                _ => return Err(Error::InvalidLinuxResource),
             };
         }
 
@@ -453,7 +453,7 @@ impl FsManager {
     /// that, you should do it manually.
     fn set_controller_topdown<T, F>(&self, f: F) -> Result<()>
     where
-        for<'a> &'a T: From<&'a Subsystem>,
+        for<'a> &'a T: TryFrom<&'a Subsystem, Error = FsError>,
         T: Controller + ControllIdentifier,
         for<'a> F: Fn(&'a T) -> Result<()>,
     {
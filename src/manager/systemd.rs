@@ -348,6 +348,7 @@ mod tests {
     //! Don't run tests in parallel, use `--test-threads=1`!
     //!
 
+    use std::convert::TryFrom;
     use std::path::Path;
     use std::thread::sleep;
     use std::time::Duration;
@@ -357,6 +358,7 @@ mod tests {
     use rand::Rng;
 
     use crate::fs::cpu::CpuController;
+    use crate::fs::error::Error;
     use crate::fs::memory::MemController;
     use crate::fs::{ControllIdentifier, Controller, Subsystem};
     use crate::manager::systemd::*;
@@ -477,7 +479,7 @@ mod tests {
 
     fn controller<'a, T>(fs_manager: &'a FsManager) -> &'a T
     where
-        &'a T: From<&'a Subsystem>,
+        &'a T: TryFrom<&'a Subsystem, Error = Error>,
         T: Controller + ControllIdentifier,
     {
         let controller: &T = fs_manager.cgroup().controller_of().unwrap();
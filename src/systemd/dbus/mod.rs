@@ -15,4 +15,4 @@ mod client;
 pub mod error;
 mod systemd_manager_proxy;
 pub use client::SystemdClient;
-mod proxy;
+pub(crate) mod proxy;
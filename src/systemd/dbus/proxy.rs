@@ -6,7 +6,7 @@
 use zbus::blocking::Connection;
 use zbus::Result;
 
-use crate::systemd::dbus::systemd_manager_proxy::ManagerProxyBlocking as SystemManager;
+pub(crate) use crate::systemd::dbus::systemd_manager_proxy::ManagerProxyBlocking as SystemManager;
 
 pub(crate) fn systemd_manager_proxy<'a>() -> Result<SystemManager<'a>> {
     let connection = Connection::system()?;
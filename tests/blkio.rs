@@ -0,0 +1,242 @@
+// Copyright (c) 2020 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0 or MIT
+//
+
+//! Integration tests about the blkio subsystem
+use cgroups_rs::fs::blkio::{BlkIoController, IoMax, IoModel};
+use cgroups_rs::fs::error::ErrorKind;
+use cgroups_rs::fs::{Cgroup, Controller, MaxValue};
+
+#[test]
+fn test_leaf_weight() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_leaf_weight")).unwrap();
+    {
+        let blkio: &BlkIoController = cg.controller_of().unwrap();
+        if !blkio.v2() && blkio.path().join("blkio.leaf_weight").exists() {
+            blkio.set_leaf_weight(200).unwrap();
+            assert_eq!(blkio.leaf_weight().unwrap(), 200);
+            assert!(blkio.set_leaf_weight(5).is_err());
+            assert!(blkio.set_leaf_weight(1001).is_err());
+        } else {
+            // Only CFQ on cgroup v1 has leaf weights.
+            for err in [
+                blkio.set_leaf_weight(200).unwrap_err(),
+                blkio.leaf_weight().unwrap_err(),
+                blkio.leaf_weight_device().unwrap_err(),
+            ] {
+                assert!(matches!(err.kind(), ErrorKind::NotSupported { .. }));
+            }
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_io_utilization() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_io_utilization")).unwrap();
+    {
+        let blkio: &BlkIoController = cg.controller_of().unwrap();
+        let window = std::time::Duration::from_millis(10);
+        if blkio.v2() {
+            // no cap is set
+            assert_eq!(blkio.io_utilization(8, 0, window).unwrap(), None);
+        } else {
+            assert!(blkio.io_utilization(8, 0, window).is_err());
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_io_model() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_io_model")).unwrap();
+    {
+        let blkio: &BlkIoController = cg.controller_of().unwrap();
+        let models = blkio.io_models();
+        assert_eq!(
+            blkio.io_model(),
+            models.first().copied().unwrap_or(IoModel::Unknown)
+        );
+        assert!(!models.contains(&IoModel::Unknown));
+        if !blkio.v2() {
+            assert!(!models.contains(&IoModel::Cost));
+        }
+        if blkio.path().join("blkio.throttle.read_bps_device").exists() {
+            assert!(models.contains(&IoModel::Max));
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_io_max() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_io_max")).unwrap();
+    {
+        let blkio: &BlkIoController = cg.controller_of().unwrap();
+        if !blkio.v2() {
+            assert!(blkio.set_io_max(8, 0, IoMax::default()).is_err());
+            assert!(blkio.io_stat().is_err());
+        } else if let Some((major, minor)) = first_disk() {
+            let max = IoMax {
+                rbps: Some(1048576),
+                wiops: Some(u64::MAX),
+                ..Default::default()
+            };
+            blkio.set_io_max(major, minor, max).unwrap();
+            let limits = blkio.io_max().unwrap();
+            assert_eq!(
+                limits,
+                vec![(
+                    major,
+                    minor,
+                    IoMax {
+                        rbps: Some(1048576),
+                        ..Default::default()
+                    }
+                )]
+            );
+
+            blkio.set_io_max(major, minor, IoMax::default()).unwrap();
+            assert!(blkio.io_max().unwrap().is_empty());
+            blkio.io_stat().unwrap();
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_io_qos() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_io_qos")).unwrap();
+    {
+        let blkio: &BlkIoController = cg.controller_of().unwrap();
+        let max = IoMax {
+            rbps: Some(1048576),
+            ..Default::default()
+        };
+        if !blkio.v2() {
+            let err = blkio
+                .set_io_qos(8, 0, Some(max), Some(MaxValue::Value(75)))
+                .unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::NotSupported { .. }));
+        } else if let Some((major, minor)) = first_disk() {
+            let latency = blkio.path().join("io.latency");
+            let target = if latency.exists() {
+                Some(MaxValue::Value(75))
+            } else {
+                None
+            };
+            let latency_line = || {
+                std::fs::read_to_string(&latency)
+                    .unwrap_or_default()
+                    .lines()
+                    .any(|l| l.starts_with(&format!("{}:{} target=75", major, minor)))
+            };
+
+            // set both
+            blkio.set_io_qos(major, minor, Some(max), target).unwrap();
+            assert_eq!(blkio.io_max().unwrap(), vec![(major, minor, max)]);
+            assert_eq!(latency_line(), target.is_some());
+
+            // clearing the limits leaves the latency target
+            blkio
+                .set_io_qos(major, minor, Some(IoMax::default()), None)
+                .unwrap();
+            assert!(blkio.io_max().unwrap().is_empty());
+            assert_eq!(latency_line(), target.is_some());
+
+            // and clearing the latency target leaves the limits
+            blkio.set_io_qos(major, minor, Some(max), None).unwrap();
+            if target.is_some() {
+                blkio
+                    .set_io_qos(major, minor, None, Some(MaxValue::Max))
+                    .unwrap();
+                assert!(!latency_line());
+            }
+            assert_eq!(blkio.io_max().unwrap(), vec![(major, minor, max)]);
+
+            blkio
+                .set_io_qos(major, minor, Some(IoMax::default()), None)
+                .unwrap();
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_io_service_and_wait_time() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_io_service_and_wait_time")).unwrap();
+    {
+        let blkio: &BlkIoController = cg.controller_of().unwrap();
+        if blkio.v2() {
+            for res in [blkio.io_service_time(), blkio.io_wait_time()] {
+                assert!(matches!(
+                    res.unwrap_err().kind(),
+                    ErrorKind::NotSupported { .. }
+                ));
+            }
+        } else {
+            // only exported by the CFQ and BFQ schedulers
+            let has_file = |name: &str| {
+                blkio.path().join(format!("blkio.{}", name)).exists()
+                    || blkio.path().join(format!("blkio.bfq.{}", name)).exists()
+            };
+            assert_eq!(blkio.io_service_time().is_ok(), has_file("io_service_time"));
+            assert_eq!(blkio.io_wait_time().is_ok(), has_file("io_wait_time"));
+
+            // nothing was done by the new control group
+            if let Ok(service) = blkio.io_service_time() {
+                assert!(service.iter().all(|s| s.total == 0));
+            }
+        }
+    }
+    cg.delete().unwrap();
+}
+
+// The `major:minor` of the first whole disk of the host, if any.
+fn first_disk() -> Option<(u64, u64)> {
+    let mut disks: Vec<_> = std::fs::read_dir("/sys/block")
+        .ok()?
+        .filter_map(|e| std::fs::read_to_string(e.ok()?.path().join("dev")).ok())
+        .collect();
+    disks.sort();
+    let (major, minor) = disks.first()?.trim().split_once(':')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+#[test]
+fn test_throttle_v1() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_throttle_v1")).unwrap();
+    {
+        let blkio: &BlkIoController = cg.controller_of().unwrap();
+        if blkio.v2() {
+            assert!(blkio.throttle_read_bps_device().is_err());
+            assert!(blkio.throttle_io_serviced().is_err());
+        } else if let Some((major, minor)) = first_disk() {
+            blkio
+                .throttle_read_bps_for_device(major, minor, 1048576)
+                .unwrap();
+            blkio
+                .throttle_write_iops_for_device(major, minor, 100)
+                .unwrap();
+            let read_bps = blkio.throttle_read_bps_device().unwrap();
+            assert_eq!(read_bps.len(), 1);
+            assert_eq!(read_bps[0].data, 1048576);
+            let write_iops = blkio.throttle_write_iops_device().unwrap();
+            assert_eq!(write_iops.len(), 1);
+            assert_eq!(write_iops[0].data, 100);
+            assert!(blkio.throttle_read_iops_device().unwrap().is_empty());
+            assert!(blkio.throttle_write_bps_device().unwrap().is_empty());
+            blkio.throttle_io_serviced().unwrap();
+            blkio.throttle_io_service_bytes().unwrap();
+        }
+    }
+    cg.delete().unwrap();
+}
@@ -8,10 +8,12 @@
 use cgroups_rs::fs::blkio::*;
 use cgroups_rs::fs::cgroup_builder::*;
 use cgroups_rs::fs::cpu::*;
+use cgroups_rs::fs::cpuset::*;
 use cgroups_rs::fs::devices::*;
 use cgroups_rs::fs::hugetlb::*;
 use cgroups_rs::fs::memory::*;
 use cgroups_rs::fs::net_cls::*;
+use cgroups_rs::fs::net_prio::*;
 use cgroups_rs::fs::pid::*;
 use cgroups_rs::fs::*;
 
@@ -28,12 +30,83 @@ pub fn test_cpu_res_build() {
     {
         let cpu: &CpuController = cg.controller_of().unwrap();
         assert!(cpu.shares().is_ok());
-        assert_eq!(cpu.shares().unwrap(), 85);
+        // the shares are converted to a weight on v2
+        let expected = if cpu.v2() { shares_to_weight(85) } else { 85 };
+        assert_eq!(cpu.shares().unwrap(), expected);
     }
 
     cg.delete().unwrap();
 }
 
+#[test]
+pub fn test_cpu_res_build_max() {
+    // 50ms every 100ms, then unlimited
+    for (quota, v2_max, v1_quota) in [(50000, "50000 100000", "50000"), (-1, "max 100000", "-1")] {
+        let h = cgroups_rs::fs::hierarchies::auto();
+        let cg: Cgroup = CgroupBuilder::new("test_cpu_res_build_max")
+            .cpu()
+            .max(quota, 100000)
+            .done()
+            .build(h)
+            .unwrap();
+
+        {
+            let cpu: &CpuController = cg.controller_of().unwrap();
+            assert_eq!(cpu.cfs_quota().unwrap(), quota);
+            assert_eq!(cpu.cfs_period().unwrap(), 100000);
+
+            let read = |file: &str| {
+                std::fs::read_to_string(cpu.path().join(file))
+                    .unwrap()
+                    .trim()
+                    .to_string()
+            };
+            if cpu.v2() {
+                assert_eq!(read("cpu.max"), v2_max);
+            } else {
+                assert_eq!(read("cpu.cfs_quota_us"), v1_quota);
+                assert_eq!(read("cpu.cfs_period_us"), "100000");
+            }
+        }
+
+        cg.delete().unwrap();
+    }
+}
+
+#[test]
+pub fn test_cpuset_res_build() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg: Cgroup = CgroupBuilder::new("test_cpuset_res_build")
+        .cpuset()
+        .cpus("0")
+        .mems("0")
+        .done()
+        .build(h)
+        .unwrap();
+
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        let set = cpuset.cpuset();
+        assert_eq!(set.cpus, vec![(0, 0)]);
+        assert_eq!(set.mems, vec![(0, 0)]);
+    }
+
+    cg.delete().unwrap();
+
+    for list in ["0-", "3-1", "a"] {
+        let h = cgroups_rs::fs::hierarchies::auto();
+        let res = CgroupBuilder::new("test_cpuset_res_build_invalid")
+            .cpuset()
+            .cpus(list)
+            .done()
+            .build(h);
+        match res {
+            Err(e) => assert!(matches!(e.kind(), error::ErrorKind::InvalidArgument(_))),
+            Ok(_) => panic!("{} was accepted", list),
+        }
+    }
+}
+
 #[test]
 pub fn test_memory_res_build() {
     let h = cgroups_rs::fs::hierarchies::auto();
@@ -59,6 +132,82 @@ pub fn test_memory_res_build() {
     cg.delete().unwrap();
 }
 
+#[test]
+pub fn test_memory_res_build_swap_max() {
+    // on v1 the setting limits memory and swap together, if swap accounting is enabled
+    if !cgroups_rs::fs::hierarchies::is_cgroup2_unified_mode() {
+        return;
+    }
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg: Cgroup = CgroupBuilder::new("test_memory_res_build_swap_max")
+        .memory()
+        .swap_max(0)
+        .done()
+        .build(h)
+        .unwrap();
+
+    {
+        let c: &MemController = cg.controller_of().unwrap();
+        if c.path().join("memory.swap.max").exists() {
+            assert_eq!(c.swap_max().unwrap(), 0);
+        }
+    }
+
+    cg.delete().unwrap();
+}
+
+#[test]
+pub fn test_memory_res_build_high() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg: Cgroup = CgroupBuilder::new("test_memory_res_build_high")
+        .memory()
+        .high(64 * 1024 * 1024)
+        .done()
+        .build(h)
+        .unwrap();
+
+    {
+        let c: &MemController = cg.controller_of().unwrap();
+        if c.v2() {
+            assert_eq!(c.mem_high().unwrap(), MaxValue::Value(64 * 1024 * 1024));
+        }
+    }
+
+    cg.delete().unwrap();
+}
+
+#[test]
+pub fn test_memory_res_build_limit_str() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg: Cgroup = CgroupBuilder::new("test_memory_res_build_limit_str")
+        .memory()
+        .limit_str("512M")
+        .done()
+        .build(h)
+        .unwrap();
+
+    {
+        let c: &MemController = cg.controller_of().unwrap();
+        assert_eq!(c.memory_stat().limit_in_bytes, 512 * 1024 * 1024);
+    }
+
+    cg.delete().unwrap();
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let err = CgroupBuilder::new("test_memory_res_build_limit_str")
+        .memory()
+        .limit_str("512 megs")
+        .done()
+        .build(h)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.kind(),
+        &cgroups_rs::fs::error::ErrorKind::InvalidBytesSize
+    );
+}
+
 #[test]
 pub fn test_pid_res_build() {
     let h = cgroups_rs::fs::hierarchies::auto();
@@ -129,12 +278,29 @@ pub fn test_network_res_build() {
 }
 
 #[test]
-pub fn test_hugepages_res_build() {
+pub fn test_network_res_build_priorities() {
     let h = cgroups_rs::fs::hierarchies::auto();
-    if h.v2() {
-        // FIXME add cases for v2
-        return;
+    let cg: Cgroup = CgroupBuilder::new("test_network_res_build_priorities")
+        .network()
+        .priorities(vec![("lo".to_string(), 5)])
+        .done()
+        .build(h)
+        .unwrap();
+
+    {
+        // net_prio is only available on v1 hosts that mount it
+        if let Some(c) = cg.controller_of::<NetPrioController>() {
+            assert!(c.if_prio_map().unwrap().contains(&("lo".to_string(), 5)));
+            c.set_if_prio_map(&[("lo".to_string(), 0)]).unwrap();
+            assert_eq!(c.ifpriomap().unwrap()["lo"], 0);
+        }
     }
+    cg.delete().unwrap();
+}
+
+#[test]
+pub fn test_hugepages_res_build() {
+    let h = cgroups_rs::fs::hierarchies::auto();
     let cg: Cgroup = CgroupBuilder::new("test_hugepages_res_build")
         .hugepages()
         .limit("2MB".to_string(), 4 * 2 * 1024 * 1024)
@@ -150,6 +316,38 @@ pub fn test_hugepages_res_build() {
     cg.delete().unwrap();
 }
 
+#[test]
+pub fn test_hugepages_res_build_multiple_sizes() {
+    let sizes: Vec<(String, u64)> = supported_page_sizes()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|size| match size.as_str() {
+            "2MB" => Some((size, 2 * 1024 * 1024)),
+            "1GB" => Some((size, 1024 * 1024 * 1024)),
+            _ => None,
+        })
+        .collect();
+
+    let mut builder = CgroupBuilder::new("test_hugepages_res_build_multiple_sizes").hugepages();
+    for (size, page) in &sizes {
+        // the second limit of a size replaces the first one
+        builder = builder
+            .limit(size.clone(), *page)
+            .limit(size.clone(), 4 * page);
+    }
+    let cg: Cgroup = builder
+        .done()
+        .build(cgroups_rs::fs::hierarchies::auto())
+        .unwrap();
+
+    if let Some(c) = cg.controller_of::<HugeTlbController>() {
+        for (size, page) in &sizes {
+            assert_eq!(c.limit_in_bytes(size).unwrap(), 4 * page);
+        }
+    }
+    cg.delete().unwrap();
+}
+
 #[test]
 #[ignore] // high version kernel not support `blkio.weight`
 pub fn test_blkio_res_build() {
@@ -167,3 +365,89 @@ pub fn test_blkio_res_build() {
     }
     cg.delete().unwrap();
 }
+
+#[test]
+pub fn test_blkio_res_build_throttle() {
+    // Any block device of the host will do.
+    let dev = std::fs::read_dir("/sys/block")
+        .unwrap()
+        .filter_map(|e| std::fs::read_to_string(e.ok()?.path().join("dev")).ok())
+        .next();
+    let (major, minor) = match dev.as_ref().and_then(|d| d.trim().split_once(':')) {
+        Some((major, minor)) => (major.parse().unwrap(), minor.parse().unwrap()),
+        None => return,
+    };
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg: Cgroup = CgroupBuilder::new("test_blkio_res_build_throttle")
+        .blkio()
+        .read_bps(major, minor, 1048576)
+        .write_iops(major, minor, 100)
+        .done()
+        .build(h)
+        .unwrap();
+
+    {
+        let c: &BlkIoController = cg.controller_of().unwrap();
+        if c.v2() {
+            let limits = c.io_max().unwrap();
+            assert_eq!(limits.len(), 1);
+            assert_eq!(limits[0].2.rbps, Some(1048576));
+            assert_eq!(limits[0].2.wiops, Some(100));
+        } else {
+            assert_eq!(c.throttle_read_bps_device().unwrap()[0].data, 1048576);
+            assert_eq!(c.throttle_write_iops_device().unwrap()[0].data, 100);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+pub fn test_enable_ancestor_controllers() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = CgroupBuilder::new("test_enable_ancestor_controllers/child")
+        .set_specified_controllers(vec!["pids".to_string()])
+        .enable_ancestor_controllers()
+        .pid()
+        .maximum_number_of_processes(MaxValue::Value(10))
+        .done()
+        .build(h)
+        .unwrap();
+    {
+        if cg.v2() {
+            let parent = cg.parent_control_group();
+            assert!(parent
+                .subtree_control()
+                .unwrap()
+                .contains(&Controllers::Pids));
+        }
+        let c: &PidController = cg.controller_of().unwrap();
+        assert_eq!(c.get_pid_max().unwrap(), MaxValue::Value(10));
+    }
+    cg.delete().unwrap();
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    Cgroup::load(h, "test_enable_ancestor_controllers")
+        .delete()
+        .unwrap();
+}
+
+#[test]
+pub fn test_delegate_to_build() {
+    use std::os::unix::fs::MetadataExt;
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let built = CgroupBuilder::new("test_delegate_to_build")
+        .delegate_to(65534, 65534)
+        .build(h);
+    if cgroups_rs::fs::hierarchies::is_cgroup2_unified_mode() {
+        let cg = built.unwrap();
+        let meta = std::fs::metadata(cg.fs_path().join("cgroup.procs")).unwrap();
+        assert_eq!((meta.uid(), meta.gid()), (65534, 65534));
+        cg.delete().unwrap();
+    } else {
+        assert!(built.is_err());
+        let h = cgroups_rs::fs::hierarchies::auto();
+        Cgroup::load(h, "test_delegate_to_build").delete().unwrap();
+    }
+}
@@ -5,18 +5,24 @@
 //
 
 //! Simple unit tests about the control groups system.
+use std::path::PathBuf;
 use std::process::Command;
 use std::thread::sleep;
 use std::time::Duration;
 
 use cgroups_rs::fs::cgroup::{
-    CGROUP_MODE_DOMAIN, CGROUP_MODE_DOMAIN_INVALID, CGROUP_MODE_DOMAIN_THREADED,
-    CGROUP_MODE_THREADED,
+    CgroupEvents, CgroupType, CreateOutcome, FreezeStatus, CGROUP_MODE_DOMAIN,
+    CGROUP_MODE_DOMAIN_INVALID, CGROUP_MODE_DOMAIN_THREADED, CGROUP_MODE_THREADED,
 };
+use cgroups_rs::fs::error::ErrorKind;
+use cgroups_rs::fs::freezer::FreezerController;
+use cgroups_rs::fs::hierarchies::V2;
 use cgroups_rs::fs::memory::MemController;
+use cgroups_rs::fs::misc::MiscController;
+use cgroups_rs::fs::pid::PidController;
 use cgroups_rs::fs::Controller;
-use cgroups_rs::fs::{Cgroup, Subsystem};
-use cgroups_rs::CgroupPid;
+use cgroups_rs::fs::{Cgroup, Controllers, MaxValue, Subsystem};
+use cgroups_rs::{CgroupPid, FreezerState};
 
 #[test]
 fn test_procs_iterator_cgroup() {
@@ -42,6 +48,266 @@ fn test_procs_iterator_cgroup() {
     cg.delete().unwrap();
 }
 
+#[test]
+fn test_procs_snapshot() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let pid = libc::pid_t::from(nix::unistd::getpid()) as u64;
+    let cg = Cgroup::new(h, String::from("test_procs_snapshot")).unwrap();
+    {
+        let snapshot = cg.procs_snapshot();
+        assert!(snapshot.stabilized);
+        assert!(snapshot.procs.is_empty());
+
+        cg.add_task_by_tgid(CgroupPid::from(pid)).unwrap();
+        let snapshot = cg.procs_snapshot();
+        assert_eq!(snapshot.procs, vec![CgroupPid::from(pid)]);
+
+        cg.remove_task_by_tgid(CgroupPid::from(pid)).unwrap();
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_get_or_create_race() {
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            std::thread::spawn(|| {
+                let h = cgroups_rs::fs::hierarchies::auto();
+                Cgroup::get_or_create(h, "test_get_or_create_race/a")
+                    .unwrap()
+                    .1
+            })
+        })
+        .collect();
+    let outcomes: Vec<CreateOutcome> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+
+    assert_eq!(
+        outcomes
+            .iter()
+            .filter(|o| **o == CreateOutcome::Created)
+            .count(),
+        1
+    );
+    assert_eq!(
+        outcomes
+            .iter()
+            .filter(|o| **o == CreateOutcome::Adopted)
+            .count(),
+        7
+    );
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let (cg, outcome) = Cgroup::get_or_create(h, "test_get_or_create_race/a").unwrap();
+    assert_eq!(outcome, CreateOutcome::Adopted);
+    cg.delete().unwrap();
+    let h = cgroups_rs::fs::hierarchies::auto();
+    Cgroup::load(h, "test_get_or_create_race").delete().unwrap();
+}
+
+#[test]
+fn test_user_procs_cgroup() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+
+    // kthreadd is always pid 2 and lives in the root control group.
+    let root = h.root_control_group();
+    let kthreadd = CgroupPid::from(2);
+    assert!(!root.user_procs().contains(&kthreadd));
+
+    let cg = Cgroup::new(h, String::from("test_user_procs_cgroup")).unwrap();
+    {
+        let mut child = Command::new("sleep").arg("infinity").spawn().unwrap();
+        let pid = CgroupPid::from(child.id() as u64);
+        cg.add_task_by_tgid(pid).unwrap();
+
+        assert_eq!(cg.user_procs(), vec![pid]);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_verify_empty_via_proc() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_verify_empty_via_proc")).unwrap();
+    {
+        assert!(cg.verify_empty_via_proc().unwrap().is_empty());
+
+        let mut child = Command::new("sleep").arg("infinity").spawn().unwrap();
+        let pid = CgroupPid::from(child.id() as u64);
+        cg.add_task_by_tgid(pid).unwrap();
+        assert_eq!(cg.verify_empty_via_proc().unwrap(), vec![pid]);
+
+        cg.move_task_to_parent_by_tgid(pid).unwrap();
+        assert!(cg.verify_empty_via_proc().unwrap().is_empty());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_all_procs_recursive() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let parent = Cgroup::new(h, String::from("test_all_procs_recursive")).unwrap();
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let a = Cgroup::new(h, String::from("test_all_procs_recursive/a")).unwrap();
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let b = Cgroup::new(h, String::from("test_all_procs_recursive/a/b")).unwrap();
+    {
+        let mut child_a = Command::new("sleep").arg("infinity").spawn().unwrap();
+        let mut child_b = Command::new("sleep").arg("infinity").spawn().unwrap();
+        let pid_a = CgroupPid::from(child_a.id() as u64);
+        let pid_b = CgroupPid::from(child_b.id() as u64);
+        a.add_task_by_tgid(pid_a).unwrap();
+        b.add_task_by_tgid(pid_b).unwrap();
+
+        let mut expected = vec![pid_a, pid_b];
+        expected.sort();
+        assert!(parent.procs().is_empty());
+        assert_eq!(parent.all_procs_recursive().unwrap(), expected);
+        assert_eq!(b.all_procs_recursive().unwrap(), vec![pid_b]);
+
+        child_a.kill().unwrap();
+        child_a.wait().unwrap();
+        child_b.kill().unwrap();
+        child_b.wait().unwrap();
+    }
+    b.delete().unwrap();
+    a.delete().unwrap();
+    parent.delete().unwrap();
+}
+
+#[test]
+fn test_own_procs_and_pids_current_recursive() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let parent = Cgroup::new(h, String::from("test_own_procs")).unwrap();
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let a = Cgroup::new(h, String::from("test_own_procs/a")).unwrap();
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let b = Cgroup::new(h, String::from("test_own_procs/a/b")).unwrap();
+    {
+        let mut child_a = Command::new("sleep").arg("infinity").spawn().unwrap();
+        let mut child_b = Command::new("sleep").arg("infinity").spawn().unwrap();
+        let pid_a = CgroupPid::from(child_a.id() as u64);
+        let pid_b = CgroupPid::from(child_b.id() as u64);
+        a.add_task_by_tgid(pid_a).unwrap();
+        b.add_task_by_tgid(pid_b).unwrap();
+
+        assert!(parent.own_procs().is_empty());
+        assert_eq!(a.own_procs(), vec![pid_a]);
+        assert_eq!(parent.pids_current_recursive().unwrap(), 2);
+        assert_eq!(b.pids_current_recursive().unwrap(), 1);
+
+        child_a.kill().unwrap();
+        child_a.wait().unwrap();
+        child_b.kill().unwrap();
+        child_b.wait().unwrap();
+    }
+    b.delete().unwrap();
+    a.delete().unwrap();
+    parent.delete().unwrap();
+}
+
+#[test]
+fn test_freeze_status() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let parent = Cgroup::new(h, String::from("test_freeze_status")).unwrap();
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let child = Cgroup::new(h, String::from("test_freeze_status/child")).unwrap();
+    {
+        let thawed = FreezeStatus {
+            self_requested: false,
+            effectively_frozen: false,
+        };
+        assert_eq!(child.freeze_status().unwrap(), thawed);
+
+        let parent_freezer: &FreezerController = parent.controller_of().unwrap();
+        parent_freezer.freeze().unwrap();
+        sleep(Duration::from_millis(100));
+        assert_eq!(
+            parent.freeze_status().unwrap(),
+            FreezeStatus {
+                self_requested: true,
+                effectively_frozen: true,
+            }
+        );
+        assert_eq!(
+            child.freeze_status().unwrap(),
+            FreezeStatus {
+                self_requested: false,
+                effectively_frozen: true,
+            }
+        );
+        let child_freezer: &FreezerController = child.controller_of().unwrap();
+        if child.v2() {
+            assert!(child_freezer.self_freezing().is_err());
+            assert!(child_freezer.parent_freezing().is_err());
+        } else {
+            assert!(!child_freezer.self_freezing().unwrap());
+            assert!(child_freezer.parent_freezing().unwrap());
+            assert!(parent_freezer.self_freezing().unwrap());
+        }
+
+        parent_freezer.thaw().unwrap();
+        sleep(Duration::from_millis(100));
+        assert_eq!(child.freeze_status().unwrap(), thawed);
+    }
+    child.delete().unwrap();
+    parent.delete().unwrap();
+}
+
+#[test]
+fn test_freezer_state_v2() {
+    if !cgroups_rs::fs::hierarchies::is_cgroup2_unified_mode() {
+        return;
+    }
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let parent = Cgroup::new(h, String::from("test_freezer_state_v2")).unwrap();
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let child = Cgroup::new(h, String::from("test_freezer_state_v2/child")).unwrap();
+    {
+        let parent_freezer: &FreezerController = parent.controller_of().unwrap();
+        let child_freezer: &FreezerController = child.controller_of().unwrap();
+        assert_eq!(parent_freezer.state().unwrap(), FreezerState::Thawed);
+
+        parent_freezer.freeze().unwrap();
+        sleep(Duration::from_millis(100));
+        assert_eq!(parent_freezer.state().unwrap(), FreezerState::Frozen);
+        // frozen through its parent
+        assert_eq!(child_freezer.state().unwrap(), FreezerState::Frozen);
+
+        parent_freezer.thaw().unwrap();
+        sleep(Duration::from_millis(100));
+        assert_eq!(parent_freezer.state().unwrap(), FreezerState::Thawed);
+        assert_eq!(child_freezer.state().unwrap(), FreezerState::Thawed);
+    }
+    child.delete().unwrap();
+    parent.delete().unwrap();
+}
+
+#[test]
+fn test_freeze_and_wait() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_freeze_and_wait")).unwrap();
+    {
+        let mut child = Command::new("sleep").arg("infinity").spawn().unwrap();
+        cg.add_task_by_tgid(CgroupPid::from(child.id() as u64))
+            .unwrap();
+
+        let freezer: &FreezerController = cg.controller_of().unwrap();
+        freezer.freeze_and_wait(Duration::from_secs(5)).unwrap();
+        assert_eq!(freezer.state().unwrap(), FreezerState::Frozen);
+        freezer.thaw().unwrap();
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+    cg.delete().unwrap();
+}
+
 #[test]
 fn test_tasks_iterator_cgroup_v1() {
     if cgroups_rs::fs::hierarchies::is_cgroup2_unified_mode() {
@@ -95,6 +361,11 @@ fn test_tasks_iterator_cgroup_threaded_mode() {
     {
         // Verify that cgroup type of the control group is domain mode.
         assert_eq!(cg.get_cgroup_type().unwrap(), CGROUP_MODE_DOMAIN);
+        assert!(!cg.is_threaded().unwrap());
+        assert_eq!(
+            cg.thread_count().unwrap_err().kind(),
+            &ErrorKind::CgroupMode
+        );
 
         // Set cgroup type of the sub-control group is thread mode.
         cg_threaded_sub1
@@ -111,10 +382,24 @@ fn test_tasks_iterator_cgroup_threaded_mode() {
             cg_threaded_sub2.get_cgroup_type().unwrap(),
             CGROUP_MODE_DOMAIN_INVALID
         );
+        assert_eq!(
+            cg_threaded_sub2.cgroup_type().unwrap(),
+            CgroupType::DomainInvalid
+        );
+        // A domain invalid control group rejects processes.
+        assert_eq!(
+            cg_threaded_sub2
+                .add_task_by_tgid(CgroupPid::from(pid))
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::DomainInvalid
+        );
         // Verify whether the cgroup type of the parent control group of
         // the control group whose cgroup type is set to thread mode is
         // domain thread mode.
         assert_eq!(cg.get_cgroup_type().unwrap(), CGROUP_MODE_DOMAIN_THREADED);
+        assert!(cg.is_threaded().unwrap());
+        assert!(cg_threaded_sub1.is_threaded().unwrap());
 
         // Set cgroup type of the sub-control group is thread mode.
         cg_threaded_sub2
@@ -141,6 +426,7 @@ fn test_tasks_iterator_cgroup_threaded_mode() {
         // Verify that the task is indeed in the xcontrol group
         assert_eq!(tasks.next(), Some(CgroupPid::from(pid)));
         assert_eq!(tasks.next(), None);
+        assert_eq!(cg_threaded_sub1.thread_count().unwrap(), 1);
 
         // Now, try move it to parent.
         cg_threaded_sub1
@@ -202,6 +488,100 @@ fn test_kill_cgroup() {
             }
         };
         assert!(status.is_some());
+        assert!(cg.procs().is_empty());
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_kill_cgroup_v1() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    if h.v2() {
+        return;
+    }
+    let cg = Cgroup::new(h, String::from("test_kill_cgroup_v1")).unwrap();
+    {
+        let err = cg.kill().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NotSupported { .. }));
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_kill_and_wait() {
+    if !cgroups_rs::fs::hierarchies::is_cgroup2_unified_mode() {
+        return;
+    }
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_kill_and_wait")).unwrap();
+    {
+        let mut child = Command::new("sleep").arg("infinity").spawn().unwrap();
+        cg.add_task_by_tgid(CgroupPid::from(child.id() as u64))
+            .unwrap();
+
+        cg.kill_and_wait(Duration::from_secs(5)).unwrap();
+        assert!(cg.procs().is_empty());
+        child.wait().unwrap();
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_try_controller_of() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_try_controller_of")).unwrap();
+    {
+        let pids = cg.try_controller_of::<PidController>();
+        assert_eq!(pids.is_ok(), cg.controller_of::<PidController>().is_some());
+        assert!(cg.try_controller_of::<FreezerController>().is_ok());
+        if !cg.v2() {
+            // misc only exists on v2
+            let err = cg.try_controller_of::<MiscController>().unwrap_err();
+            assert_eq!(
+                err.kind(),
+                &ErrorKind::ControllerNotAvailable("misc".to_string())
+            );
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cached_handles() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cached_handles"))
+        .unwrap()
+        .with_cached_handles();
+    {
+        let pids: &PidController = cg.controller_of().unwrap();
+        for max in [10, 20, 30] {
+            pids.set_pid_max(MaxValue::Value(max)).unwrap();
+            assert_eq!(pids.get_pid_max().unwrap(), MaxValue::Value(max));
+        }
+
+        cg.release_cached_handles();
+        pids.set_pid_max(MaxValue::Max).unwrap();
+        assert_eq!(pids.get_pid_max().unwrap(), MaxValue::Max);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cgroup_events() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cgroup_events")).unwrap();
+    {
+        assert_eq!(cg.events().unwrap(), CgroupEvents::default());
+
+        let mut child = Command::new("sleep").arg("infinity").spawn().unwrap();
+        cg.add_task_by_tgid(CgroupPid::from(child.id() as u64))
+            .unwrap();
+        // v1 has no cgroup.events
+        assert_eq!(cg.events().unwrap().populated, cg.v2());
+        assert!(!cg.events().unwrap().frozen);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
     }
     cg.delete().unwrap();
 }
@@ -273,3 +653,624 @@ fn test_cgroup_v2() {
 
     cg.delete().unwrap();
 }
+
+#[test]
+fn test_pressure_accounting() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_pressure_accounting")).unwrap();
+    {
+        if !cg.v2() {
+            let err = cg.pressure_accounting().unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::NotSupported { .. }));
+            assert_eq!(
+                err.to_string(),
+                "cgroup.pressure is not supported: only available on cgroup v2"
+            );
+        } else if cg.pressure_accounting().is_ok() {
+            cg.set_pressure_accounting(false).unwrap();
+            assert!(!cg.pressure_accounting().unwrap());
+            cg.set_pressure_accounting(true).unwrap();
+            assert!(cg.pressure_accounting().unwrap());
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cgroup_custom_root() {
+    // A fake cgroup2 file system, no need for it to be mounted.
+    let root = std::env::temp_dir().join(format!("cgroups-rs-root-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("cgroup.controllers"), "cpu memory pids").unwrap();
+
+    let h = Box::new(V2::with_root(root.to_str().unwrap()));
+    let cg = Cgroup::new(h, String::from("parent/child")).unwrap();
+    assert!(root.join("parent/child").is_dir());
+    assert_eq!(
+        std::fs::read_to_string(root.join("parent/cgroup.subtree_control")).unwrap(),
+        "+pids"
+    );
+
+    let mem_controller: &MemController = cg.controller_of().unwrap();
+    assert_eq!(mem_controller.path(), root.join("parent/child"));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_cgroup_fs_path() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let root = h.root_control_group();
+    assert_eq!(root.relative_path(), "");
+
+    let cg = Cgroup::new(h, String::from("test_cgroup_fs_path/child")).unwrap();
+    {
+        assert_eq!(cg.relative_path(), "test_cgroup_fs_path/child");
+        assert!(cg.fs_path().is_dir());
+        assert!(cg.fs_path().ends_with("test_cgroup_fs_path/child"));
+
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        assert_eq!(
+            cg.fs_path_for(Controllers::Mem),
+            Some(mem_controller.path().to_path_buf())
+        );
+        if cg.v2() {
+            assert_eq!(root.fs_path(), PathBuf::from("/sys/fs/cgroup"));
+        }
+    }
+    cg.delete().unwrap();
+    Cgroup::load(
+        cgroups_rs::fs::hierarchies::auto(),
+        String::from("test_cgroup_fs_path"),
+    )
+    .delete()
+    .unwrap();
+}
+
+#[test]
+fn test_enable_disable_subtree_controllers() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_enable_disable_subtree_controllers")).unwrap();
+    if !cg.v2() {
+        assert!(cg.enable_subtree_controllers(&[Controllers::Pids]).is_err());
+        assert!(cg
+            .disable_subtree_controllers(&[Controllers::Pids])
+            .is_err());
+    } else {
+        cg.set_subtree_controllers(&[]).unwrap();
+        cg.enable_subtree_controllers(&[Controllers::Pids]).unwrap();
+        cg.enable_subtree_controllers(&[Controllers::Mem, Controllers::Pids])
+            .unwrap();
+        let mut enabled = cg.subtree_control().unwrap();
+        enabled.sort_by_key(|c| c.to_string());
+        assert_eq!(enabled, vec![Controllers::Mem, Controllers::Pids]);
+
+        cg.disable_subtree_controllers(&[Controllers::Mem]).unwrap();
+        assert_eq!(cg.subtree_control().unwrap(), vec![Controllers::Pids]);
+        // disabling a controller that isn't enabled is a no-op
+        cg.disable_subtree_controllers(&[Controllers::Mem]).unwrap();
+        cg.disable_subtree_controllers(&[Controllers::Pids])
+            .unwrap();
+        assert!(cg.subtree_control().unwrap().is_empty());
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_set_subtree_controllers() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_set_subtree_controllers")).unwrap();
+    if !cg.v2() {
+        assert!(cg.set_subtree_controllers(&[Controllers::Mem]).is_err());
+    } else {
+        cg.set_subtree_controllers(&[Controllers::Mem, Controllers::Pids])
+            .unwrap();
+        let mut enabled = cg.subtree_control().unwrap();
+        enabled.sort_by_key(|c| c.to_string());
+        assert_eq!(enabled, vec![Controllers::Mem, Controllers::Pids]);
+
+        cg.set_subtree_controllers(&[Controllers::Pids]).unwrap();
+        assert_eq!(cg.subtree_control().unwrap(), vec![Controllers::Pids]);
+        cg.set_subtree_controllers(&[]).unwrap();
+
+        // controllers can't be enabled for the children while the control group has processes
+        let mut child = Command::new("sleep").arg("infinity").spawn().unwrap();
+        cg.add_task_by_tgid(CgroupPid::from(child.id() as u64))
+            .unwrap();
+        let err = cg
+            .set_subtree_controllers(&[Controllers::Pids])
+            .unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::ControllerEnableBlockedByProcesses("pids".to_string())
+        );
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_managed_files() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_managed_files")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        let files = mem_controller.managed_files();
+        if cg.v2() {
+            assert!(files.contains(&"memory.max"));
+            assert!(!files.contains(&"memory.limit_in_bytes"));
+        } else {
+            assert!(files.contains(&"memory.limit_in_bytes"));
+            assert!(!files.contains(&"memory.max"));
+        }
+
+        // The files of the pids controller exist on both versions.
+        for sub in cg.subsystems() {
+            if let Subsystem::Pid(pids) = sub {
+                for file in pids.managed_files() {
+                    assert!(pids.path().join(file).exists(), "{} is missing", file);
+                }
+            }
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_create_with_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::create_with_mode(h, "test_create_with_mode", 0o750).unwrap();
+    {
+        for sub in cg.subsystems() {
+            let mode = std::fs::metadata(sub.to_controller().path())
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o7777, 0o750);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_can_write() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_can_write")).unwrap();
+    {
+        // the tests run as root
+        assert!(cg.can_write(Controllers::Pids));
+        assert!(cg.can_write(Controllers::Mem));
+        if !cg.v2() {
+            // not a v1 controller
+            assert!(!cg.can_write(Controllers::Misc));
+        }
+    }
+    cg.delete().unwrap();
+
+    // the root control group has no limits
+    let root = cgroups_rs::fs::hierarchies::auto().root_control_group();
+    assert!(!root.can_write(Controllers::Pids));
+}
+
+#[test]
+fn test_create_nested_from_scratch() {
+    let names = [
+        "test_create_nested_from_scratch",
+        "test_create_nested_from_scratch/deep",
+        "test_create_nested_from_scratch/deep/nested",
+    ];
+    let h = cgroups_rs::fs::hierarchies::auto();
+    assert!(!Cgroup::load(h, names[0]).exists());
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, names[2]).unwrap();
+    assert!(cg.exists());
+    for name in &names[..2] {
+        let h = cgroups_rs::fs::hierarchies::auto();
+        let ancestor = Cgroup::load(h, *name);
+        assert!(ancestor.exists());
+        if ancestor.v2() {
+            assert!(ancestor
+                .subtree_control()
+                .unwrap()
+                .contains(&Controllers::Pids));
+        }
+    }
+
+    // creating it again, or one of its ancestors, isn't an error
+    let h = cgroups_rs::fs::hierarchies::auto();
+    Cgroup::new(h, names[2]).unwrap();
+    let h = cgroups_rs::fs::hierarchies::auto();
+    Cgroup::new(h, names[1]).unwrap();
+
+    for name in names.iter().rev() {
+        let h = cgroups_rs::fs::hierarchies::auto();
+        let level = Cgroup::load(h, *name);
+        level.delete().unwrap();
+        assert!(!level.exists());
+    }
+}
+
+#[test]
+fn test_set_type() {
+    // only threaded controllers, a domain one enabled for the children prevents a threaded child
+    let cg = Cgroup::new_with_specified_controllers(
+        cgroups_rs::fs::hierarchies::auto(),
+        String::from("test_set_type"),
+        Some(vec![String::from("pids")]),
+    )
+    .unwrap();
+    let sub = Cgroup::new_with_specified_controllers(
+        cgroups_rs::fs::hierarchies::auto(),
+        String::from("test_set_type/sub"),
+        Some(vec![String::from("pids")]),
+    )
+    .unwrap();
+    if !cg.v2() {
+        assert!(sub.set_type(CgroupType::Threaded).is_err());
+    } else {
+        sub.set_type(CgroupType::Threaded).unwrap();
+        assert_eq!(sub.cgroup_type().unwrap(), CgroupType::Threaded);
+        assert_eq!(cg.cgroup_type().unwrap(), CgroupType::DomainThreaded);
+
+        // a threaded control group can't go back to being a domain
+        let err = sub.set_type(CgroupType::Domain).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::CgroupTypeRejected("domain".to_string())
+        );
+
+        // threads of the processes of the subtree can be moved to the threaded control group
+        let mut child = Command::new("sleep").arg("infinity").spawn().unwrap();
+        let pid = CgroupPid::from(child.id() as u64);
+        cg.add_task_by_tgid(pid).unwrap();
+        sub.add_thread(pid).unwrap();
+        assert_eq!(sub.threads(), vec![pid]);
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+    sub.delete().unwrap();
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_watch_events() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_watch_events")).unwrap();
+    if !cg.v2() {
+        assert!(cg.watch_events().is_err());
+        cg.delete().unwrap();
+        return;
+    }
+
+    let mut child = Command::new("sleep").arg("infinity").spawn().unwrap();
+    cg.add_task_by_tgid(CgroupPid::from(child.id() as u64))
+        .unwrap();
+    let rx = cg.watch_events().unwrap();
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    // the kernel notifies that the subtree isn't populated anymore
+    let events = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(!events.populated);
+
+    // the watcher stops once the control group is gone
+    cg.delete().unwrap();
+    let res = rx.recv_timeout(Duration::from_secs(5));
+    assert_eq!(res, Err(std::sync::mpsc::RecvTimeoutError::Disconnected));
+}
+
+#[test]
+fn test_tree_limits() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_tree_limits")).unwrap();
+    if !cg.v2() {
+        assert!(cg.set_max_descendants(MaxValue::Value(1)).is_err());
+        assert!(cg.max_depth().is_err());
+        cg.delete().unwrap();
+        return;
+    }
+
+    assert_eq!(cg.max_descendants().unwrap(), MaxValue::Max);
+    assert_eq!(cg.max_depth().unwrap(), MaxValue::Max);
+
+    cg.set_max_descendants(MaxValue::Value(1)).unwrap();
+    assert_eq!(cg.max_descendants().unwrap(), MaxValue::Value(1));
+    let child = Cgroup::new(
+        cgroups_rs::fs::hierarchies::auto(),
+        String::from("test_tree_limits/child"),
+    )
+    .unwrap();
+    assert!(Cgroup::new(
+        cgroups_rs::fs::hierarchies::auto(),
+        String::from("test_tree_limits/other")
+    )
+    .is_err());
+
+    cg.set_max_descendants(MaxValue::Max).unwrap();
+    cg.set_max_depth(MaxValue::Value(1)).unwrap();
+    assert_eq!(cg.max_depth().unwrap(), MaxValue::Value(1));
+    assert!(Cgroup::new(
+        cgroups_rs::fs::hierarchies::auto(),
+        String::from("test_tree_limits/child/grandchild")
+    )
+    .is_err());
+
+    child.delete().unwrap();
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_procs_iter() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_procs_iter")).unwrap();
+    {
+        assert_eq!(cg.procs_iter().next(), None);
+
+        let mut child_a = Command::new("sleep").arg("infinity").spawn().unwrap();
+        let mut child_b = Command::new("sleep").arg("infinity").spawn().unwrap();
+        let pid_a = CgroupPid::from(child_a.id() as u64);
+        let pid_b = CgroupPid::from(child_b.id() as u64);
+        cg.add_task_by_tgid(pid_a).unwrap();
+        cg.add_task_by_tgid(pid_b).unwrap();
+
+        let mut procs: Vec<_> = cg.procs_iter().collect();
+        procs.sort();
+        assert_eq!(procs, cg.procs());
+        let mut tasks: Vec<_> = cg.tasks_iter().collect();
+        tasks.sort();
+        assert_eq!(tasks, cg.tasks());
+        assert!(cg.procs_iter().any(|pid| pid == pid_b));
+
+        child_a.kill().unwrap();
+        child_a.wait().unwrap();
+        child_b.kill().unwrap();
+        child_b.wait().unwrap();
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_load_existing() {
+    let auto = cgroups_rs::fs::hierarchies::auto;
+    assert!(!Cgroup::exists_in(auto(), "test_load_existing"));
+    let err = Cgroup::load_existing(auto(), "test_load_existing").unwrap_err();
+    assert_eq!(
+        err.kind(),
+        &ErrorKind::NotFound("test_load_existing".to_string())
+    );
+
+    let cg = Cgroup::new(auto(), String::from("test_load_existing")).unwrap();
+    assert!(Cgroup::exists_in(auto(), "test_load_existing"));
+    let loaded = Cgroup::load_existing(auto(), "test_load_existing").unwrap();
+    assert_eq!(loaded.path(), cg.path());
+    cg.delete().unwrap();
+
+    assert!(!Cgroup::exists_in(auto(), "test_load_existing"));
+    assert!(Cgroup::exists_in(auto(), ""));
+}
+
+#[test]
+fn test_invalid_name() {
+    let auto = cgroups_rs::fs::hierarchies::auto;
+    for name in ["test_invalid_name/..", "test_invalid_name/cgroup.procs"] {
+        let err = Cgroup::new(auto(), name).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidName(_)));
+        assert!(Cgroup::load_existing(auto(), name).is_err());
+    }
+    assert!(!Cgroup::exists_in(auto(), "test_invalid_name"));
+
+    let cg = Cgroup::new(auto(), "test_invalid_name.slice/foo.service").unwrap();
+    assert_eq!(cg.parent_control_group().path(), "test_invalid_name.slice");
+    cg.delete().unwrap();
+    cg.parent_control_group().delete().unwrap();
+}
+
+#[test]
+fn test_root_parent_control_group() {
+    let root = Cgroup::load(cgroups_rs::fs::hierarchies::auto(), "");
+    assert_eq!(root.parent_control_group().path(), "");
+}
+
+#[test]
+fn test_subsystem_try_from() {
+    use std::convert::TryFrom;
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, "test_subsystem_try_from").unwrap();
+    for sub in cg.subsystems() {
+        let is_pids = matches!(sub, Subsystem::Pid(_));
+        match <&PidController>::try_from(sub) {
+            Ok(pids) => assert!(is_pids && pids.path() == sub.to_controller().path()),
+            Err(e) => {
+                assert!(!is_pids);
+                assert!(matches!(e.kind(), ErrorKind::ControllerNotFound(c) if c == "pids"));
+            }
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_move_all_tasks_to() {
+    let h = cgroups_rs::fs::hierarchies::auto;
+    let src = Cgroup::new(h(), "test_move_all_tasks_to/src").unwrap();
+    let dest = Cgroup::new(h(), "test_move_all_tasks_to/dest").unwrap();
+    {
+        assert_eq!(src.move_all_tasks_to(&dest).unwrap(), 0);
+
+        let mut children: Vec<_> = (0..2)
+            .map(|_| Command::new("sleep").arg("infinity").spawn().unwrap())
+            .collect();
+        let mut pids: Vec<_> = children
+            .iter()
+            .map(|c| CgroupPid::from(c.id() as u64))
+            .collect();
+        pids.sort();
+        for pid in &pids {
+            src.add_task_by_tgid(*pid).unwrap();
+        }
+
+        assert_eq!(src.move_all_tasks_to(&dest).unwrap(), 2);
+        assert!(src.procs().is_empty());
+        let mut moved = dest.procs();
+        moved.sort();
+        assert_eq!(moved, pids);
+
+        for child in &mut children {
+            child.kill().unwrap();
+            child.wait().unwrap();
+        }
+    }
+    src.delete().unwrap();
+    dest.delete().unwrap();
+    src.parent_control_group().delete().unwrap();
+}
+
+#[test]
+fn test_cgroup_stat() {
+    let h = cgroups_rs::fs::hierarchies::auto;
+    let parent = Cgroup::new(h(), "test_cgroup_stat").unwrap();
+    let child = Cgroup::new(h(), "test_cgroup_stat/child").unwrap();
+    if parent.v2() {
+        let stat = parent.cgroup_stat().unwrap();
+        assert_eq!(stat.nr_descendants, 1);
+        assert_eq!(child.cgroup_stat().unwrap().nr_descendants, 0);
+
+        child.delete().unwrap();
+        assert_eq!(parent.cgroup_stat().unwrap().nr_descendants, 0);
+    } else {
+        let err = parent.cgroup_stat().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NotSupported { .. }));
+        child.delete().unwrap();
+    }
+    parent.delete().unwrap();
+}
+
+#[test]
+fn test_delegate_to() {
+    use std::os::unix::fs::MetadataExt;
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, "test_delegate_to").unwrap();
+    if cg.v2() {
+        cg.delegate_to(65534, 65534).unwrap();
+        let owner = |file: &str| {
+            let meta = std::fs::metadata(cg.fs_path().join(file)).unwrap();
+            (meta.uid(), meta.gid())
+        };
+        assert_eq!(owner(""), (65534, 65534));
+        assert_eq!(owner("cgroup.procs"), (65534, 65534));
+        assert_eq!(owner("cgroup.subtree_control"), (65534, 65534));
+        assert_eq!(owner("cgroup.max.depth"), (0, 0));
+    } else {
+        let err = cg.delegate_to(65534, 65534).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NotSupported { .. }));
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_add_tasks() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, "test_add_tasks").unwrap();
+    {
+        let mut children: Vec<_> = (0..3)
+            .map(|_| Command::new("sleep").arg("infinity").spawn().unwrap())
+            .collect();
+        let mut pids: Vec<_> = children
+            .iter()
+            .map(|c| CgroupPid::from(c.id() as u64))
+            .collect();
+        pids.sort();
+
+        let mut exited = Command::new("true").spawn().unwrap();
+        exited.wait().unwrap();
+        let exited = CgroupPid::from(exited.id() as u64);
+
+        let mut tasks = pids.clone();
+        tasks.insert(1, exited);
+        let failed = cg.add_tasks(&tasks).unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, exited);
+
+        let mut procs = cg.procs();
+        procs.sort();
+        assert_eq!(procs, pids);
+
+        for child in &mut children {
+            child.kill().unwrap();
+            child.wait().unwrap();
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_auto_for() {
+    use cgroups_rs::fs::hierarchies::{auto_for, hierarchy_mode, HierarchyMode};
+
+    if hierarchy_mode() != HierarchyMode::Hybrid {
+        return;
+    }
+
+    // the controllers of the v2 hierarchy are found, whether they are bound to v1 or not
+    let v2 = V2::from_mountinfo().unwrap();
+    for controller in v2.mount_map().into_keys() {
+        let h = auto_for(controller.clone());
+        assert!(h
+            .subsystems()
+            .iter()
+            .any(|s| s.to_controller().control_type() == controller));
+    }
+}
+
+#[test]
+fn test_delete_recursive() {
+    let h = cgroups_rs::fs::hierarchies::auto;
+    let top = Cgroup::new(h(), "test_delete_recursive").unwrap();
+    let leaf = Cgroup::new(h(), "test_delete_recursive/a/b").unwrap();
+    Cgroup::new(h(), "test_delete_recursive/c").unwrap();
+    {
+        let mut child = Command::new("sleep").arg("infinity").spawn().unwrap();
+        let pid = CgroupPid::from(child.id() as u64);
+        leaf.add_task_by_tgid(pid).unwrap();
+
+        // a subtree with processes isn't retried
+        let err = top
+            .delete_with_retry(100, Duration::from_millis(100))
+            .unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::RemoveFailed);
+        assert!(leaf.exists());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+    top.delete_with_retry(5, Duration::from_millis(10)).unwrap();
+    assert!(!top.exists());
+    assert!(!leaf.exists());
+
+    // nothing left to delete
+    top.delete_recursive().unwrap();
+}
+
+#[test]
+fn test_is_root_of_delegated_hierarchy() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    assert!(h.root_control_group().is_root());
+
+    let cg = Cgroup::new(h, String::from("test_is_root_of_delegated_hierarchy")).unwrap();
+    {
+        assert!(!cg.is_root());
+        if cg.v2() {
+            // the top of a hierarchy rooted at a control group, as in a container
+            let root = V2::with_root(cg.fs_path().to_string_lossy());
+            let top = Cgroup::load(Box::new(root), "");
+            assert!(!top.is_root());
+        }
+    }
+    cg.delete().unwrap();
+}
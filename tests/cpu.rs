@@ -5,7 +5,8 @@
 
 //! Simple unit tests about the CPU control groups system.
 use cgroups_rs::fs::cpu::CpuController;
-use cgroups_rs::fs::Cgroup;
+use cgroups_rs::fs::error::ErrorKind;
+use cgroups_rs::fs::{Cgroup, Controller};
 
 #[test]
 fn test_cfs_quota_and_periods() {
@@ -59,3 +60,138 @@ fn test_cfs_quota_and_periods() {
 
     cg.delete().unwrap();
 }
+
+#[test]
+fn test_cpu_stat() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpu_stat")).unwrap();
+    {
+        let cpu_controller: &CpuController = cg.controller_of().unwrap();
+        let stat = cpu_controller.cpu_stat().unwrap();
+        assert!(stat.usage_usec >= stat.user_usec);
+        assert!(stat.nr_periods >= stat.nr_throttled);
+        if !cg.v2() {
+            assert_eq!(stat.usage_usec, 0);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_unified_stat() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_unified_stat")).unwrap();
+    {
+        let cpu_controller: &CpuController = cg.controller_of().unwrap();
+        let stat = cpu_controller.unified_stat().unwrap();
+        assert_eq!(stat.usage_usec, 0);
+        assert!(stat.nr_throttled <= stat.nr_periods);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_effective_quota_ratio() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let parent = Cgroup::new(h, String::from("test_effective_quota_ratio")).unwrap();
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let child = Cgroup::new(h, String::from("test_effective_quota_ratio/child")).unwrap();
+    {
+        let parent_cpu: &CpuController = parent.controller_of().unwrap();
+        let child_cpu: &CpuController = child.controller_of().unwrap();
+        assert_eq!(child_cpu.effective_quota_ratio().unwrap(), None);
+
+        parent_cpu
+            .set_cfs_quota_and_period(Some(50000), Some(100000))
+            .unwrap();
+        assert_eq!(parent_cpu.effective_quota_ratio().unwrap(), Some(0.5));
+        assert_eq!(child_cpu.effective_quota_ratio().unwrap(), Some(0.5));
+
+        child_cpu
+            .set_cfs_quota_and_period(Some(20000), Some(100000))
+            .unwrap();
+        assert_eq!(child_cpu.effective_quota_ratio().unwrap(), Some(0.2));
+        assert_eq!(parent_cpu.effective_quota_ratio().unwrap(), Some(0.5));
+    }
+    child.delete().unwrap();
+    parent.delete().unwrap();
+}
+
+#[test]
+fn test_set_cpu_limit() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_set_cpu_limit")).unwrap();
+    {
+        let cpu: &CpuController = cg.controller_of().unwrap();
+
+        cpu.set_cpu_limit(1.5).unwrap();
+        assert_eq!(cpu.cfs_quota().unwrap(), 150000);
+        assert_eq!(cpu.cfs_period().unwrap(), 100000);
+        assert_eq!(cpu.effective_quota_ratio().unwrap(), Some(1.5));
+
+        cpu.clear_cpu_limit().unwrap();
+        assert_eq!(cpu.effective_quota_ratio().unwrap(), None);
+
+        cpu.set_cpu_limit(0.25).unwrap();
+        cpu.set_cpu_limit(0.0).unwrap();
+        assert_eq!(cpu.effective_quota_ratio().unwrap(), None);
+
+        assert!(cpu.set_cpu_limit(-1.0).is_err());
+        assert!(cpu.set_cpu_limit(f64::NAN).is_err());
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_throttle_ratio() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_throttle_ratio")).unwrap();
+    {
+        let cpu: &CpuController = cg.controller_of().unwrap();
+        assert_eq!(cpu.throttle_ratio().unwrap(), 0.0);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpu_pressure() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpu_pressure")).unwrap();
+    {
+        let cpu: &CpuController = cg.controller_of().unwrap();
+        if !cpu.v2() {
+            assert!(cpu.pressure().is_err());
+        } else if cpu.path().join("cpu.pressure").exists() {
+            // nothing ran in the control group
+            assert_eq!(cpu.pressure().unwrap().some.total, 0);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_uclamp() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_uclamp")).unwrap();
+    {
+        let cpu: &CpuController = cg.controller_of().unwrap();
+        if !cpu.v2() {
+            assert!(cpu.set_uclamp_min(20.0).is_err());
+            assert!(cpu.uclamp_max().is_err());
+        } else if cpu.path().join("cpu.uclamp.min").exists() {
+            assert_eq!(cpu.uclamp_min().unwrap(), 0.0);
+            assert_eq!(cpu.uclamp_max().unwrap(), 100.0);
+            cpu.set_uclamp_min(20.0).unwrap();
+            cpu.set_uclamp_max(80.0).unwrap();
+            assert_eq!(cpu.uclamp_min().unwrap(), 20.0);
+            assert_eq!(cpu.uclamp_max().unwrap(), 80.0);
+            cpu.set_uclamp_max(100.0).unwrap();
+            assert_eq!(cpu.uclamp_max().unwrap(), 100.0);
+            assert!(matches!(
+                cpu.set_uclamp_min(150.0).unwrap_err().kind(),
+                ErrorKind::InvalidArgument(_)
+            ));
+        }
+    }
+    cg.delete().unwrap();
+}
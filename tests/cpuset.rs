@@ -5,7 +5,7 @@
 //
 use std::fs;
 
-use cgroups_rs::fs::cpuset::CpuSetController;
+use cgroups_rs::fs::cpuset::{CpuList, CpuSetController, CpuSetPartition, IsolatedCpusCheck};
 use cgroups_rs::fs::error::ErrorKind;
 use cgroups_rs::fs::Cgroup;
 use cgroups_rs::CgroupPid;
@@ -61,6 +61,28 @@ fn test_cpuset_set_cpus() {
     cg.delete().unwrap();
 }
 
+#[test]
+fn test_cpuset_lists() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_lists")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+
+        let effective_mems = cpuset.effective_mems_list().unwrap();
+        let first_mem = effective_mems.iter().next().unwrap();
+        cpuset.set_cpus_list(&CpuList::from(vec![0])).unwrap();
+        cpuset
+            .set_mems_list(&CpuList::from(vec![first_mem]))
+            .unwrap();
+
+        assert_eq!(cpuset.cpus_list().unwrap().to_string(), "0");
+        assert_eq!(cpuset.mems_list().unwrap(), CpuList::from(vec![first_mem]));
+        assert!(cpuset.effective_cpus_list().unwrap().contains(0));
+        assert!(cpuset.unmet_cpus().unwrap().is_empty());
+    }
+    cg.delete().unwrap();
+}
+
 #[test]
 fn test_cpuset_set_cpus_add_task() {
     let h = cgroups_rs::fs::hierarchies::auto();
@@ -90,3 +112,120 @@ fn test_cpuset_set_cpus_add_task() {
 
     cg.delete().unwrap();
 }
+
+#[test]
+fn test_cpuset_effective_cpus() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let parent = Cgroup::new(h, String::from("test_cpuset_effective_cpus")).unwrap();
+    {
+        let cpuset: &CpuSetController = parent.controller_of().unwrap();
+        assert!(!cpuset.effective_cpus().unwrap().is_empty());
+        assert_eq!(
+            cpuset.cpuset().effective_cpus,
+            cpuset.effective_cpus().unwrap()
+        );
+        cpuset.set_cpus("0").unwrap();
+    }
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let child = Cgroup::new(h, String::from("test_cpuset_effective_cpus/child")).unwrap();
+    {
+        // the child only gets the CPUs its parent grants
+        let cpuset: &CpuSetController = child.controller_of().unwrap();
+        assert_eq!(cpuset.effective_cpus().unwrap(), vec![(0, 0)]);
+        assert!(cpuset.unmet_cpus().unwrap().is_empty());
+    }
+    child.delete().unwrap();
+    parent.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_watch_effective_mems() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_watch_effective_mems")).unwrap();
+    let rx = {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        assert!(!cpuset.effective_mems().unwrap().is_empty());
+        cpuset.watch_effective_mems().unwrap()
+    };
+    cg.delete().unwrap();
+
+    // The watcher stops once the control group is gone.
+    let res = rx.recv_timeout(std::time::Duration::from_secs(5));
+    assert_eq!(res, Err(std::sync::mpsc::RecvTimeoutError::Disconnected));
+}
+
+#[test]
+fn test_cpuset_partition_root() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_partition_root")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        if cg.v2() {
+            assert_eq!(cpuset.partition().unwrap(), CpuSetPartition::Member);
+            assert!(cpuset.partition_root().unwrap().is_none());
+        } else {
+            assert!(matches!(
+                cpuset.partition_root().unwrap_err().kind(),
+                ErrorKind::NotSupported { .. }
+            ));
+            assert!(matches!(
+                cpuset
+                    .make_root_partition("0", IsolatedCpusCheck::Error)
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::NotSupported { .. }
+            ));
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_relax_domain_level_checked() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_relax_domain_level_checked")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        if cg.v2() {
+            assert!(cpuset.set_relax_domain_level_checked(1).is_err());
+        } else {
+            assert!(cpuset.set_relax_domain_level_checked(-2).is_err());
+            cpuset.set_relax_domain_level_checked(1).unwrap();
+            assert_eq!(cpuset.cpuset().sched_relax_domain_level, 1);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_unmet_cpus() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_unmet_cpus")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+
+        // The CPUs inherited from the parent are all granted.
+        assert_eq!(cpuset.unmet_cpus().unwrap(), vec![]);
+
+        cpuset.set_cpus("0").unwrap();
+        assert_eq!(cpuset.unmet_cpus().unwrap(), vec![]);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_load_balanced() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_load_balanced")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        // A new control group is load balanced on both versions.
+        assert!(cpuset.load_balanced().unwrap());
+        if !cg.v2() {
+            cpuset.set_load_balancing(false).unwrap();
+            assert!(!cpuset.load_balanced().unwrap());
+        }
+    }
+    cg.delete().unwrap();
+}
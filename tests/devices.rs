@@ -6,8 +6,12 @@
 
 //! Integration tests about the devices subsystem
 
-use cgroups_rs::fs::devices::{DevicePermissions, DeviceType, DevicesController};
-use cgroups_rs::fs::{Cgroup, DeviceResource};
+use cgroups_rs::fs::devices::{
+    resolve_block_device, DevicePermissions, DeviceType, DevicesController,
+};
+use cgroups_rs::fs::error::ErrorKind;
+use cgroups_rs::fs::{Cgroup, DeviceResource, Resources};
+use std::path::PathBuf;
 
 #[test]
 fn test_devices_parsing() {
@@ -71,3 +75,86 @@ fn test_devices_parsing() {
     }
     cg.delete().unwrap();
 }
+
+#[test]
+fn test_devices_mknod_only_rule() {
+    if cgroups_rs::fs::hierarchies::is_cgroup2_unified_mode() {
+        return;
+    }
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = cgroups_rs::fs::cgroup_builder::CgroupBuilder::new("test_devices_mknod_only_rule")
+        .devices()
+        .device(-1, -1, DeviceType::All, false, DevicePermissions::all())
+        .rule(DeviceResource::mknod_only(DeviceType::Char, 1, 3))
+        .rule(DeviceResource::rw(DeviceType::Char, 1, 5))
+        .done()
+        .build(h)
+        .unwrap();
+    {
+        let devices: &DevicesController = cg.controller_of().unwrap();
+        assert_eq!(
+            devices.allowed_devices().unwrap(),
+            vec![
+                DeviceResource::mknod_only(DeviceType::Char, 1, 3),
+                DeviceResource::rw(DeviceType::Char, 1, 5),
+            ]
+        );
+        let err = devices
+            .allow_device(DeviceType::Char, 1, 7, &[])
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidArgument(_)));
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_devices_v2_not_supported() {
+    if !cgroups_rs::fs::hierarchies::is_cgroup2_unified_mode() {
+        return;
+    }
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_devices_v2_not_supported")).unwrap();
+    {
+        // v2 has no devices controller, the device rules are reported as not supported
+        // rather than failing to read devices.list
+        let devices = DevicesController::new(cg.fs_path(), PathBuf::from("/"));
+        let err = devices.allowed_devices().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NotSupported { .. }));
+        assert!(devices
+            .allow_device(DeviceType::Char, 1, 3, &DevicePermissions::all())
+            .is_err());
+
+        let mut res = Resources::default();
+        res.devices
+            .devices
+            .push(DeviceResource::rw(DeviceType::Char, 1, 5));
+        let err = cg.apply(&res).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NotSupported { .. }));
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_resolve_block_device() {
+    // Every entry of /sys/dev/block is named after the numbers of a block device.
+    if let Some(entry) = std::fs::read_dir("/sys/dev/block")
+        .ok()
+        .and_then(|mut dir| dir.next())
+    {
+        let entry = entry.unwrap();
+        let numbers = entry.file_name().into_string().unwrap();
+        let (major, minor) = numbers.split_once(':').unwrap();
+        let (major, minor) = (major.parse().unwrap(), minor.parse().unwrap());
+
+        let name = resolve_block_device(major, minor).unwrap();
+        assert!(std::path::Path::new("/sys/class/block")
+            .join(&name)
+            .exists());
+        // The second lookup is served from the cache.
+        assert_eq!(resolve_block_device(major, minor), Some(name));
+    }
+
+    assert_eq!(resolve_block_device(0, 0), None);
+}
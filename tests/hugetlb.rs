@@ -6,7 +6,8 @@
 //! Integration tests about the hugetlb subsystem
 use cgroups_rs::fs::error::*;
 use cgroups_rs::fs::hugetlb::{self, HugeTlbController};
-use cgroups_rs::fs::Cgroup;
+use cgroups_rs::fs::{Cgroup, Controller};
+use std::collections::BTreeSet;
 use std::fs;
 
 #[test]
@@ -42,3 +43,30 @@ fn test_hugetlb_sizes() {
 fn assert_no_error(r: Result<u64>) {
     assert!(r.is_ok())
 }
+
+#[test]
+fn test_hugetlb_cgroup_sizes() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_hugetlb_cgroup_sizes")).unwrap();
+    {
+        if let Some(hugetlb_controller) = cg.controller_of::<HugeTlbController>() {
+            let sizes = hugetlb_controller.sizes().unwrap();
+            // the order of the sizes differs, from the control files and from sysfs
+            let host_sizes = hugetlb::supported_page_sizes().unwrap();
+            assert_eq!(
+                sizes.iter().collect::<BTreeSet<_>>(),
+                host_sizes.iter().collect::<BTreeSet<_>>()
+            );
+
+            for size in sizes {
+                assert_no_error(hugetlb_controller.limit_in_bytes(&size));
+                assert_no_error(hugetlb_controller.usage_in_bytes(&size));
+                assert_no_error(hugetlb_controller.failcnt(&size));
+                if hugetlb_controller.v2() {
+                    assert!(hugetlb_controller.max_usage_in_bytes(&size).is_err());
+                }
+            }
+        }
+    }
+    cg.delete().unwrap();
+}
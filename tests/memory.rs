@@ -103,3 +103,314 @@ fn set_mem_v2() {
 
     cg.delete().unwrap();
 }
+
+#[test]
+fn set_swappiness_v1() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    if h.v2() {
+        return;
+    }
+
+    let cg = Cgroup::new(h, String::from("set_swappiness_v1")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        mem_controller.set_swappiness(30).unwrap();
+        assert_eq!(mem_controller.swappiness().unwrap(), 30);
+
+        assert!(mem_controller.set_swappiness(101).is_err());
+        assert_eq!(mem_controller.swappiness().unwrap(), 30);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_page_faults() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_page_faults")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        let pgfault = mem_controller.pgfault().unwrap();
+        let pgmajfault = mem_controller.pgmajfault().unwrap();
+        assert!(pgmajfault <= pgfault);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_write_and_verify() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_write_and_verify")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        let file = if mem_controller.v2() {
+            "memory.max"
+        } else {
+            "memory.limit_in_bytes"
+        };
+
+        // memory limits are rounded down to the page size
+        let page_size = nix::unistd::sysconf(nix::unistd::SysconfVar::PAGE_SIZE)
+            .unwrap()
+            .unwrap();
+        let stored = mem_controller
+            .write_and_verify(file, &(page_size * 100 + 1).to_string())
+            .unwrap();
+        assert_eq!(stored, Some((page_size * 100).to_string()));
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn kmem_usage_v1() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("kmem_usage_v1")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        if mem_controller.v2() {
+            assert!(mem_controller.kmem_usage().is_err());
+        } else {
+            let usage = mem_controller.kmem_usage().unwrap();
+            assert!(mem_controller.kmem_max_usage().unwrap() >= usage);
+            assert!(mem_controller.kmem_limit().is_ok());
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_utilization() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_utilization")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        let util = mem_controller.utilization().unwrap();
+        assert_eq!(util.max, None);
+        assert_eq!(util.percent_of_max, None);
+
+        mem_controller.set_limit(1024 * 1024 * 1024).unwrap();
+        let util = mem_controller.utilization().unwrap();
+        assert_eq!(util.max, Some(1024 * 1024 * 1024));
+        assert!(util.percent_of_max.is_some());
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_kernel_memory_stat() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_kernel_memory_stat")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        if mem_controller.v2() {
+            let stat = mem_controller.kernel_memory_stat().unwrap();
+            assert!(stat.slab.is_some());
+            assert!(stat.kernel_stack.is_some());
+        } else {
+            assert!(mem_controller.kernel_memory_stat().is_err());
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_memory_breakdown() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_memory_breakdown")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        let breakdown = mem_controller.memory_breakdown().unwrap();
+        assert!(breakdown.shmem <= breakdown.file);
+        assert_eq!(mem_controller.anon().unwrap(), breakdown.anon);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_set_limits() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_set_limits")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        let gib = 1024 * 1024 * 1024;
+        if mem_controller.v2() {
+            let res = mem_controller.set_limits(Some(gib / 2), Some(gib)).unwrap();
+            assert_eq!(res.high, Some(MaxValue::Value(gib / 2)));
+            assert_eq!(res.max, Some(MaxValue::Value(gib)));
+
+            let res = mem_controller
+                .set_limits(Some(gib / 4), Some(gib / 2))
+                .unwrap();
+            assert_eq!(res.high, Some(MaxValue::Value(gib / 4)));
+            assert_eq!(res.max, Some(MaxValue::Value(gib / 2)));
+
+            assert!(mem_controller.set_limits(Some(gib), None).is_err());
+
+            let res = mem_controller.set_limits(Some(-1), Some(-1)).unwrap();
+            assert_eq!(res.high, Some(MaxValue::Max));
+            assert_eq!(res.max, Some(MaxValue::Max));
+        } else {
+            assert!(mem_controller.set_limits(Some(gib / 2), Some(gib)).is_err());
+
+            let res = mem_controller.set_limits(None, Some(gib)).unwrap();
+            assert_eq!(res.high, None);
+            assert_eq!(res.max, Some(MaxValue::Value(gib)));
+
+            let res = mem_controller.set_limits(None, Some(-1)).unwrap();
+            assert_eq!(res.max, Some(MaxValue::Max));
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_oom_group() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_oom_group")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        if mem_controller.v2() {
+            mem_controller.set_oom_group(true).unwrap();
+            assert!(mem_controller.oom_group().unwrap());
+            assert_eq!(mem_controller.group_oom_count().unwrap(), 0);
+        } else {
+            assert!(mem_controller.set_oom_group(true).is_err());
+            assert!(mem_controller.group_oom_count().is_err());
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_swap_high() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_swap_high")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        if mem_controller.v2() {
+            assert_eq!(mem_controller.swap_high().unwrap(), -1);
+            mem_controller.set_swap_high(64 * 1024 * 1024).unwrap();
+            assert_eq!(mem_controller.swap_high().unwrap(), 64 * 1024 * 1024);
+            mem_controller.set_swap_high(-1).unwrap();
+            assert_eq!(mem_controller.swap_high().unwrap(), -1);
+        } else {
+            assert!(mem_controller.set_swap_high(0).is_err());
+            assert!(mem_controller.swap_high().is_err());
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_mem_high() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_mem_high")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        if mem_controller.v2() {
+            assert_eq!(mem_controller.mem_high().unwrap(), MaxValue::Max);
+            mem_controller.set_limit(128 * 1024 * 1024).unwrap();
+            mem_controller
+                .set_mem_high(MaxValue::Value(64 * 1024 * 1024))
+                .unwrap();
+            assert_eq!(
+                mem_controller.mem_high().unwrap(),
+                MaxValue::Value(64 * 1024 * 1024)
+            );
+            assert_eq!(
+                mem_controller.get_mem().unwrap().max,
+                Some(MaxValue::Value(128 * 1024 * 1024))
+            );
+            mem_controller.set_mem_high(MaxValue::Max).unwrap();
+            assert_eq!(mem_controller.mem_high().unwrap(), MaxValue::Max);
+        } else {
+            assert!(mem_controller.set_mem_high(MaxValue::Max).is_err());
+            assert!(mem_controller.mem_high().is_err());
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_swap_max() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_swap_max")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        if !mem_controller.v2() {
+            assert!(mem_controller.set_swap_max(0).is_err());
+            assert!(mem_controller.swap_max().is_err());
+            assert!(mem_controller.memory_swap_current().is_err());
+            assert!(mem_controller.swap_events().is_err());
+        } else if mem_controller.path().join("memory.swap.max").exists() {
+            assert_eq!(mem_controller.swap_max().unwrap(), -1);
+            mem_controller.set_swap_max(0).unwrap();
+            assert_eq!(mem_controller.swap_max().unwrap(), 0);
+            assert_eq!(mem_controller.memory_swap_current().unwrap(), 0);
+            assert_eq!(mem_controller.swap_events().unwrap().fail, 0);
+            mem_controller.set_swap_max(-1).unwrap();
+            assert_eq!(mem_controller.memswap().limit_in_bytes, -1);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_memory_events() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_memory_events")).unwrap();
+    {
+        // nothing happened in a new control group, on v1 as on v2
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        assert_eq!(mem_controller.memory_events().unwrap(), Default::default());
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_watch_memory_events() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_watch_memory_events")).unwrap();
+    let rx = {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        if !mem_controller.v2() {
+            assert!(mem_controller.watch_memory_events().is_err());
+            None
+        } else {
+            Some(mem_controller.watch_memory_events().unwrap())
+        }
+    };
+    cg.delete().unwrap();
+
+    // The watcher stops once the control group is gone.
+    if let Some(rx) = rx {
+        let res = rx.recv_timeout(std::time::Duration::from_secs(5));
+        assert_eq!(res, Err(std::sync::mpsc::RecvTimeoutError::Disconnected));
+    }
+}
+
+#[test]
+fn test_last_oom_kill_count() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_last_oom_kill_count")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        assert_eq!(mem_controller.last_oom_kill_count().unwrap(), 0);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_throttle_time() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_throttle_time")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        if !mem_controller.v2() {
+            assert!(mem_controller.throttle_time().is_err());
+        } else if mem_controller.path().join("memory.pressure").exists() {
+            // nothing ran in the control group
+            assert_eq!(mem_controller.throttle_time().unwrap().as_micros(), 0);
+        }
+    }
+    cg.delete().unwrap();
+}
@@ -0,0 +1,77 @@
+// Copyright (c) 2020 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0 or MIT
+//
+
+//! Integration tests about the misc subsystem
+use cgroups_rs::fs::cgroup_builder::CgroupBuilder;
+use cgroups_rs::fs::misc::MiscController;
+use cgroups_rs::fs::{Cgroup, MaxValue};
+
+#[test]
+fn test_misc_set_max_unknown_key() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_misc_set_max_unknown_key")).unwrap();
+    {
+        // the misc controller is only available on recent v2 hosts
+        if let Some(misc) = cg.controller_of::<MiscController>() {
+            let err = misc
+                .set_max("no_such_resource", MaxValue::Value(1))
+                .unwrap_err();
+            assert!(err.to_string().contains("no_such_resource"));
+            assert!(misc.clear("no_such_resource").is_err());
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_misc_capacity() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_misc_capacity")).unwrap();
+    {
+        if let Some(misc) = cg.controller_of::<MiscController>() {
+            for key in misc.capacity().unwrap().keys() {
+                misc.clear(key).unwrap();
+            }
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_misc_res_build() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let v2 = h.v2();
+    let cg = CgroupBuilder::new("test_misc_res_build")
+        .misc()
+        .max("sev".to_string(), MaxValue::Value(1))
+        .max("sev".to_string(), MaxValue::Value(2))
+        .done()
+        .build(h);
+    if !v2 {
+        // no misc controller on v1, the limits aren't applied
+        cg.unwrap().delete().unwrap();
+        return;
+    }
+    let cg = match cg {
+        Ok(cg) => cg,
+        // the host has no SEV
+        Err(_) => {
+            let h = cgroups_rs::fs::hierarchies::auto();
+            Cgroup::load(h, "test_misc_res_build").delete().unwrap();
+            return;
+        }
+    };
+    {
+        if let Some(misc) = cg.controller_of::<MiscController>() {
+            assert!(misc
+                .max()
+                .unwrap()
+                .contains(&("sev".to_string(), MaxValue::Value(2))));
+            assert_eq!(misc.current().unwrap().get("sev").copied().unwrap_or(0), 0);
+            assert_eq!(misc.events().unwrap().get("sev").copied().unwrap_or(0), 0);
+        }
+    }
+    cg.delete().unwrap();
+}
@@ -0,0 +1,26 @@
+// Copyright (c) 2020 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0 or MIT
+//
+
+//! Integration tests about the net_cls subsystem
+use cgroups_rs::fs::net_cls::NetClsController;
+use cgroups_rs::fs::Cgroup;
+
+#[test]
+fn test_net_cls_class() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_net_cls_class")).unwrap();
+    {
+        // the net_cls controller only exists on v1, and isn't always mounted
+        if let Some(net_cls) = cg.controller_of::<NetClsController>() {
+            net_cls.set_class_major_minor(0x10, 0x1).unwrap();
+            assert_eq!(net_cls.get_class().unwrap(), 0x100001);
+            assert_eq!(net_cls.get_class_major_minor().unwrap(), (0x10, 0x1));
+
+            net_cls.set_class(0).unwrap();
+            assert_eq!(net_cls.get_class_major_minor().unwrap(), (0, 0));
+        }
+    }
+    cg.delete().unwrap();
+}
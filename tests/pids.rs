@@ -7,9 +7,11 @@
 //! Integration tests about the pids subsystem
 use cgroups_rs::fs::pid::PidController;
 use cgroups_rs::fs::{Cgroup, Controller, MaxValue};
+use cgroups_rs::CgroupPid;
 use libc::pid_t;
 use nix::sys::wait::{waitpid, WaitStatus};
 use nix::unistd::{fork, ForkResult};
+use std::process::Command;
 
 #[test]
 fn create_and_delete_cgroup() {
@@ -98,3 +100,63 @@ fn test_pid_events_is_not_zero() {
     }
     cg.delete().unwrap();
 }
+
+#[test]
+fn test_pid_effective_max() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let parent = Cgroup::new(h, String::from("test_pid_effective_max")).unwrap();
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_pid_effective_max/child")).unwrap();
+    {
+        let parent_pids: &PidController = parent.controller_of().unwrap();
+        let pids: &PidController = cg.controller_of().unwrap();
+        assert_eq!(pids.effective_max().unwrap(), MaxValue::Max);
+
+        parent_pids.set_pid_max(MaxValue::Value(20)).unwrap();
+        assert_eq!(pids.effective_max().unwrap(), MaxValue::Value(20));
+
+        pids.set_pid_max(MaxValue::Value(10)).unwrap();
+        assert_eq!(pids.effective_max().unwrap(), MaxValue::Value(10));
+        assert_eq!(parent_pids.effective_max().unwrap(), MaxValue::Value(20));
+    }
+    cg.delete().unwrap();
+    parent.delete().unwrap();
+}
+
+#[test]
+fn test_pid_fork_pressure() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_pid_fork_pressure")).unwrap();
+    {
+        let pids: &PidController = cg.controller_of().unwrap();
+        let pressure = pids
+            .fork_pressure(std::time::Duration::from_millis(50))
+            .unwrap();
+        assert_eq!(pressure, 0.0);
+
+        // processes joining while sampling count as created
+        let sampled = pids.clone();
+        let sampler = std::thread::spawn(move || {
+            sampled.fork_pressure(std::time::Duration::from_millis(500))
+        });
+        let earlier = pids.sample().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let mut children: Vec<_> = (0..3)
+            .map(|_| Command::new("sleep").arg("infinity").spawn().unwrap())
+            .collect();
+        for child in &children {
+            cg.add_task_by_tgid(CgroupPid::from(child.id() as u64))
+                .unwrap();
+        }
+        let later = pids.sample().unwrap();
+        assert_eq!(later.current, earlier.current + 3);
+        assert!(later.fork_rate_since(&earlier) > 0.0);
+        assert!(sampler.join().unwrap().unwrap() > 0.0);
+
+        for child in &mut children {
+            child.kill().unwrap();
+            child.wait().unwrap();
+        }
+    }
+    cg.delete().unwrap();
+}
@@ -0,0 +1,64 @@
+// Copyright (c) 2020 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0 or MIT
+//
+
+//! Integration tests about the rdma subsystem
+use cgroups_rs::fs::cgroup_builder::CgroupBuilder;
+use cgroups_rs::fs::rdma::{RdmaController, RdmaLimit};
+use cgroups_rs::fs::Cgroup;
+
+#[test]
+fn test_rdma_max() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_rdma_max")).unwrap();
+    {
+        // the rdma controller is only available on hosts with RDMA/IB devices support
+        if let Some(rdma) = cg.controller_of::<RdmaController>() {
+            let limit = RdmaLimit {
+                hca_handle: Some(2),
+                hca_object: None,
+            };
+            for device in rdma.max_parsed().unwrap().keys() {
+                rdma.set_max_limits(device, limit).unwrap();
+                assert_eq!(rdma.max_parsed().unwrap()[device], limit);
+                rdma.set_unlimited(device).unwrap();
+                assert_eq!(rdma.max_parsed().unwrap()[device], RdmaLimit::default());
+            }
+            for device in rdma.current_parsed().unwrap().keys() {
+                assert!(rdma.max_parsed().unwrap().contains_key(device));
+            }
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_rdma_res_build() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let limit = RdmaLimit {
+        hca_handle: Some(2),
+        hca_object: Some(2000),
+    };
+    let cg = CgroupBuilder::new("test_rdma_res_build")
+        .rdma()
+        .max("mlx4_0".to_string(), RdmaLimit::default())
+        .max("mlx4_0".to_string(), limit)
+        .done()
+        .build(h);
+    let cg = match cg {
+        Ok(cg) => cg,
+        // the host has no such device
+        Err(_) => {
+            let h = cgroups_rs::fs::hierarchies::auto();
+            Cgroup::load(h, "test_rdma_res_build").delete().unwrap();
+            return;
+        }
+    };
+    {
+        if let Some(rdma) = cg.controller_of::<RdmaController>() {
+            assert_eq!(rdma.max_parsed().unwrap()["mlx4_0"], limit);
+        }
+    }
+    cg.delete().unwrap();
+}
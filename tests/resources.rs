@@ -5,8 +5,13 @@
 //
 
 //! Integration test about setting resources using `apply()`
+use cgroups_rs::fs::cpu::{shares_to_weight, weight_to_shares, CpuController};
+use cgroups_rs::fs::error::ErrorKind;
+use cgroups_rs::fs::memory::MemController;
 use cgroups_rs::fs::pid::PidController;
-use cgroups_rs::fs::{Cgroup, MaxValue, PidResources, Resources};
+use cgroups_rs::fs::{
+    Cgroup, Controller, CpuResources, MaxValue, MemoryResources, PidResources, Resources,
+};
 
 #[test]
 fn pid_resources() {
@@ -29,3 +34,438 @@ fn pid_resources() {
     }
     cg.delete().unwrap();
 }
+
+#[test]
+fn reconfigure_atomic_raises_memory_limits() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    if h.v2() {
+        return;
+    }
+
+    let cg = Cgroup::new(h, String::from("reconfigure_atomic_raises_memory_limits")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        let (small, large) = (256 * 1024 * 1024, 512 * 1024 * 1024);
+        mem_controller.set_limit(small).unwrap();
+        mem_controller.set_memswap_limit(small).unwrap();
+
+        // The memory limit can't go above memsw, so it has to be raised second.
+        let res = Resources {
+            memory: MemoryResources {
+                memory_hard_limit: Some(large),
+                memory_swap_limit: Some(large),
+                ..Default::default()
+            },
+            pid: PidResources {
+                maximum_number_of_processes: Some(MaxValue::Value(128)),
+            },
+            ..Default::default()
+        };
+        cg.reconfigure_atomic(&res).unwrap();
+
+        assert_eq!(mem_controller.memory_stat().limit_in_bytes, large);
+        assert_eq!(mem_controller.memswap().limit_in_bytes, large);
+        let pidcontroller: &PidController = cg.controller_of().unwrap();
+        assert_eq!(pidcontroller.get_pid_max().unwrap(), MaxValue::Value(128));
+
+        // And lowered in the opposite order.
+        let res = Resources {
+            memory: MemoryResources {
+                memory_hard_limit: Some(small),
+                memory_swap_limit: Some(small),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        cg.reconfigure_atomic(&res).unwrap();
+
+        assert_eq!(mem_controller.memory_stat().limit_in_bytes, small);
+        assert_eq!(mem_controller.memswap().limit_in_bytes, small);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn apply_on_root_cgroup_is_rejected() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let root = h.root_control_group();
+    assert!(root.is_root());
+
+    let res = Resources {
+        memory: MemoryResources {
+            memory_hard_limit: Some(1024 * 1024 * 1024),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let err = root.apply(&res).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::RootCgroupNotConfigurable(file)
+            if file == "memory.max" || file == "memory.limit_in_bytes"
+    ));
+
+    let mem_controller: &MemController = root.controller_of().unwrap();
+    let err = mem_controller.set_limit(1024 * 1024 * 1024).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::RootCgroupNotConfigurable(_)
+    ));
+}
+
+#[test]
+fn reset_all_restores_defaults() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("reset_all_restores_defaults")).unwrap();
+    {
+        let res = Resources {
+            memory: MemoryResources {
+                memory_hard_limit: Some(256 * 1024 * 1024),
+                ..Default::default()
+            },
+            pid: PidResources {
+                maximum_number_of_processes: Some(MaxValue::Value(64)),
+            },
+            cpu: CpuResources {
+                shares: Some(512),
+                quota: Some(50000),
+                period: Some(200000),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        cg.apply(&res).unwrap();
+
+        cg.reset_all().unwrap();
+
+        let pid_controller: &PidController = cg.controller_of().unwrap();
+        assert_eq!(pid_controller.get_pid_max().unwrap(), MaxValue::Max);
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        assert_eq!(mem_controller.utilization().unwrap().max, None);
+        let cpu_controller: &CpuController = cg.controller_of().unwrap();
+        assert_eq!(cpu_controller.cfs_quota().unwrap(), -1);
+        assert_eq!(cpu_controller.cfs_period().unwrap(), 100000);
+        let default_shares = if cg.v2() { 100 } else { 1024 };
+        assert_eq!(cpu_controller.shares().unwrap(), default_shares);
+    }
+    cg.delete().unwrap();
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let root = Cgroup::load(h, "");
+    assert!(matches!(
+        root.reset_all().unwrap_err().kind(),
+        ErrorKind::RootCgroupNotConfigurable(_)
+    ));
+}
+
+#[test]
+fn read_resources_round_trip() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("read_resources_round_trip")).unwrap();
+    {
+        let res = Resources {
+            memory: MemoryResources {
+                memory_hard_limit: Some(256 * 1024 * 1024),
+                ..Default::default()
+            },
+            pid: PidResources {
+                maximum_number_of_processes: Some(MaxValue::Value(64)),
+            },
+            cpu: CpuResources {
+                shares: Some(512),
+                quota: Some(50000),
+                period: Some(100000),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        cg.apply(&res).unwrap();
+
+        let read = cg.read_resources().unwrap();
+        assert_eq!(read.memory.memory_hard_limit, Some(256 * 1024 * 1024));
+        assert_eq!(
+            read.pid.maximum_number_of_processes,
+            Some(MaxValue::Value(64))
+        );
+        // the shares go through a lossy conversion to a weight and back on v2
+        let shares = if cg.v2() {
+            weight_to_shares(shares_to_weight(512))
+        } else {
+            512
+        };
+        assert_eq!(read.cpu.shares, Some(shares));
+        assert_eq!(read.cpu.quota, Some(50000));
+        assert_eq!(read.cpu.period, Some(100000));
+
+        // applying what was read back changes nothing
+        cg.apply(&read).unwrap();
+        assert_eq!(cg.read_resources().unwrap(), read);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn snapshot_resources_matches_read_resources() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("snapshot_resources_matches_read_resources")).unwrap();
+    {
+        let res = Resources {
+            pid: PidResources {
+                maximum_number_of_processes: Some(MaxValue::Value(32)),
+            },
+            ..Default::default()
+        };
+        cg.apply(&res).unwrap();
+
+        let snapshot = cg.snapshot_resources();
+        assert_eq!(
+            snapshot.pid.maximum_number_of_processes,
+            Some(MaxValue::Value(32))
+        );
+        assert_eq!(snapshot, cg.read_resources().unwrap());
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn apply_with_timeout_completes() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("apply_with_timeout_completes")).unwrap();
+    {
+        let res = Resources {
+            pid: PidResources {
+                maximum_number_of_processes: Some(MaxValue::Value(256)),
+            },
+            ..Default::default()
+        };
+        cg.apply_with_timeout(&res, std::time::Duration::from_secs(10))
+            .unwrap();
+
+        let pidcontroller: &PidController = cg.controller_of().unwrap();
+        assert_eq!(pidcontroller.get_pid_max().unwrap(), MaxValue::Value(256));
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn create_tree_applies_to_leaf() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let res = Resources {
+        pid: PidResources {
+            maximum_number_of_processes: Some(MaxValue::Value(128)),
+        },
+        ..Default::default()
+    };
+    let cg = Cgroup::create_tree(h, "create_tree_applies_to_leaf/a/b", &res).unwrap();
+    {
+        let pidcontroller: &PidController = cg.controller_of().unwrap();
+        assert_eq!(pidcontroller.get_pid_max().unwrap(), MaxValue::Value(128));
+
+        let parent = cg.parent_control_group();
+        assert!(parent.exists());
+        let pidcontroller: &PidController = parent.controller_of().unwrap();
+        assert_eq!(pidcontroller.get_pid_max().unwrap(), MaxValue::Max);
+    }
+    let parent = cg.parent_control_group();
+    let top = parent.parent_control_group();
+    cg.delete().unwrap();
+    parent.delete().unwrap();
+    top.delete().unwrap();
+}
+
+#[test]
+fn create_tree_rolls_back_on_failure() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let existing = Cgroup::new(h, "create_tree_rolls_back_on_failure").unwrap();
+
+    // rejected by the kernel
+    let res = Resources {
+        pid: PidResources {
+            maximum_number_of_processes: Some(MaxValue::Value(-5)),
+        },
+        ..Default::default()
+    };
+    let h = cgroups_rs::fs::hierarchies::auto();
+    assert!(Cgroup::create_tree(h, "create_tree_rolls_back_on_failure/a/b", &res).is_err());
+
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let created = Cgroup::load(h, "create_tree_rolls_back_on_failure/a");
+    assert!(!created.exists());
+    assert!(existing.exists());
+    existing.delete().unwrap();
+}
+
+#[test]
+fn apply_transactional_rolls_back() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("apply_transactional_rolls_back")).unwrap();
+    {
+        let mem: &MemController = cg.controller_of().unwrap();
+        let limit_before = mem.memory_stat().limit_in_bytes;
+
+        // the memory limit is applied before the pids limit, which the kernel rejects
+        let res = Resources {
+            memory: MemoryResources {
+                memory_hard_limit: Some(64 * 1024 * 1024),
+                ..Default::default()
+            },
+            pid: PidResources {
+                maximum_number_of_processes: Some(MaxValue::Value(-5)),
+            },
+            ..Default::default()
+        };
+        let err = cg.apply_transactional(&res).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::ApplyRolledBack {
+                failed: "pids".to_string(),
+                not_restored: vec![],
+            }
+        );
+        assert_eq!(mem.memory_stat().limit_in_bytes, limit_before);
+
+        let res = Resources {
+            memory: MemoryResources {
+                memory_hard_limit: Some(64 * 1024 * 1024),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        cg.apply_transactional(&res).unwrap();
+        assert_eq!(mem.memory_stat().limit_in_bytes, 64 * 1024 * 1024);
+    }
+    cg.delete().unwrap();
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_resources_serde_round_trip() {
+    use cgroups_rs::fs::devices::{DevicePermissions, DeviceType};
+    use cgroups_rs::fs::rdma::RdmaLimit;
+    use cgroups_rs::fs::{
+        BlkIoDeviceThrottleResource, DeviceResource, HugePageResource, MiscResource,
+        NetworkPriority, RdmaResource,
+    };
+
+    let mut res = Resources {
+        memory: MemoryResources {
+            memory_hard_limit: Some(1024 * 1024 * 1024),
+            swappiness: Some(10),
+            ..Default::default()
+        },
+        pid: PidResources {
+            maximum_number_of_processes: Some(MaxValue::Max),
+        },
+        cpu: CpuResources {
+            cpus: Some("0-1".to_string()),
+            quota: Some(50000),
+            period: Some(100000),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    res.devices.devices.push(DeviceResource {
+        allow: true,
+        devtype: DeviceType::Char,
+        major: 1,
+        minor: 3,
+        access: vec![DevicePermissions::Read, DevicePermissions::Write],
+    });
+    res.network.class_id = Some(1337);
+    res.network.priorities.push(NetworkPriority {
+        name: "lo".to_string(),
+        priority: 5,
+    });
+    res.hugepages.limits.push(HugePageResource {
+        size: "2MB".to_string(),
+        limit: 4 * 2 * 1024 * 1024,
+    });
+    res.blkio.weight = Some(100);
+    res.blkio
+        .throttle_read_bps_device
+        .push(BlkIoDeviceThrottleResource {
+            major: 8,
+            minor: 0,
+            rate: 1048576,
+        });
+    res.misc.limits.push(MiscResource {
+        key: "sev".to_string(),
+        max: MaxValue::Value(2),
+    });
+    res.rdma.limits.push(RdmaResource {
+        device: "mlx4_0".to_string(),
+        limit: RdmaLimit {
+            hca_handle: Some(2),
+            hca_object: None,
+        },
+    });
+    res.cpu
+        .attrs
+        .insert("cpu.idle".to_string(), "1".to_string());
+
+    let json = serde_json::to_string(&res).unwrap();
+    let back: Resources = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, res);
+
+    // the field names are part of the format
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["memory"]["memory_hard_limit"], 1024 * 1024 * 1024);
+    assert_eq!(value["network"]["priorities"][0]["name"], "lo");
+    assert_eq!(value["rdma"]["limits"][0]["device"], "mlx4_0");
+}
+
+#[test]
+fn apply_translates_between_versions() {
+    let h = cgroups_rs::fs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("apply_translates_between_versions")).unwrap();
+    {
+        let mib = 1024 * 1024;
+        let res = Resources {
+            memory: MemoryResources {
+                memory_hard_limit: Some(256 * mib),
+                memory_swap_limit: Some(384 * mib),
+                ..Default::default()
+            },
+            cpu: CpuResources {
+                shares: Some(2048),
+                quota: Some(50000),
+                period: Some(100000),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        cg.apply(&res).unwrap();
+
+        let cpu: &CpuController = cg.controller_of().unwrap();
+        let mem: &MemController = cg.controller_of().unwrap();
+        if cg.v2() {
+            assert_eq!(cpu.shares().unwrap(), shares_to_weight(2048));
+        } else {
+            assert_eq!(cpu.shares().unwrap(), 2048);
+        }
+        assert_eq!(cpu.cfs_quota().unwrap(), 50000);
+        assert_eq!(cpu.cfs_period().unwrap(), 100000);
+
+        // without swap accounting there is no memory+swap limit to check
+        let swap_file = if cg.v2() {
+            "memory.swap.max"
+        } else {
+            "memory.memsw.limit_in_bytes"
+        };
+        let swap_accounting = mem.path().join(swap_file).exists();
+        if swap_accounting {
+            if cg.v2() {
+                assert_eq!(mem.swap_max().unwrap(), 128 * mib);
+            }
+            assert_eq!(mem.memswap_limit().unwrap(), 384 * mib);
+        }
+
+        let read = cg.read_resources().unwrap();
+        let read_shares = read.cpu.shares.unwrap();
+        assert_eq!(shares_to_weight(read_shares), shares_to_weight(2048));
+        if swap_accounting {
+            assert_eq!(read.memory.memory_swap_limit, Some(384 * mib));
+        }
+    }
+    cg.delete().unwrap();
+}